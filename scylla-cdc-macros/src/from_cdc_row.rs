@@ -0,0 +1,204 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Field, Fields, FieldsNamed, Ident, Lit, Meta, NestedMeta};
+
+const METADATA_COLUMNS: [&str; 6] = [
+    "stream_id",
+    "time",
+    "batch_seq_no",
+    "end_of_batch",
+    "operation",
+    "ttl",
+];
+
+// Describes where the value of a field is taken from.
+enum FieldSource {
+    // Value of a column from the logged table.
+    Value(String),
+    // Information if a value of a column was deleted.
+    Deleted(String),
+    // Elements deleted from a collection column.
+    DeletedElements(String),
+    // One of the CDC metadata columns, accessed as a member of CDCRow.
+    Metadata(Ident),
+}
+
+/// #[derive(FromCDCRow)] derives FromCDCRow for struct
+pub fn from_cdc_row_derive(tokens_input: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(tokens_input as DeriveInput);
+
+    match generate(&item) {
+        Ok(generated) => generated.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn generate(item: &DeriveInput) -> syn::Result<TokenStream2> {
+    let struct_fields = parse_named_fields(item)?;
+    let struct_name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let mut sources = Vec::with_capacity(struct_fields.named.len());
+    for field in struct_fields.named.iter() {
+        sources.push((field, parse_field_source(field)?));
+    }
+
+    // Metadata is moved out of the row, so it has to be read after all other columns.
+    let (metadata_fields, column_fields): (Vec<_>, Vec<_>) = sources
+        .iter()
+        .partition(|(_, source)| matches!(source, FieldSource::Metadata(_)));
+
+    let read_fields_code = column_fields
+        .iter()
+        .chain(metadata_fields.iter())
+        .map(|(field, source)| read_field(field, source));
+
+    let field_names = struct_fields.named.iter().map(|field| &field.ident);
+    let field_vars = struct_fields.named.iter().map(field_var);
+
+    Ok(quote! {
+        impl #impl_generics scylla_cdc::_macro_internal::FromCDCRow for #struct_name #ty_generics #where_clause {
+            fn from_cdc_row(mut row: scylla_cdc::_macro_internal::CDCRow<'_>)
+            -> Result<Self, scylla_cdc::_macro_internal::FromCDCRowError> {
+                use scylla_cdc::_macro_internal::{CqlValue, FromCDCRowError, FromCqlVal};
+
+                #(#read_fields_code)*
+
+                Ok(#struct_name {
+                    #(#field_names: #field_vars,)*
+                })
+            }
+        }
+    })
+}
+
+fn parse_named_fields(item: &DeriveInput) -> syn::Result<&FieldsNamed> {
+    match &item.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named_fields) => Ok(named_fields),
+            _ => Err(syn::Error::new(
+                item.span(),
+                "derive(FromCDCRow) works only for structs with named fields",
+            )),
+        },
+        _ => Err(syn::Error::new(
+            item.span(),
+            "derive(FromCDCRow) works only on structs",
+        )),
+    }
+}
+
+fn parse_field_source(field: &Field) -> syn::Result<FieldSource> {
+    let field_name = field.ident.as_ref().unwrap();
+    let mut source = None;
+
+    for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("cdc")) {
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            meta => return Err(syn::Error::new(meta.span(), "expected #[cdc(...)]")),
+        };
+
+        for meta in nested {
+            let parsed = match &meta {
+                NestedMeta::Meta(Meta::Path(path)) => {
+                    match path.get_ident().filter(|ident| is_metadata_column(ident)) {
+                        Some(ident) => FieldSource::Metadata(ident.clone()),
+                        None => return Err(unknown_attribute(&meta)),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value)) => {
+                    let column = match &name_value.lit {
+                        Lit::Str(column) => column.value(),
+                        lit => return Err(syn::Error::new(lit.span(), "expected string literal")),
+                    };
+                    if name_value.path.is_ident("rename") {
+                        FieldSource::Value(column)
+                    } else if name_value.path.is_ident("deleted") {
+                        FieldSource::Deleted(column)
+                    } else if name_value.path.is_ident("deleted_elements") {
+                        FieldSource::DeletedElements(column)
+                    } else {
+                        return Err(unknown_attribute(&meta));
+                    }
+                }
+                _ => return Err(unknown_attribute(&meta)),
+            };
+
+            if source.replace(parsed).is_some() {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "only one #[cdc(...)] attribute is allowed per field",
+                ));
+            }
+        }
+    }
+
+    Ok(source.unwrap_or_else(|| FieldSource::Value(field_name.to_string())))
+}
+
+fn is_metadata_column(ident: &Ident) -> bool {
+    METADATA_COLUMNS.iter().any(|column| ident == column)
+}
+
+fn unknown_attribute(meta: &NestedMeta) -> syn::Error {
+    syn::Error::new(
+        meta.span(),
+        format!(
+            "unknown attribute, expected one of: rename = \"...\", deleted = \"...\", \
+            deleted_elements = \"...\", {}",
+            METADATA_COLUMNS.join(", ")
+        ),
+    )
+}
+
+fn field_var(field: &Field) -> Ident {
+    format_ident!("field_{}", field.ident.as_ref().unwrap())
+}
+
+// Generates tokens assigning the value of the field to a local variable.
+fn read_field(field: &Field, source: &FieldSource) -> TokenStream2 {
+    let var = field_var(field);
+    let field_type = &field.ty;
+
+    match source {
+        FieldSource::Value(column) => quote_spanned! {field.span() =>
+            if !row.column_exists(#column) {
+                return Err(FromCDCRowError::ColumnNotFound(#column.to_string()));
+            }
+            let #var = <#field_type as FromCqlVal<Option<CqlValue>>>::from_cql(row.take_value(#column))
+                .map_err(|err| FromCDCRowError::BadCqlVal {
+                    err,
+                    column: #column.to_string(),
+                })?;
+        },
+        FieldSource::Deleted(column) => {
+            let cdc_column = format!("cdc$deleted_{}", column);
+            quote_spanned! {field.span() =>
+                if !row.column_deletable(#column) {
+                    return Err(FromCDCRowError::ColumnNotFound(#cdc_column.to_string()));
+                }
+                let #var: #field_type = row.is_value_deleted(#column);
+            }
+        }
+        FieldSource::DeletedElements(column) => {
+            let cdc_column = format!("cdc$deleted_elements_{}", column);
+            quote_spanned! {field.span() =>
+                if !row.collection_exists(#column) {
+                    return Err(FromCDCRowError::ColumnNotFound(#cdc_column.to_string()));
+                }
+                let #var = <#field_type as FromCqlVal<CqlValue>>::from_cql(
+                    CqlValue::Set(row.take_deleted_elements(#column)),
+                )
+                .map_err(|err| FromCDCRowError::BadCqlVal {
+                    err,
+                    column: #cdc_column.to_string(),
+                })?;
+            }
+        }
+        FieldSource::Metadata(member) => quote_spanned! {field.span() =>
+            let #var: #field_type = row.#member;
+        },
+    }
+}
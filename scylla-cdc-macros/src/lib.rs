@@ -0,0 +1,13 @@
+//! Procedural macros for the [scylla-cdc](https://docs.rs/scylla-cdc) crate.
+//! They should be used through the re-exports in `scylla_cdc`, not directly.
+use proc_macro::TokenStream;
+
+mod from_cdc_row;
+
+/// #[derive(FromCDCRow)] derives FromCDCRow for a struct with named fields.
+/// For the list of supported attributes,
+/// please refer to the documentation of `scylla_cdc::consumer::FromCDCRow`.
+#[proc_macro_derive(FromCDCRow, attributes(cdc))]
+pub fn from_cdc_row_derive(tokens_input: TokenStream) -> TokenStream {
+    from_cdc_row::from_cdc_row_derive(tokens_input)
+}
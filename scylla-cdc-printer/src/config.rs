@@ -0,0 +1,38 @@
+//! Settings of the printer loaded from a configuration file and environment variables.
+use anyhow::bail;
+use scylla_cdc::config::{CheckpointConfig, SessionConfig, TimingConfig};
+use serde::Deserialize;
+
+/// Prefix of the environment variables overriding settings of the configuration file.
+pub const ENV_PREFIX: &str = "SCYLLA_CDC_PRINTER_";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub source: SessionConfig,
+    pub keyspace: String,
+    pub table: String,
+    pub timing: TimingConfig,
+    /// Saving the progress of printing, so that the printer resumes where it stopped.
+    pub checkpoints: Option<CheckpointConfig>,
+}
+
+impl Config {
+    /// Checks if the settings are complete and valid.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.keyspace.is_empty() {
+            bail!("keyspace: not set, use --keyspace or the `keyspace` setting");
+        }
+        if self.table.is_empty() {
+            bail!("table: not set, use --table or the `table` setting");
+        }
+        self.source
+            .validate("source")
+            .map_err(|err| err.context("use --hostname or the `source.hosts` setting"))?;
+        self.timing.validate("timing")?;
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.validate("checkpoints")?;
+        }
+        Ok(())
+    }
+}
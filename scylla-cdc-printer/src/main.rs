@@ -1,60 +1,103 @@
+pub mod config;
 pub mod printer;
 
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
 
 use clap::Parser;
-use scylla::SessionBuilder;
+use scylla_cdc::checkpoints::CheckpointNamespace;
 use scylla_cdc::log_reader::CDCLogReaderBuilder;
 
+use crate::config::{Config, ENV_PREFIX};
 use crate::printer::PrinterConsumerFactory;
 
+/// Flags override the settings of the configuration file,
+/// which are overridden by environment variables prefixed with SCYLLA_CDC_PRINTER_.
 #[derive(Parser)]
 struct Args {
+    /// Path of a TOML or YAML configuration file
+    #[clap(short, long, action = clap::ArgAction::Set)]
+    config: Option<PathBuf>,
+
     /// Keyspace name
     #[clap(short, long, action = clap::ArgAction::Set)]
-    keyspace: String,
+    keyspace: Option<String>,
 
     /// Table name
     #[clap(short, long, action = clap::ArgAction::Set)]
-    table: String,
+    table: Option<String>,
 
     /// Address of a node in source cluster
     #[clap(short, long, action = clap::ArgAction::Set)]
-    hostname: String,
+    hostname: Option<String>,
 
-    /// Window size in seconds
-    #[clap(long, default_value_t = 60., action = clap::ArgAction::Set)]
-    window_size: f64,
+    /// Window size in seconds, 60 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    window_size: Option<f64>,
 
-    /// Safety interval in seconds
-    #[clap(long, default_value_t = 30., action = clap::ArgAction::Set)]
-    safety_interval: f64,
+    /// Safety interval in seconds, 30 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    safety_interval: Option<f64>,
 
-    /// Sleep interval in seconds
-    #[clap(long, default_value_t = 10., action = clap::ArgAction::Set)]
-    sleep_interval: f64,
+    /// Sleep interval in seconds, 10 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    sleep_interval: Option<f64>,
+}
+
+impl Args {
+    fn override_config(self, config: &mut Config) {
+        if let Some(keyspace) = self.keyspace {
+            config.keyspace = keyspace;
+        }
+        if let Some(table) = self.table {
+            config.table = table;
+        }
+        if let Some(hostname) = self.hostname {
+            config.source.hosts = vec![hostname];
+        }
+        if let Some(window_size) = self.window_size {
+            config.timing.window_size = window_size;
+        }
+        if let Some(safety_interval) = self.safety_interval {
+            config.timing.safety_interval = safety_interval;
+        }
+        if let Some(sleep_interval) = self.sleep_interval {
+            config.timing.sleep_interval = sleep_interval;
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let session = Arc::new(
-        SessionBuilder::new()
-            .known_node(args.hostname)
-            .build()
-            .await?,
-    );
-    let (mut cdc_log_printer, handle) = CDCLogReaderBuilder::new()
-        .session(session)
-        .keyspace(&args.keyspace)
-        .table_name(&args.table)
-        .window_size(Duration::from_secs_f64(args.window_size))
-        .safety_interval(Duration::from_secs_f64(args.safety_interval))
-        .sleep_interval(Duration::from_secs_f64(args.sleep_interval))
-        .consumer_factory(Arc::new(PrinterConsumerFactory))
-        .build()
-        .await?;
+    let mut config: Config = scylla_cdc::config::load(args.config.as_deref(), ENV_PREFIX)?;
+    args.override_config(&mut config);
+    config.validate()?;
+
+    let session = Arc::new(config.source.build().await?);
+    let mut builder = CDCLogReaderBuilder::new()
+        .session(session.clone())
+        .keyspace(&config.keyspace)
+        .table_name(&config.table)
+        .consumer_factory(Arc::new(PrinterConsumerFactory));
+    builder = config.timing.configure(builder);
+    if let Some(checkpoints) = &config.checkpoints {
+        let namespace =
+            CheckpointNamespace::new("scylla-cdc-printer", &config.keyspace, &config.table);
+        let saver = checkpoints
+            .build_saver(&session, &config.keyspace, namespace)
+            .await?;
+        if saver.load_last_generation().await?.is_some() {
+            // Resume from the saved checkpoints instead of now.
+            builder = builder.start_timestamp(chrono::Duration::zero());
+        }
+        builder = builder
+            .checkpoint_saver(saver)
+            .should_save_progress(true)
+            .should_load_progress(true)
+            .pause_between_saves(checkpoints.pause_between_saves());
+    }
+    let (mut cdc_log_printer, handle) = builder.build().await?;
 
     tokio::signal::ctrl_c().await.unwrap();
 
@@ -0,0 +1,364 @@
+//! Settings of the replicator loaded from a configuration file and environment variables.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use scylla_cdc::config::{CheckpointConfig, CheckpointSaverConfig, SessionConfig, TimingConfig};
+use serde::Deserialize;
+
+use crate::destination_schema::SchemaCreationOptions;
+use crate::replicator::ReplicatedTable;
+use crate::replicator_consumer::{
+    ColumnFilter, ConflictPolicy, ReplicationMode, ReplicationOptions, RowFilter,
+    DEFAULT_MAX_BATCH_SIZE,
+};
+use crate::snapshot::SnapshotOptions;
+
+/// Prefix of the environment variables overriding settings of the configuration file.
+pub const ENV_PREFIX: &str = "SCYLLA_CDC_REPLICATOR_";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub source: SessionConfig,
+    pub destination: SessionConfig,
+    pub keyspace: String,
+    /// Destination keyspace of the tables that don't set their own, the source keyspace by default.
+    pub destination_keyspace: Option<String>,
+    pub tables: Vec<TableConfig>,
+    /// RFC 3339 formatted datetime of the first replicated change, now by default.
+    pub start_datetime: Option<String>,
+    pub timing: TimingConfig,
+    /// Saving the progress of the replication, so that it resumes where it stopped.
+    pub checkpoints: Option<CheckpointConfig>,
+    /// Cluster storing the checkpoint tables, used by the `table` checkpoint saver.
+    pub checkpoint_cluster: Cluster,
+    pub replication: ReplicationConfig,
+    pub create_schema: SchemaCreationConfig,
+    pub snapshot: SnapshotConfig,
+    /// Address of the HTTP endpoint serving Prometheus metrics, requires the `metrics` feature.
+    pub metrics_address: Option<SocketAddr>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cluster {
+    #[default]
+    Source,
+    Destination,
+}
+
+/// Replicated source table.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableConfig {
+    pub name: String,
+    /// Name of the destination table, the name of the source table by default.
+    pub destination: Option<String>,
+    /// Keyspace of the destination table, the `destination_keyspace` setting by default.
+    pub destination_keyspace: Option<String>,
+    /// Maps source column names to destination column names.
+    #[serde(default)]
+    pub column_renames: HashMap<String, String>,
+}
+
+impl TableConfig {
+    pub fn new(name: String) -> TableConfig {
+        TableConfig {
+            name,
+            destination: None,
+            destination_keyspace: None,
+            column_renames: HashMap::new(),
+        }
+    }
+}
+
+/// Settings of applying changes to destination tables, see [`ReplicationOptions`].
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplicationConfig {
+    pub conflict_policy: ConflictPolicy,
+    pub mode: ReplicationMode,
+    /// 0 disables batching.
+    pub max_batch_size: usize,
+    pub propagate_schema_changes: bool,
+    pub drop_removed_columns: bool,
+    pub columns: Option<Vec<String>>,
+    pub exclude_columns: Option<Vec<String>>,
+    /// Maps key column names to their replicated values.
+    pub row_filter: HashMap<String, Vec<String>>,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        ReplicationConfig {
+            conflict_policy: ConflictPolicy::default(),
+            mode: ReplicationMode::default(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            propagate_schema_changes: false,
+            drop_removed_columns: false,
+            columns: None,
+            exclude_columns: None,
+            row_filter: HashMap::new(),
+        }
+    }
+}
+
+/// Creating the destination schema, see [`SchemaCreationOptions`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaCreationConfig {
+    pub enabled: bool,
+    /// Replication settings of the created destination keyspace as a CQL map.
+    pub replication: Option<String>,
+}
+
+/// Copying existing rows before replicating changes, see [`SnapshotOptions`].
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    pub parallelism: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        SnapshotConfig {
+            enabled: false,
+            parallelism: SnapshotOptions::default().parallelism,
+        }
+    }
+}
+
+impl Config {
+    /// Checks if the settings are complete and valid.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.keyspace.is_empty() {
+            bail!("keyspace: not set, use --keyspace or the `keyspace` setting");
+        }
+        if self.tables.is_empty() {
+            bail!("tables: no tables were provided, use --table or the `tables` setting");
+        }
+        self.source
+            .validate("source")
+            .map_err(|err| err.context("use --source or the `source.hosts` setting"))?;
+        self.destination
+            .validate("destination")
+            .map_err(|err| err.context("use --destination or the `destination.hosts` setting"))?;
+        self.timing.validate("timing")?;
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.validate("checkpoints")?;
+        }
+        if let Some(start_datetime) = &self.start_datetime {
+            if self.snapshot.enabled {
+                bail!("start_datetime: can't be used together with a snapshot");
+            }
+            if let Err(err) = chrono::DateTime::parse_from_rfc3339(start_datetime) {
+                bail!(
+                    "start_datetime: invalid RFC 3339 datetime {:?}: {}",
+                    start_datetime,
+                    err
+                );
+            }
+        }
+        if self.replication.columns.is_some() && self.replication.exclude_columns.is_some() {
+            bail!("replication: columns and exclude_columns can't be used together");
+        }
+        if self.snapshot.parallelism == 0 {
+            bail!("snapshot.parallelism: must be positive");
+        }
+        if self.metrics_address.is_some() && !cfg!(feature = "metrics") {
+            bail!("metrics_address: serving metrics requires the `metrics` feature");
+        }
+        Ok(())
+    }
+
+    pub fn source_tables(&self) -> Vec<String> {
+        self.tables.iter().map(|table| table.name.clone()).collect()
+    }
+
+    /// Source tables with their destination tables and column renames.
+    pub fn replicated_tables(&self) -> Vec<ReplicatedTable> {
+        self.tables
+            .iter()
+            .map(|table| ReplicatedTable {
+                source_table: table.name.clone(),
+                dest_keyspace: table
+                    .destination_keyspace
+                    .clone()
+                    .unwrap_or_else(|| self.destination_keyspace()),
+                dest_table: table.destination.as_ref().unwrap_or(&table.name).clone(),
+                column_renames: table.column_renames.clone(),
+            })
+            .collect()
+    }
+
+    /// Destination keyspace of the tables that don't set their own.
+    pub fn destination_keyspace(&self) -> String {
+        self.destination_keyspace
+            .clone()
+            .unwrap_or_else(|| self.keyspace.clone())
+    }
+
+    /// Start of the replicated changes, if set.
+    pub fn start_timestamp(&self) -> anyhow::Result<Option<chrono::Duration>> {
+        self.start_datetime
+            .as_ref()
+            .map(|s| {
+                Ok(chrono::Duration::milliseconds(
+                    chrono::DateTime::parse_from_rfc3339(s)?.timestamp_millis(),
+                ))
+            })
+            .transpose()
+    }
+
+    /// Keyspace of the checkpoint tables if it isn't set, the keyspace of the checkpoint cluster.
+    pub fn default_checkpoint_keyspace(&self) -> String {
+        match self.checkpoint_cluster {
+            Cluster::Source => self.keyspace.clone(),
+            Cluster::Destination => self.destination_keyspace(),
+        }
+    }
+
+    /// Settings of saving the progress of `source_table`.
+    /// Tables share the CDC streams, so the progress of every table is saved separately:
+    /// in its own namespace of the checkpoint table, or in its own SQLite table or file,
+    /// named after the configured one with the `_<source_table>` suffix.
+    pub fn table_checkpoints(&self, source_table: &str) -> Option<CheckpointConfig> {
+        let mut checkpoints = self.checkpoints.clone()?;
+        match &mut checkpoints.saver {
+            CheckpointSaverConfig::Table { .. } => {}
+            CheckpointSaverConfig::Sqlite { table, .. } => {
+                *table = format!("{}_{}", table, source_table);
+            }
+            CheckpointSaverConfig::File { path } => {
+                let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+                file_name.push(format!("_{}", source_table));
+                if let Some(extension) = path.extension() {
+                    file_name.push(".");
+                    file_name.push(extension);
+                }
+                *path = path.with_file_name(PathBuf::from(file_name));
+            }
+        }
+        Some(checkpoints)
+    }
+
+    pub fn replication_options(&self) -> ReplicationOptions {
+        let replication = &self.replication;
+        let column_filter = match (&replication.columns, &replication.exclude_columns) {
+            (Some(columns), _) => ColumnFilter::Allow(columns.iter().cloned().collect()),
+            (_, Some(columns)) => ColumnFilter::Deny(columns.iter().cloned().collect()),
+            (None, None) => ColumnFilter::All,
+        };
+        let row_filter = replication
+            .row_filter
+            .iter()
+            .fold(RowFilter::default(), |filter, (column, values)| {
+                filter.allow_values(column.clone(), values.clone())
+            });
+        ReplicationOptions {
+            conflict_policy: replication.conflict_policy,
+            replication_mode: replication.mode,
+            max_batch_size: Some(replication.max_batch_size).filter(|size| *size > 0),
+            propagate_schema_changes: replication.propagate_schema_changes,
+            drop_removed_columns: replication.drop_removed_columns,
+            column_filter,
+            row_filter,
+            // Columns are renamed separately in every table, see `replicated_tables`.
+            column_renames: HashMap::new(),
+        }
+    }
+
+    pub fn schema_creation_options(&self) -> Option<SchemaCreationOptions> {
+        self.create_schema.enabled.then(|| SchemaCreationOptions {
+            replication: self.create_schema.replication.clone(),
+        })
+    }
+
+    pub fn snapshot_options(&self) -> Option<SnapshotOptions> {
+        self.snapshot.enabled.then(|| SnapshotOptions {
+            parallelism: self.snapshot.parallelism,
+            ..SnapshotOptions::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_table_checkpoints() {
+        let config_with_saver = |saver| Config {
+            checkpoints: Some(CheckpointConfig::new(saver)),
+            ..Config::default()
+        };
+
+        let config = config_with_saver(CheckpointSaverConfig::Sqlite {
+            path: PathBuf::from("checkpoints.db"),
+            table: "checkpoints".to_string(),
+        });
+        assert!(matches!(
+            config.table_checkpoints("t").unwrap().saver,
+            CheckpointSaverConfig::Sqlite { table, .. } if table == "checkpoints_t"
+        ));
+
+        let config = config_with_saver(CheckpointSaverConfig::File {
+            path: PathBuf::from("/var/lib/replicator/checkpoints.json"),
+        });
+        assert!(matches!(
+            config.table_checkpoints("t").unwrap().saver,
+            CheckpointSaverConfig::File { path }
+                if path == Path::new("/var/lib/replicator/checkpoints_t.json")
+        ));
+
+        assert!(Config::default().table_checkpoints("t").is_none());
+    }
+
+    #[test]
+    fn test_replicated_tables() {
+        let config = Config {
+            keyspace: "ks".to_string(),
+            tables: vec![
+                TableConfig::new("t1".to_string()),
+                TableConfig {
+                    destination: Some("renamed".to_string()),
+                    destination_keyspace: Some("other_ks".to_string()),
+                    column_renames: HashMap::from([("v".to_string(), "value".to_string())]),
+                    ..TableConfig::new("t2".to_string())
+                },
+            ],
+            ..Config::default()
+        };
+
+        let tables = config.replicated_tables();
+        assert_eq!(
+            (
+                tables[0].dest_keyspace.as_str(),
+                tables[0].dest_table.as_str()
+            ),
+            ("ks", "t1")
+        );
+        assert!(tables[0].column_renames.is_empty());
+        assert_eq!(
+            (
+                tables[1].dest_keyspace.as_str(),
+                tables[1].dest_table.as_str()
+            ),
+            ("other_ks", "renamed")
+        );
+        assert_eq!(tables[1].column_renames["v"], "value");
+
+        // Tables without their own keyspace use the destination keyspace.
+        let config = Config {
+            destination_keyspace: Some("dest_ks".to_string()),
+            ..config
+        };
+        assert_eq!(config.replicated_tables()[0].dest_keyspace, "dest_ks");
+        assert_eq!(config.replicated_tables()[1].dest_keyspace, "other_ks");
+    }
+}
@@ -0,0 +1,542 @@
+//! Creating the destination keyspace, user defined types and tables
+//! from the schema metadata of the source cluster.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use scylla::transport::topology::{CollectionType, ColumnKind, CqlType, Keyspace, Strategy, Table};
+use scylla::Session;
+use tracing::info;
+
+use crate::replicator::ReplicatedTable;
+use crate::replicator_consumer::{dest_column_name, rename_columns, ColumnFilter};
+
+/// Options of creating the destination schema.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaCreationOptions {
+    /// Replication settings of the created keyspace as a CQL map,
+    /// e.g. `{'class': 'NetworkTopologyStrategy', 'dc1': 3}`.
+    /// If `None`, the replication settings of the source keyspace are used.
+    pub replication: Option<String>,
+}
+
+/// Creates the destination keyspace, all user defined types of the source keyspace
+/// and the destination table, unless they already exist.
+/// An existing destination table is checked to be compatible with the source table:
+/// it must have the same primary key and all replicated columns of the source table of the same types.
+/// Columns not replicated because of `column_filter` are not created in the destination table.
+/// Columns of the source table listed in the table's column renames are renamed in the destination table.
+/// A created table gets the clustering order of the source table,
+/// but other table options, e.g. compaction or default TTL, are not copied.
+/// Fetching schema metadata must be enabled in both sessions.
+pub async fn create_destination_schema(
+    source_session: &Session,
+    source_keyspace: &str,
+    dest_session: &Session,
+    table: &ReplicatedTable,
+    column_filter: &ColumnFilter,
+    options: &SchemaCreationOptions,
+) -> anyhow::Result<()> {
+    let ReplicatedTable {
+        source_table,
+        dest_keyspace,
+        dest_table,
+        column_renames,
+    } = table;
+    let source_keyspace_info = source_session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .get(source_keyspace)
+        .cloned()
+        .ok_or_else(|| anyhow!("Keyspace {} not found", source_keyspace))?;
+    let replication = match &options.replication {
+        Some(replication) => replication.clone(),
+        None => replication_map(&source_keyspace_info.strategy)?,
+    };
+
+    dest_session
+        .query(
+            format!(
+                "CREATE KEYSPACE IF NOT EXISTS {} WITH REPLICATION = {}",
+                dest_keyspace, replication
+            ),
+            (),
+        )
+        .await?;
+    for query in create_types_queries(dest_keyspace, &source_keyspace_info) {
+        dest_session.query(query, ()).await?;
+    }
+    dest_session.await_schema_agreement().await?;
+    dest_session.refresh_metadata().await?;
+
+    let dest_keyspace_info = dest_session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .get(dest_keyspace)
+        .cloned()
+        .ok_or_else(|| anyhow!("Keyspace {} not found", dest_keyspace))?;
+    let source_schema = source_keyspace_info
+        .tables
+        .get(&source_table.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("Table {}.{} not found", source_keyspace, source_table))?;
+    let source_schema = &rename_columns(&filter_columns(source_schema, column_filter), |name| {
+        dest_column_name(column_renames, name).to_string()
+    });
+    let keyspace_table_name = format!("{}.{}", dest_keyspace, dest_table);
+
+    match dest_keyspace_info
+        .tables
+        .get(&dest_table.to_ascii_lowercase())
+    {
+        Some(dest_schema) => check_compatibility(source_schema, dest_schema)
+            .map_err(|err| anyhow!("Table {} is incompatible: {}", keyspace_table_name, err))?,
+        None => {
+            info!("Creating table {}.", keyspace_table_name);
+            let descending_columns =
+                fetch_descending_columns(source_session, source_keyspace, source_table)
+                    .await?
+                    .iter()
+                    .map(|name| dest_column_name(column_renames, name).to_string())
+                    .collect();
+            dest_session
+                .query(
+                    create_table_query(&keyspace_table_name, source_schema, &descending_columns),
+                    (),
+                )
+                .await?;
+        }
+    }
+    dest_session.await_schema_agreement().await?;
+    dest_session.refresh_metadata().await?;
+
+    Ok(())
+}
+
+// Returns names of the clustering columns of the table in descending order.
+async fn fetch_descending_columns(
+    session: &Session,
+    keyspace: &str,
+    table: &str,
+) -> anyhow::Result<HashSet<String>> {
+    Ok(session
+        .query(
+            "SELECT column_name, clustering_order FROM system_schema.columns \
+            WHERE keyspace_name = ? AND table_name = ?",
+            (keyspace, table.to_ascii_lowercase()),
+        )
+        .await?
+        .rows_typed_or_empty::<(String, String)>()
+        .filter_map(|row| row.ok())
+        .filter(|(_, clustering_order)| clustering_order == "desc")
+        .map(|(name, _)| name)
+        .collect())
+}
+
+// Returns replication settings of the keyspace as a CQL map.
+fn replication_map(strategy: &Strategy) -> anyhow::Result<String> {
+    let (class, settings) = match strategy {
+        Strategy::SimpleStrategy { replication_factor } => (
+            "SimpleStrategy",
+            vec![(
+                "replication_factor".to_string(),
+                replication_factor.to_string(),
+            )],
+        ),
+        Strategy::NetworkTopologyStrategy {
+            datacenter_repfactors,
+        } => (
+            "NetworkTopologyStrategy",
+            datacenter_repfactors
+                .iter()
+                .map(|(datacenter, factor)| (datacenter.clone(), factor.to_string()))
+                .sorted()
+                .collect(),
+        ),
+        Strategy::LocalStrategy => {
+            return Err(anyhow!(
+                "Keyspaces with LocalStrategy can't be created, set the replication explicitly"
+            ))
+        }
+        // Settings of other strategies aren't necessarily numbers, so they are quoted.
+        Strategy::Other { name, data } => (
+            name.as_str(),
+            data.iter()
+                .map(|(key, value)| (key.clone(), format!("'{}'", value.replace('\'', "''"))))
+                .sorted()
+                .collect(),
+        ),
+    };
+
+    Ok(format!(
+        "{{'class': '{}'{}}}",
+        class,
+        settings
+            .iter()
+            .map(|(key, value)| format!(", '{}': {}", key, value))
+            .join("")
+    ))
+}
+
+// Returns queries creating user defined types of the keyspace,
+// ordered so that every type is created after the types it uses.
+fn create_types_queries(dest_keyspace: &str, keyspace: &Keyspace) -> Vec<String> {
+    let mut remaining: HashMap<&String, &Vec<(String, CqlType)>> =
+        keyspace.user_defined_types.iter().collect();
+    let mut queries = vec![];
+
+    while !remaining.is_empty() {
+        let ready = remaining
+            .iter()
+            .filter(|(_, fields)| {
+                fields.iter().all(|(_, type_)| {
+                    used_types(type_)
+                        .iter()
+                        .all(|used| !remaining.contains_key(used))
+                })
+            })
+            .map(|(name, _)| *name)
+            .sorted()
+            .collect::<Vec<_>>();
+        if ready.is_empty() {
+            // Types can't depend on each other cyclically, so it shouldn't happen.
+            break;
+        }
+
+        for name in ready {
+            let fields = remaining.remove(name).unwrap();
+            queries.push(format!(
+                "CREATE TYPE IF NOT EXISTS {}.{} ({})",
+                dest_keyspace,
+                name,
+                fields
+                    .iter()
+                    .map(|(field, type_)| format!("{} {}", field, cql_type_name(type_)))
+                    .join(", ")
+            ));
+        }
+    }
+
+    queries
+}
+
+// Returns names of user defined types used by the type.
+fn used_types(cql_type: &CqlType) -> Vec<String> {
+    match cql_type {
+        CqlType::Native(_) => vec![],
+        CqlType::Collection { type_, .. } => match type_ {
+            CollectionType::List(type_) | CollectionType::Set(type_) => used_types(type_),
+            CollectionType::Map(key_type, value_type) => {
+                let mut types = used_types(key_type);
+                types.extend(used_types(value_type));
+                types
+            }
+        },
+        CqlType::Tuple(types) => types.iter().flat_map(used_types).collect(),
+        CqlType::UserDefinedType { name, .. } => vec![name.clone()],
+    }
+}
+
+// Returns the table schema without the non-key columns that aren't replicated.
+fn filter_columns(table_schema: &Table, column_filter: &ColumnFilter) -> Table {
+    let mut table_schema = table_schema.clone();
+    table_schema.columns.retain(|name, column| {
+        matches!(
+            column.kind,
+            ColumnKind::PartitionKey | ColumnKind::Clustering
+        ) || column_filter.is_replicated(name)
+    });
+    table_schema
+}
+
+fn create_table_query(
+    keyspace_table_name: &str,
+    schema: &Table,
+    descending_columns: &HashSet<String>,
+) -> String {
+    let other_columns = schema
+        .columns
+        .iter()
+        .filter(|(_, column)| {
+            column.kind != ColumnKind::PartitionKey && column.kind != ColumnKind::Clustering
+        })
+        .map(|(name, _)| name)
+        .sorted();
+    let columns = schema
+        .partition_key
+        .iter()
+        .chain(schema.clustering_key.iter())
+        .chain(other_columns)
+        .map(|name| {
+            let column = &schema.columns[name];
+            let static_suffix = match column.kind {
+                ColumnKind::Static => " STATIC",
+                _ => "",
+            };
+            format!("{} {}{}", name, cql_type_name(&column.type_), static_suffix)
+        })
+        .join(", ");
+    let primary_key = std::iter::once(format!("({})", schema.partition_key.iter().join(", ")))
+        .chain(schema.clustering_key.iter().cloned())
+        .join(", ");
+
+    let clustering_order = if schema
+        .clustering_key
+        .iter()
+        .any(|name| descending_columns.contains(name))
+    {
+        format!(
+            " WITH CLUSTERING ORDER BY ({})",
+            schema
+                .clustering_key
+                .iter()
+                .map(|name| {
+                    let order = if descending_columns.contains(name) {
+                        "DESC"
+                    } else {
+                        "ASC"
+                    };
+                    format!("{} {}", name, order)
+                })
+                .join(", ")
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({}, PRIMARY KEY ({})){}",
+        keyspace_table_name, columns, primary_key, clustering_order
+    )
+}
+
+// Checks if changes of the source table can be replicated to the destination table.
+fn check_compatibility(source_schema: &Table, dest_schema: &Table) -> anyhow::Result<()> {
+    if source_schema.partition_key != dest_schema.partition_key {
+        return Err(anyhow!(
+            "partition key ({}) differs from the source table ({})",
+            dest_schema.partition_key.join(", "),
+            source_schema.partition_key.join(", ")
+        ));
+    }
+    if source_schema.clustering_key != dest_schema.clustering_key {
+        return Err(anyhow!(
+            "clustering key ({}) differs from the source table ({})",
+            dest_schema.clustering_key.join(", "),
+            source_schema.clustering_key.join(", ")
+        ));
+    }
+
+    for (name, source_column) in source_schema
+        .columns
+        .iter()
+        .sorted_by_key(|(name, _)| *name)
+    {
+        match dest_schema.columns.get(name) {
+            None => return Err(anyhow!("column {} is missing", name)),
+            Some(dest_column) if dest_column.type_ != source_column.type_ => {
+                return Err(anyhow!(
+                    "column {} has type {}, but {} in the source table",
+                    name,
+                    cql_type_name(&dest_column.type_),
+                    cql_type_name(&source_column.type_)
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the name of the type in CQL, e.g. `frozen<list<int>>`.
+pub(crate) fn cql_type_name(cql_type: &CqlType) -> String {
+    let freeze = |name: String, frozen: bool| {
+        if frozen {
+            format!("frozen<{}>", name)
+        } else {
+            name
+        }
+    };
+
+    match cql_type {
+        CqlType::Native(native_type) => format!("{:?}", native_type).to_lowercase(),
+        CqlType::Collection { frozen, type_ } => {
+            let name = match type_ {
+                CollectionType::List(type_) => format!("list<{}>", cql_type_name(type_)),
+                CollectionType::Set(type_) => format!("set<{}>", cql_type_name(type_)),
+                CollectionType::Map(key_type, value_type) => format!(
+                    "map<{}, {}>",
+                    cql_type_name(key_type),
+                    cql_type_name(value_type)
+                ),
+            };
+            freeze(name, *frozen)
+        }
+        CqlType::Tuple(types) => format!("tuple<{}>", types.iter().map(cql_type_name).join(", ")),
+        CqlType::UserDefinedType { frozen, name } => freeze(name.clone(), *frozen),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scylla::transport::topology::{Column, NativeType};
+
+    fn column(type_: CqlType, kind: ColumnKind) -> Column {
+        Column { type_, kind }
+    }
+
+    #[test]
+    fn test_create_table_query() {
+        let udt = CqlType::UserDefinedType {
+            frozen: true,
+            name: "address".to_string(),
+        };
+        let schema = Table {
+            columns: HashMap::from([
+                (
+                    "pk".to_string(),
+                    column(CqlType::Native(NativeType::Int), ColumnKind::PartitionKey),
+                ),
+                (
+                    "ck".to_string(),
+                    column(CqlType::Native(NativeType::BigInt), ColumnKind::Clustering),
+                ),
+                (
+                    "s".to_string(),
+                    column(CqlType::Native(NativeType::Text), ColumnKind::Static),
+                ),
+                (
+                    "v".to_string(),
+                    column(
+                        CqlType::Collection {
+                            frozen: false,
+                            type_: CollectionType::Map(
+                                Box::new(CqlType::Native(NativeType::Timeuuid)),
+                                Box::new(udt),
+                            ),
+                        },
+                        ColumnKind::Regular,
+                    ),
+                ),
+            ]),
+            partition_key: vec!["pk".to_string()],
+            clustering_key: vec!["ck".to_string()],
+            partitioner: None,
+        };
+
+        assert_eq!(
+            create_table_query("ks.t", &schema, &HashSet::new()),
+            "CREATE TABLE IF NOT EXISTS ks.t (pk int, ck bigint, s text STATIC, \
+            v map<timeuuid, frozen<address>>, PRIMARY KEY ((pk), ck))"
+        );
+        assert_eq!(
+            create_table_query("ks.t", &schema, &HashSet::from(["ck".to_string()])),
+            "CREATE TABLE IF NOT EXISTS ks.t (pk int, ck bigint, s text STATIC, \
+            v map<timeuuid, frozen<address>>, PRIMARY KEY ((pk), ck)) \
+            WITH CLUSTERING ORDER BY (ck DESC)"
+        );
+        assert!(check_compatibility(&schema, &schema).is_ok());
+
+        let mut other_schema = schema.clone();
+        other_schema.columns.remove("s");
+        assert!(check_compatibility(&schema, &other_schema).is_err());
+        assert!(check_compatibility(&other_schema, &schema).is_ok());
+    }
+
+    #[test]
+    fn test_create_table_query_with_column_filter() {
+        let schema = Table {
+            columns: HashMap::from([
+                (
+                    "pk".to_string(),
+                    column(CqlType::Native(NativeType::Int), ColumnKind::PartitionKey),
+                ),
+                (
+                    "ck".to_string(),
+                    column(CqlType::Native(NativeType::Int), ColumnKind::Clustering),
+                ),
+                (
+                    "v".to_string(),
+                    column(CqlType::Native(NativeType::Int), ColumnKind::Regular),
+                ),
+                (
+                    "secret".to_string(),
+                    column(CqlType::Native(NativeType::Text), ColumnKind::Regular),
+                ),
+            ]),
+            partition_key: vec!["pk".to_string()],
+            clustering_key: vec!["ck".to_string()],
+            partitioner: None,
+        };
+        let denied = filter_columns(&schema, &ColumnFilter::Deny(["secret".to_string()].into()));
+        // Key columns are kept even if they aren't listed.
+        let allowed = filter_columns(&schema, &ColumnFilter::Allow(["v".to_string()].into()));
+
+        for filtered in [&denied, &allowed] {
+            assert_eq!(
+                create_table_query("ks.t", filtered, &HashSet::new()),
+                "CREATE TABLE IF NOT EXISTS ks.t (pk int, ck int, v int, PRIMARY KEY ((pk), ck))"
+            );
+            // Destination tables may leave out the columns that aren't replicated.
+            assert!(check_compatibility(filtered, filtered).is_ok());
+            assert!(check_compatibility(filtered, &schema).is_ok());
+            assert!(check_compatibility(&schema, filtered).is_err());
+        }
+    }
+
+    #[test]
+    fn test_create_types_queries() {
+        let keyspace = Keyspace {
+            strategy: Strategy::SimpleStrategy {
+                replication_factor: 3,
+            },
+            tables: HashMap::new(),
+            user_defined_types: HashMap::from([
+                (
+                    "person".to_string(),
+                    vec![
+                        ("name".to_string(), CqlType::Native(NativeType::Text)),
+                        (
+                            "addresses".to_string(),
+                            CqlType::Collection {
+                                frozen: true,
+                                type_: CollectionType::List(Box::new(CqlType::UserDefinedType {
+                                    frozen: true,
+                                    name: "address".to_string(),
+                                })),
+                            },
+                        ),
+                    ],
+                ),
+                (
+                    "address".to_string(),
+                    vec![("street".to_string(), CqlType::Native(NativeType::Text))],
+                ),
+            ]),
+        };
+
+        assert_eq!(
+            create_types_queries("ks", &keyspace),
+            vec![
+                "CREATE TYPE IF NOT EXISTS ks.address (street text)",
+                "CREATE TYPE IF NOT EXISTS ks.person (name text, addresses frozen<list<frozen<address>>>)",
+            ]
+        );
+        assert_eq!(
+            replication_map(&keyspace.strategy).unwrap(),
+            "{'class': 'SimpleStrategy', 'replication_factor': 3}"
+        );
+        let other_strategy = Strategy::Other {
+            name: "org.example.CustomStrategy".to_string(),
+            data: HashMap::from([
+                ("dc1".to_string(), "3".to_string()),
+                ("rack_policy".to_string(), "it's spread".to_string()),
+            ]),
+        };
+        assert_eq!(
+            replication_map(&other_strategy).unwrap(),
+            "{'class': 'org.example.CustomStrategy', 'dc1': '3', 'rack_policy': 'it''s spread'}"
+        );
+    }
+}
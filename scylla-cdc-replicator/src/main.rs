@@ -1,73 +1,388 @@
+pub mod config;
+pub mod destination_schema;
+#[cfg(feature = "metrics")]
+mod metrics_server;
 mod replication_tests;
 mod replicator;
 pub mod replicator_consumer;
+pub mod snapshot;
 
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::bail;
 use clap::Parser;
 use futures_util::StreamExt;
+use scylla_cdc::checkpoints::CheckpointNamespace;
+use scylla_cdc::config::{CheckpointConfig, CheckpointSaverConfig};
 use tokio::select;
 
-use crate::replicator::Replicator;
+use crate::config::{Cluster, Config, TableConfig, ENV_PREFIX};
+use crate::replicator::{CheckpointOptions, Replicator, ReplicatorOptions};
+use crate::replicator_consumer::{ConflictPolicy, ReplicationMode};
 
+/// Flags override the settings of the configuration file,
+/// which are overridden by environment variables prefixed with SCYLLA_CDC_REPLICATOR_.
 #[derive(Parser)]
 struct Args {
+    /// Path of a TOML or YAML configuration file
+    #[clap(short, long, action = clap::ArgAction::Set)]
+    config: Option<PathBuf>,
+
     /// Keyspace name
     #[clap(short, long, action = clap::ArgAction::Set)]
-    keyspace: String,
+    keyspace: Option<String>,
 
     /// Table names provided as a comma delimited string
     #[clap(short, long, action = clap::ArgAction::Set)]
-    table: String,
+    table: Option<String>,
 
     /// Address of a node in source cluster
     #[clap(short, long, action = clap::ArgAction::Set)]
-    source: String,
+    source: Option<String>,
 
     /// Address of a node in destination cluster
     #[clap(short, long, action = clap::ArgAction::Set)]
-    destination: String,
+    destination: Option<String>,
+
+    /// Destination keyspace name of the tables that don't set their own,
+    /// the source keyspace name by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    destination_keyspace: Option<String>,
 
-    /// Window size in seconds
-    #[clap(long, default_value_t = 60., action = clap::ArgAction::Set)]
-    window_size: f64,
+    /// Destination tables provided as a comma delimited string of "table" or "keyspace.table",
+    /// in the order of the source tables. The source table names by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    destination_tables: Option<String>,
 
-    /// Safety interval in seconds
-    #[clap(long, default_value_t = 30., action = clap::ArgAction::Set)]
-    safety_interval: f64,
+    /// Replicate a source column to a destination column of a different name,
+    /// provided as "source_column=destination_column" for all tables
+    /// or "table.source_column=destination_column" for one source table.
+    /// Can be repeated for multiple columns
+    #[clap(long, value_parser = parse_column_rename, action = clap::ArgAction::Append)]
+    rename_column: Vec<ColumnRename>,
 
-    /// Sleep interval in seconds
-    #[clap(long, default_value_t = 10., action = clap::ArgAction::Set)]
-    sleep_interval: f64,
+    /// Window size in seconds, 60 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    window_size: Option<f64>,
+
+    /// Safety interval in seconds, 30 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    safety_interval: Option<f64>,
+
+    /// Sleep interval in seconds, 10 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    sleep_interval: Option<f64>,
 
     /// Start datetime as RFC 3339 formatted string
     #[clap(long, action = clap::ArgAction::Set)]
     start_datetime: Option<String>,
+
+    /// Policy of handling rows of destination tables that diverged from source tables.
+    /// Conflicts are detected only for source tables with preimage enabled. "ignore" by default
+    #[clap(long, value_enum, action = clap::ArgAction::Set)]
+    conflict_policy: Option<ConflictPolicy>,
+
+    /// Rows of the CDC log used to replicate inserts and updates.
+    /// Replicating postimages requires source tables with postimage enabled. "delta" by default
+    #[clap(long, value_enum, action = clap::ArgAction::Set)]
+    replication_mode: Option<ReplicationMode>,
+
+    /// Maximum size in bytes of values of the statements sent in one batch.
+    /// Statements replicating a CDC batch are sent in one unlogged batch per partition.
+    /// 0 disables batching. 65536 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    max_batch_size: Option<usize>,
+
+    /// Add columns added to source tables to destination tables
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    propagate_schema_changes: bool,
+
+    /// With --propagate-schema-changes, also drop columns of destination tables
+    /// missing in source tables, including columns added only to destination tables
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    drop_removed_columns: bool,
+
+    /// Create the destination keyspace, user defined types and tables if they don't exist,
+    /// and check if existing destination tables are compatible with source tables
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    create_schema: bool,
+
+    /// Replication settings of the created destination keyspace as a CQL map,
+    /// e.g. "{'class': 'NetworkTopologyStrategy', 'dc1': 3}".
+    /// Replication settings of the source keyspace are used by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    replication: Option<String>,
+
+    /// Copy all rows of source tables to destination tables before replicating changes
+    /// made since the copy started
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    snapshot: bool,
+
+    /// Number of token ranges of a table copied concurrently by the snapshot, 8 by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    snapshot_parallelism: Option<usize>,
+
+    /// Names of the only non-key columns replicated, provided as a comma delimited string
+    #[clap(long, conflicts_with = "exclude-columns", action = clap::ArgAction::Set)]
+    columns: Option<String>,
+
+    /// Names of the columns not replicated, provided as a comma delimited string
+    #[clap(long, action = clap::ArgAction::Set)]
+    exclude_columns: Option<String>,
+
+    /// Replicate only rows whose key column has one of the listed values,
+    /// provided as "column=value1,value2". Can be repeated for multiple columns
+    #[clap(long, value_parser = parse_row_filter, action = clap::ArgAction::Append)]
+    row_filter: Vec<(String, Vec<String>)>,
+
+    /// Save the progress of every table in this table of the checkpoint cluster
+    /// and resume from it on restart.
+    /// Provided as "keyspace.table" or "table", in the keyspace of the checkpoint cluster by default
+    #[clap(long, action = clap::ArgAction::Set)]
+    checkpoint_table: Option<String>,
+
+    /// Cluster storing the checkpoint tables, "source" by default
+    #[clap(long, value_enum, action = clap::ArgAction::Set)]
+    checkpoint_cluster: Option<Cluster>,
+
+    /// Ignore the saved progress, overwriting it as the replication advances.
+    /// The replication starts from --start-datetime, or now
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    reset_progress: bool,
+
+    /// Address of the HTTP endpoint serving Prometheus metrics at /metrics, e.g. "0.0.0.0:9180".
+    /// Requires the `metrics` feature
+    #[clap(long, action = clap::ArgAction::Set)]
+    metrics_address: Option<SocketAddr>,
+}
+
+impl Args {
+    fn override_config(self, config: &mut Config) -> anyhow::Result<()> {
+        if let Some(keyspace) = self.keyspace {
+            config.keyspace = keyspace;
+        }
+        if let Some(tables) = self.table {
+            config.tables = split_names(&tables)
+                .into_iter()
+                .map(TableConfig::new)
+                .collect();
+        }
+        if let Some(dest_tables) = self.destination_tables {
+            let dest_tables = split_names(&dest_tables);
+            if dest_tables.len() != config.tables.len() {
+                bail!("The numbers of source and destination tables differ");
+            }
+            for (table, dest_table) in config.tables.iter_mut().zip(dest_tables) {
+                match dest_table.split_once('.') {
+                    Some((keyspace, name)) => {
+                        table.destination_keyspace = Some(keyspace.to_string());
+                        table.destination = Some(name.to_string());
+                    }
+                    None => table.destination = Some(dest_table),
+                }
+            }
+        }
+        for rename in self.rename_column {
+            let mut renamed = false;
+            for table in config.tables.iter_mut() {
+                if rename.table.as_ref().is_none_or(|name| *name == table.name) {
+                    table
+                        .column_renames
+                        .insert(rename.source.clone(), rename.destination.clone());
+                    renamed = true;
+                }
+            }
+            if let (Some(name), false) = (&rename.table, renamed) {
+                bail!(
+                    "Column {} renamed in table {} which isn't replicated",
+                    rename.source,
+                    name
+                );
+            }
+        }
+        if let Some(source) = self.source {
+            config.source.hosts = vec![source];
+        }
+        if let Some(destination) = self.destination {
+            config.destination.hosts = vec![destination];
+        }
+        if let Some(dest_keyspace) = self.destination_keyspace {
+            config.destination_keyspace = Some(dest_keyspace);
+        }
+        if let Some(window_size) = self.window_size {
+            config.timing.window_size = window_size;
+        }
+        if let Some(safety_interval) = self.safety_interval {
+            config.timing.safety_interval = safety_interval;
+        }
+        if let Some(sleep_interval) = self.sleep_interval {
+            config.timing.sleep_interval = sleep_interval;
+        }
+        if let Some(start_datetime) = self.start_datetime {
+            config.start_datetime = Some(start_datetime);
+        }
+
+        let replication = &mut config.replication;
+        if let Some(conflict_policy) = self.conflict_policy {
+            replication.conflict_policy = conflict_policy;
+        }
+        if let Some(replication_mode) = self.replication_mode {
+            replication.mode = replication_mode;
+        }
+        if let Some(max_batch_size) = self.max_batch_size {
+            replication.max_batch_size = max_batch_size;
+        }
+        replication.propagate_schema_changes |= self.propagate_schema_changes;
+        replication.drop_removed_columns |= self.drop_removed_columns;
+        // Columns set by flags replace the column filter of the configuration file.
+        if let Some(columns) = self.columns {
+            replication.columns = Some(split_names(&columns));
+            replication.exclude_columns = None;
+        }
+        if let Some(columns) = self.exclude_columns {
+            replication.exclude_columns = Some(split_names(&columns));
+            replication.columns = None;
+        }
+        replication.row_filter.extend(self.row_filter);
+
+        config.create_schema.enabled |= self.create_schema;
+        if let Some(keyspace_replication) = self.replication {
+            config.create_schema.replication = Some(keyspace_replication);
+        }
+        config.snapshot.enabled |= self.snapshot;
+        if let Some(parallelism) = self.snapshot_parallelism {
+            config.snapshot.parallelism = parallelism;
+        }
+
+        if let Some(checkpoint_table) = self.checkpoint_table {
+            let (keyspace, table) = match checkpoint_table.split_once('.') {
+                Some((keyspace, table)) => (Some(keyspace.to_string()), table.to_string()),
+                None => (None, checkpoint_table),
+            };
+            let saver = CheckpointSaverConfig::Table {
+                keyspace,
+                table,
+                ttl: None,
+                application_id: None,
+                import_from: None,
+            };
+            match &mut config.checkpoints {
+                Some(checkpoints) => checkpoints.saver = saver,
+                None => config.checkpoints = Some(CheckpointConfig::new(saver)),
+            }
+        }
+        if let Some(checkpoint_cluster) = self.checkpoint_cluster {
+            config.checkpoint_cluster = checkpoint_cluster;
+        }
+        if let Some(metrics_address) = self.metrics_address {
+            config.metrics_address = Some(metrics_address);
+        }
+        Ok(())
+    }
+}
+
+fn parse_row_filter(condition: &str) -> Result<(String, Vec<String>), String> {
+    let (column, values) = condition
+        .split_once('=')
+        .ok_or_else(|| "expected column=value1,value2".to_string())?;
+    Ok((
+        column.to_string(),
+        values.split(',').map(str::to_string).collect(),
+    ))
+}
+
+#[derive(Clone, Debug)]
+struct ColumnRename {
+    /// The source table of the renamed column, all tables if not set.
+    table: Option<String>,
+    source: String,
+    destination: String,
+}
+
+fn parse_column_rename(rename: &str) -> Result<ColumnRename, String> {
+    let (source, destination) = rename
+        .split_once('=')
+        .ok_or_else(|| "expected [table.]source_column=destination_column".to_string())?;
+    let (table, source) = match source.split_once('.') {
+        Some((table, source)) => (Some(table.to_string()), source),
+        None => (None, source),
+    };
+    Ok(ColumnRename {
+        table,
+        source: source.to_string(),
+        destination: destination.to_string(),
+    })
+}
+
+fn split_names(names: &str) -> Vec<String> {
+    names.split(',').map(|s| s.to_string()).collect()
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let tables: Vec<_> = args.table.split(',').map(|s| s.to_string()).collect();
-    assert!(!tables.is_empty(), "no tables were provided");
-    let start_timestamp = chrono::Duration::milliseconds(match args.start_datetime {
-        Some(s) => chrono::DateTime::parse_from_rfc3339(&s)?.timestamp_millis(),
-        None => chrono::Local::now().timestamp_millis(),
-    });
-    let sleep_interval = Duration::from_secs_f64(args.sleep_interval);
+    let reset_progress = args.reset_progress;
+    let mut config: Config = scylla_cdc::config::load(args.config.as_deref(), ENV_PREFIX)?;
+    args.override_config(&mut config)?;
+    config.validate()?;
+
+    #[cfg(feature = "metrics")]
+    let _metrics_server = config
+        .metrics_address
+        .map(metrics_server::start)
+        .transpose()?;
+
+    let source_session = Arc::new(config.source.build().await?);
+    let dest_session = Arc::new(config.destination.build().await?);
+    let checkpoints = match &config.checkpoints {
+        Some(checkpoints) => {
+            let session = match config.checkpoint_cluster {
+                Cluster::Source => &source_session,
+                Cluster::Destination => &dest_session,
+            };
+            let keyspace = config.default_checkpoint_keyspace();
+            let mut savers = HashMap::new();
+            for table in config.source_tables() {
+                let table_checkpoints = config.table_checkpoints(&table).unwrap();
+                let namespace =
+                    CheckpointNamespace::new("scylla-cdc-replicator", &config.keyspace, &table);
+                let saver = table_checkpoints
+                    .build_saver(session, &keyspace, namespace)
+                    .await?;
+                savers.insert(table, saver);
+            }
+            Some(CheckpointOptions {
+                savers,
+                pause_between_saves: checkpoints.pause_between_saves(),
+                reset_progress,
+            })
+        }
+        None if reset_progress => {
+            bail!("--reset-progress requires saving checkpoints, use --checkpoint-table or the `checkpoints` setting")
+        }
+        None => None,
+    };
     let mut result = Ok(());
 
     let (mut replicator, mut handles) = Replicator::new(
-        args.source,
-        args.keyspace.clone(),
-        tables.clone(),
-        args.destination,
-        args.keyspace,
-        tables,
-        start_timestamp,
-        Duration::from_secs_f64(args.window_size),
-        Duration::from_secs_f64(args.safety_interval),
-        sleep_interval,
+        source_session,
+        config.keyspace.clone(),
+        config.replicated_tables(),
+        dest_session,
+        ReplicatorOptions {
+            start_timestamp: config.start_timestamp()?,
+            window_size: Duration::from_secs_f64(config.timing.window_size),
+            safety_interval: Duration::from_secs_f64(config.timing.safety_interval),
+            sleep_interval: Duration::from_secs_f64(config.timing.sleep_interval),
+            replication: config.replication_options(),
+            schema_creation: config.schema_creation_options(),
+            snapshot: config.snapshot_options(),
+            checkpoints,
+        },
     )
     .await?;
 
@@ -0,0 +1,84 @@
+//! HTTP endpoint serving the Prometheus metrics of the replication at `/metrics`.
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use futures_util::future::RemoteHandle;
+use futures_util::FutureExt;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use tracing::{error, info};
+
+/// Binds to `address` and serves the metrics in a spawned task,
+/// which is stopped when the returned handle is dropped. Errors of serving are logged.
+pub fn start(address: SocketAddr) -> anyhow::Result<RemoteHandle<()>> {
+    let make_service =
+        make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle_request)) });
+    let server = Server::try_bind(&address)?.serve(make_service);
+    info!("Serving metrics at http://{}/metrics", address);
+
+    let (fut, handle) = async move {
+        if let Err(err) = server.await {
+            error!("Serving metrics failed: {}", err);
+        }
+    }
+    .remote_handle();
+    tokio::spawn(fut);
+    Ok(handle)
+}
+
+async fn handle_request(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = if request.uri().path() != "/metrics" {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+    } else {
+        match scylla_cdc::metrics::encode_text() {
+            Ok(text) => Response::builder()
+                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(Body::from(text)),
+            Err(err) => Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(err.to_string())),
+        }
+    };
+
+    Ok(response.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    async fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        // Binding to port 0 would hide the chosen port, so a free one is found first.
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let _server = start(address).unwrap();
+
+        let response = get(address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("text/plain; version=0.0.4"));
+
+        let response = get(address, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
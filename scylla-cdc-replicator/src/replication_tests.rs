@@ -1,14 +1,20 @@
 #[cfg(test)]
 mod tests {
-    use crate::replicator_consumer::ReplicatorConsumer;
-    use anyhow::anyhow;
+    use crate::destination_schema::{create_destination_schema, SchemaCreationOptions};
+    use crate::replicator::{check_cdc_options, ReplicatedTable};
+    use crate::replicator_consumer::{
+        ColumnFilter, ConflictPolicy, ReplicationMode, ReplicationOptions,
+        ReplicatorConsumerFactory, RowFilter,
+    };
+    use crate::snapshot::{copy_table, SnapshotOptions};
     use futures_util::FutureExt;
     use itertools::Itertools;
     use scylla::frame::response::result::CqlValue::{Boolean, Int, Text, UserDefinedType};
     use scylla::frame::response::result::{CqlValue, Row};
     use scylla::Session;
-    use scylla_cdc::consumer::{CDCRow, CDCRowSchema, Consumer};
-    use scylla_cdc_test_utils::prepare_db;
+    use scylla_cdc::consumer::{CDCRow, CDCRowSchema, ConsumerFactory};
+    use scylla_cdc_test_utils::{prepare_db, unique_name};
+    use std::collections::HashMap;
     use std::sync::Arc;
 
     /// Tuple representing a column in the table that will be replicated.
@@ -46,30 +52,36 @@ mod tests {
         ks_dst: &str,
         name: &str,
         last_read: &mut (u64, i32),
+        options: ReplicationOptions,
+    ) -> anyhow::Result<()> {
+        replicate_to(session, ks_src, name, ks_dst, name, last_read, options).await
+    }
+
+    // Replicates changes of the source table to the destination table of a different name.
+    async fn replicate_to(
+        session: &Arc<Session>,
+        ks_src: &str,
+        name_src: &str,
+        ks_dst: &str,
+        name_dst: &str,
+        last_read: &mut (u64, i32),
+        options: ReplicationOptions,
     ) -> anyhow::Result<()> {
         let result = session
             .query(
-                format!("SELECT * FROM {}.{}_scylla_cdc_log", ks_src, name),
+                format!("SELECT * FROM {}.{}_scylla_cdc_log", ks_src, name_src),
                 (),
             )
             .await?;
 
-        let table_schema = session
-            .get_cluster_data()
-            .get_keyspace_info()
-            .get(ks_dst)
-            .ok_or_else(|| anyhow!("Keyspace not found"))?
-            .tables
-            .get(&name.to_ascii_lowercase())
-            .ok_or_else(|| anyhow!("Table not found"))?
-            .clone();
-
-        let mut consumer = ReplicatorConsumer::new(
+        let mut consumer = ReplicatorConsumerFactory::new(
+            session.clone(),
             session.clone(),
             ks_dst.to_string(),
-            name.to_string(),
-            table_schema,
-        )
+            name_dst.to_string(),
+            options,
+        )?
+        .new_consumer()
         .await;
 
         let schema = CDCRowSchema::new(&result.col_specs);
@@ -307,10 +319,33 @@ mod tests {
         udt_schemas: Vec<TestUDTSchema<'_>>,
         operations: Vec<TestOperation<'_>>,
     ) -> anyhow::Result<(Arc<Session>, String, String)> {
+        test_replication_with_options(
+            table_schema,
+            udt_schemas,
+            operations,
+            ReplicationOptions::default(),
+        )
+        .await
+    }
+
+    /// Function that tests replication process with the given options.
+    /// Timestamps of the columns are compared only in the delta replication mode.
+    /// Different tests in the same cluster must have different table names.
+    async fn test_replication_with_options(
+        table_schema: TestTableSchema<'_>,
+        udt_schemas: Vec<TestUDTSchema<'_>>,
+        operations: Vec<TestOperation<'_>>,
+        options: ReplicationOptions,
+    ) -> anyhow::Result<(Arc<Session>, String, String)> {
+        let replication_mode = options.replication_mode;
         let mut schema_queries = get_udt_queries(udt_schemas);
         let create_dst_table_query = get_table_create_query(&table_schema);
+        let cdc_options = match replication_mode {
+            ReplicationMode::Delta => "'enabled' : true",
+            ReplicationMode::Postimage => "'enabled' : true, 'postimage' : true",
+        };
         let create_src_table_query =
-            format!("{} WITH cdc = {{'enabled' : true}}", create_dst_table_query);
+            format!("{} WITH cdc = {{{}}}", create_dst_table_query, cdc_options);
 
         let len = schema_queries.len();
         schema_queries.push(create_src_table_query);
@@ -328,10 +363,13 @@ mod tests {
                 &ks_dst,
                 &table_schema.name,
                 &mut last_read,
+                options.clone(),
             )
             .await?;
             compare_changes(&session, &ks_src, &ks_dst, &table_schema.name).await?;
-            compare_timestamps(&session, &ks_src, &ks_dst, &table_schema).await?;
+            if replication_mode == ReplicationMode::Delta {
+                compare_timestamps(&session, &ks_src, &ks_dst, &table_schema).await?;
+            }
         }
         Ok((session, ks_src, ks_dst))
     }
@@ -879,7 +917,7 @@ mod tests {
             other_columns: vec![("v", "int")],
         };
 
-        let operations = std::iter::repeat(0..5).take(3).multi_cartesian_product().map(|x| {
+        let operations = std::iter::repeat_n(0..5, 3).multi_cartesian_product().map(|x| {
             format!("INSERT INTO RANGE_DELETE (pk1, pk2, ck1, ck2, ck3, v) VALUES (0, 0, {}, {}, {}, 0)", x[0], x[1], x[2])
         }).collect::<Vec<_>>();
 
@@ -894,4 +932,857 @@ mod tests {
 
         test_replication(schema, operations).await.unwrap();
     }
+
+    fn with_conflict_policy(conflict_policy: ConflictPolicy) -> ReplicationOptions {
+        ReplicationOptions {
+            conflict_policy,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conflict_detection() {
+        let schema = TestTableSchema {
+            name: "CONFLICT_DETECTION".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![("v1", "int"), ("v2", "int")],
+        };
+        let create_dst_table_query = get_table_create_query(&schema);
+        let create_src_table_query = format!(
+            "{} WITH cdc = {{'enabled' : true, 'preimage' : true}}",
+            create_dst_table_query
+        );
+        let (session, ks_src) = prepare_db(&[create_src_table_query], 1).await.unwrap();
+        let (_, ks_dst) = prepare_db(&[create_dst_table_query], 1).await.unwrap();
+        session.refresh_metadata().await.unwrap();
+        let mut last_read = (0, 0);
+
+        let read_v1 = |session: Arc<Session>, ks: String| async move {
+            session
+                .query(
+                    format!(
+                        "SELECT v1 FROM {}.CONFLICT_DETECTION WHERE pk = 1 AND ck = 2",
+                        ks
+                    ),
+                    (),
+                )
+                .await
+                .unwrap()
+                .single_row_typed::<(i32,)>()
+                .unwrap()
+                .0
+        };
+
+        session
+            .query(
+                "INSERT INTO CONFLICT_DETECTION (pk, ck, v1, v2) VALUES (1, 2, 3, 4)",
+                (),
+            )
+            .await
+            .unwrap();
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut last_read,
+            with_conflict_policy(ConflictPolicy::Halt),
+        )
+        .await
+        .unwrap();
+
+        // Make the destination row diverge from the source row.
+        session
+            .query(
+                format!(
+                    "UPDATE {}.CONFLICT_DETECTION SET v1 = 10 WHERE pk = 1 AND ck = 2",
+                    ks_dst
+                ),
+                (),
+            )
+            .await
+            .unwrap();
+
+        // Changes of other columns are not in conflict.
+        session
+            .query(
+                "UPDATE CONFLICT_DETECTION SET v2 = 5 WHERE pk = 1 AND ck = 2",
+                (),
+            )
+            .await
+            .unwrap();
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut last_read,
+            with_conflict_policy(ConflictPolicy::Halt),
+        )
+        .await
+        .unwrap();
+
+        session
+            .query(
+                "UPDATE CONFLICT_DETECTION SET v1 = 6 WHERE pk = 1 AND ck = 2",
+                (),
+            )
+            .await
+            .unwrap();
+
+        let mut halted_last_read = last_read;
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut halted_last_read,
+            with_conflict_policy(ConflictPolicy::Halt),
+        )
+        .await
+        .unwrap_err();
+
+        let mut skipped_last_read = last_read;
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut skipped_last_read,
+            with_conflict_policy(ConflictPolicy::Skip),
+        )
+        .await
+        .unwrap();
+        assert_eq!(read_v1(session.clone(), ks_dst.clone()).await, 10);
+
+        let mut reported_last_read = last_read;
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut reported_last_read,
+            with_conflict_policy(ConflictPolicy::Report),
+        )
+        .await
+        .unwrap();
+        assert_eq!(read_v1(session.clone(), ks_dst.clone()).await, 6);
+
+        // Replicating an already applied change is not a conflict.
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut last_read,
+            with_conflict_policy(ConflictPolicy::Halt),
+        )
+        .await
+        .unwrap();
+        compare_changes(&session, &ks_src, &ks_dst, &schema.name)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_postimage_replication() {
+        let schema = TestTableSchema {
+            name: "POSTIMAGE_REPLICATION".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![("v1", "int"), ("v2", "text"), ("v3", "frozen<list<int>>")],
+        };
+
+        let operations = vec![
+            "INSERT INTO POSTIMAGE_REPLICATION (pk, ck, v1, v2, v3) VALUES (1, 2, 3, 'abc', [1, 2])",
+            "INSERT INTO POSTIMAGE_REPLICATION (pk, ck, v1) VALUES (1, 3, 4) USING TTL 1000",
+            "UPDATE POSTIMAGE_REPLICATION SET v2 = 'def' WHERE pk = 1 AND ck = 2",
+            "DELETE v1 FROM POSTIMAGE_REPLICATION WHERE pk = 1 AND ck = 2",
+            "DELETE FROM POSTIMAGE_REPLICATION WHERE pk = 1 AND ck = 3",
+        ];
+
+        let options = ReplicationOptions {
+            replication_mode: ReplicationMode::Postimage,
+            ..Default::default()
+        };
+        test_replication_with_options(schema, vec![], operations, options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_postimage_replication_of_collections() {
+        let schema = TestTableSchema {
+            name: "POSTIMAGE_COLLECTIONS".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![
+                ("v1", "list<int>"),
+                ("v2", "set<int>"),
+                ("v3", "map<int, text>"),
+            ],
+        };
+
+        let operations = vec![
+            "INSERT INTO POSTIMAGE_COLLECTIONS (pk, ck, v1, v2, v3) VALUES (1, 2, [1, 2], {3, 4}, {5: 'a'})",
+            "UPDATE POSTIMAGE_COLLECTIONS SET v1 = v1 + [3], v2 = v2 - {3} WHERE pk = 1 AND ck = 2",
+            "UPDATE POSTIMAGE_COLLECTIONS SET v3 = v3 + {6: 'b'} WHERE pk = 1 AND ck = 2",
+            "UPDATE POSTIMAGE_COLLECTIONS SET v1 = [7] WHERE pk = 1 AND ck = 2",
+            // The post-image of a collection without elements is null.
+            "UPDATE POSTIMAGE_COLLECTIONS SET v2 = v2 - {4} WHERE pk = 1 AND ck = 2",
+        ];
+
+        let options = ReplicationOptions {
+            replication_mode: ReplicationMode::Postimage,
+            ..Default::default()
+        };
+        test_replication_with_options(schema, vec![], operations, options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_size_limits() {
+        for (name, max_batch_size) in [
+            ("BATCHING_DISABLED", None),
+            ("BATCH_SIZE_EXCEEDED", Some(16)),
+        ] {
+            let schema = TestTableSchema {
+                name: name.to_string(),
+                partition_key: vec![("pk", "int")],
+                clustering_key: vec![("ck", "int")],
+                other_columns: vec![("v1", "int"), ("v2", "text"), ("v3", "list<int>")],
+            };
+            let operations = [
+                format!(
+                    "INSERT INTO {} (pk, ck, v1, v2, v3) VALUES (1, 2, 3, 'abc', [1, 2])",
+                    name
+                ),
+                format!(
+                    "BEGIN UNLOGGED BATCH \
+                    UPDATE {0} SET v1 = 4, v3 = v3 + [3] WHERE pk = 1 AND ck = 2; \
+                    INSERT INTO {0} (pk, ck, v2) VALUES (2, 3, 'def'); \
+                    APPLY BATCH",
+                    name
+                ),
+            ];
+            let options = ReplicationOptions {
+                max_batch_size,
+                ..Default::default()
+            };
+
+            test_replication_with_options(
+                schema,
+                vec![],
+                operations.iter().map(String::as_str).collect(),
+                options,
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schema_changes() {
+        for (name, propagate_schema_changes, drop_removed_columns) in [
+            ("PROPAGATED_SCHEMA_CHANGES", true, true),
+            ("PROPAGATED_ADDED_COLUMNS", true, false),
+            ("NOT_PROPAGATED_SCHEMA_CHANGES", false, false),
+        ] {
+            let schema = TestTableSchema {
+                name: name.to_string(),
+                partition_key: vec![("pk", "int")],
+                clustering_key: vec![("ck", "int")],
+                other_columns: vec![("v1", "int")],
+            };
+            let create_dst_table_query = get_table_create_query(&schema);
+            let create_src_table_query =
+                format!("{} WITH cdc = {{'enabled' : true}}", create_dst_table_query);
+            let (session, ks_src) = prepare_db(&[create_src_table_query], 1).await.unwrap();
+            let (_, ks_dst) = prepare_db(&[create_dst_table_query], 1).await.unwrap();
+            session.refresh_metadata().await.unwrap();
+            let options = ReplicationOptions {
+                propagate_schema_changes,
+                drop_removed_columns,
+                ..Default::default()
+            };
+            let mut last_read = (0, 0);
+
+            let operations = [
+                format!("INSERT INTO {} (pk, ck, v1) VALUES (1, 2, 3)", name),
+                format!("ALTER TABLE {} ADD v2 text", name),
+                format!(
+                    "INSERT INTO {} (pk, ck, v1, v2) VALUES (1, 3, 4, 'a')",
+                    name
+                ),
+                format!("ALTER TABLE {} DROP v1", name),
+                format!("UPDATE {} SET v2 = 'b' WHERE pk = 1 AND ck = 2", name),
+            ];
+            for operation in operations {
+                session.query(operation, ()).await.unwrap();
+                session.await_schema_agreement().await.unwrap();
+                replicate(
+                    &session,
+                    &ks_src,
+                    &ks_dst,
+                    &schema.name,
+                    &mut last_read,
+                    options.clone(),
+                )
+                .await
+                .unwrap();
+            }
+
+            let replicated_columns = session
+                .query(format!("SELECT * FROM {}.{}", ks_dst, name), ())
+                .await
+                .unwrap()
+                .col_specs
+                .into_iter()
+                .map(|spec| spec.name)
+                .collect::<Vec<_>>();
+            if drop_removed_columns {
+                assert_eq!(replicated_columns, vec!["pk", "ck", "v2"]);
+                compare_changes(&session, &ks_src, &ks_dst, &schema.name)
+                    .await
+                    .unwrap();
+            } else if propagate_schema_changes {
+                assert_eq!(replicated_columns, vec!["pk", "ck", "v1", "v2"]);
+            } else {
+                assert_eq!(replicated_columns, vec!["pk", "ck", "v1"]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schema_change_propagated_by_many_consumers() {
+        let name = "SCHEMA_CHANGE_CONSUMERS";
+        let create_dst_table_query = format!("CREATE TABLE {} (pk int PRIMARY KEY, v1 int)", name);
+        let create_src_table_query =
+            format!("{} WITH cdc = {{'enabled' : true}}", create_dst_table_query);
+        let (session, ks_src) = prepare_db(&[create_src_table_query], 1).await.unwrap();
+        let (_, ks_dst) = prepare_db(&[create_dst_table_query], 1).await.unwrap();
+        session.refresh_metadata().await.unwrap();
+
+        let factory = ReplicatorConsumerFactory::new(
+            session.clone(),
+            session.clone(),
+            ks_dst.clone(),
+            name.to_string(),
+            ReplicationOptions {
+                propagate_schema_changes: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut consumers = [factory.new_consumer().await, factory.new_consumer().await];
+
+        for query in [
+            format!("ALTER TABLE {} ADD v2 text", name),
+            format!("INSERT INTO {} (pk, v1, v2) VALUES (1, 2, 'a')", name),
+        ] {
+            session.query(query, ()).await.unwrap();
+            session.await_schema_agreement().await.unwrap();
+        }
+        let result = session
+            .query(
+                format!("SELECT * FROM {}.{}_scylla_cdc_log", ks_src, name),
+                (),
+            )
+            .await
+            .unwrap();
+        let schema = CDCRowSchema::new(&result.col_specs);
+        let row = result.rows.unwrap().remove(0);
+
+        // Every consumer notices the new column, but it is added only once.
+        let results = futures_util::future::join_all(consumers.iter_mut().map(|consumer| {
+            consumer.consume_cdc(CDCRow::from_row(
+                Row {
+                    columns: row.columns.clone(),
+                },
+                &schema,
+            ))
+        }))
+        .await;
+        for result in results {
+            result.unwrap();
+        }
+        compare_changes(&session, &ks_src, &ks_dst, name)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_destination_schema_creation() {
+        let udt_schema = TestUDTSchema {
+            name: "SCHEMA_CREATION_UDT".to_string(),
+            fields: vec![("a", "int"), ("b", "text")],
+        };
+        let schema = TestTableSchema {
+            name: "SCHEMA_CREATION".to_string(),
+            partition_key: vec![("pk1", "int"), ("pk2", "text")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![
+                ("v1", "SCHEMA_CREATION_UDT"),
+                ("v2", "list<frozen<SCHEMA_CREATION_UDT>>"),
+                ("v3", "int static"),
+            ],
+        };
+        let mut schema_queries = get_udt_queries(vec![udt_schema]);
+        schema_queries.push(format!(
+            "{} WITH cdc = {{'enabled' : true}}",
+            get_table_create_query(&schema)
+        ));
+        let (session, ks_src) = prepare_db(&schema_queries, 1).await.unwrap();
+        let ks_dst = unique_name();
+        let table = ReplicatedTable {
+            source_table: schema.name.clone(),
+            dest_keyspace: ks_dst.clone(),
+            dest_table: schema.name.clone(),
+            column_renames: HashMap::new(),
+        };
+        let options = SchemaCreationOptions {
+            replication: Some("{'class': 'SimpleStrategy', 'replication_factor': 1}".to_string()),
+        };
+
+        create_destination_schema(
+            &session,
+            &ks_src,
+            &session,
+            &table,
+            &ColumnFilter::All,
+            &options,
+        )
+        .await
+        .unwrap();
+        // Creating the schema again only checks the existing tables.
+        create_destination_schema(
+            &session,
+            &ks_src,
+            &session,
+            &table,
+            &ColumnFilter::All,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let mut last_read = (0, 0);
+        let operations = [
+            "INSERT INTO SCHEMA_CREATION (pk1, pk2, ck, v1, v2, v3) VALUES (1, 'a', 2, {a: 3, b: 'b'}, [{a: 4, b: 'c'}], 5)",
+            "UPDATE SCHEMA_CREATION SET v1.a = 6 WHERE pk1 = 1 AND pk2 = 'a' AND ck = 2",
+        ];
+        for operation in operations {
+            session.query(operation, ()).await.unwrap();
+            replicate(
+                &session,
+                &ks_src,
+                &ks_dst,
+                &schema.name,
+                &mut last_read,
+                ReplicationOptions::default(),
+            )
+            .await
+            .unwrap();
+            compare_changes(&session, &ks_src, &ks_dst, &schema.name)
+                .await
+                .unwrap();
+        }
+
+        // Tables with different types of columns are incompatible.
+        session
+            .query(
+                format!("ALTER TABLE {}.SCHEMA_CREATION DROP v3", ks_dst),
+                (),
+            )
+            .await
+            .unwrap();
+        session
+            .query(
+                format!("ALTER TABLE {}.SCHEMA_CREATION ADD v3 text static", ks_dst),
+                (),
+            )
+            .await
+            .unwrap();
+        session.await_schema_agreement().await.unwrap();
+        session.refresh_metadata().await.unwrap();
+        create_destination_schema(
+            &session,
+            &ks_src,
+            &session,
+            &table,
+            &ColumnFilter::All,
+            &options,
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let udt_schema = TestUDTSchema {
+            name: "SNAPSHOT_UDT".to_string(),
+            fields: vec![("a", "int"), ("b", "text")],
+        };
+        let schema = TestTableSchema {
+            name: "SNAPSHOT".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![
+                ("v1", "int"),
+                ("v2", "frozen<list<int>>"),
+                ("v3", "set<text>"),
+                ("v4", "SNAPSHOT_UDT"),
+                ("v5", "int static"),
+            ],
+        };
+        let (session, ks_src, ks_dst) = test_replication_with_options(
+            schema.clone(),
+            vec![udt_schema],
+            vec![],
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let operations = [
+            "INSERT INTO SNAPSHOT (pk, ck, v1, v2, v3, v4, v5) VALUES (1, 2, 3, [4, 5], {'a'}, {a: 6, b: 'b'}, 7)",
+            "INSERT INTO SNAPSHOT (pk, ck, v1) VALUES (1, 3, 8) USING TTL 1000",
+            "INSERT INTO SNAPSHOT (pk, ck) VALUES (2, 1)",
+            "UPDATE SNAPSHOT SET v2 = [9] WHERE pk = 3 AND ck = 1",
+            "INSERT INTO SNAPSHOT (pk, v5) VALUES (4, 10)",
+        ];
+        for operation in operations {
+            session.query(operation, ()).await.unwrap();
+        }
+        for pk in 5..100 {
+            session
+                .query(
+                    "INSERT INTO SNAPSHOT (pk, ck, v1) VALUES (?, ?, ?)",
+                    (pk, pk, pk),
+                )
+                .await
+                .unwrap();
+        }
+
+        let snapshot_timestamp =
+            chrono::Duration::microseconds(chrono::Local::now().timestamp_micros());
+        let options = SnapshotOptions {
+            parallelism: 3,
+            token_ranges: 16,
+        };
+        copy_table(
+            &session,
+            &ks_src,
+            &schema.name,
+            &session,
+            &ks_dst,
+            &schema.name,
+            snapshot_timestamp,
+            &ReplicationOptions::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+        compare_changes(&session, &ks_src, &ks_dst, &schema.name)
+            .await
+            .unwrap();
+        compare_timestamps(&session, &ks_src, &ks_dst, &schema)
+            .await
+            .unwrap();
+
+        let ttl_query = format!(
+            "SELECT TTL(v1) FROM {}.SNAPSHOT WHERE pk = 1 AND ck = 3",
+            ks_dst
+        );
+        let (ttl,) = session
+            .query(ttl_query, ())
+            .await
+            .unwrap()
+            .single_row_typed::<(Option<i32>,)>()
+            .unwrap();
+        assert!(ttl.is_some());
+
+        // Changes made after the snapshot started are replicated from the CDC log.
+        session
+            .query(
+                "UPDATE SNAPSHOT SET v3 = v3 + {'c'} WHERE pk = 1 AND ck = 2",
+                (),
+            )
+            .await
+            .unwrap();
+        replicate(
+            &session,
+            &ks_src,
+            &ks_dst,
+            &schema.name,
+            &mut (0, 0),
+            ReplicationOptions::default(),
+        )
+        .await
+        .unwrap();
+        compare_changes(&session, &ks_src, &ks_dst, &schema.name)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_with_row_filter() {
+        let schema = TestTableSchema {
+            name: "SNAPSHOT_FILTERING".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "int")],
+            other_columns: vec![("v", "int"), ("s", "int static")],
+        };
+        let (session, ks_src, ks_dst) =
+            test_replication_with_options(schema.clone(), vec![], vec![], Default::default())
+                .await
+                .unwrap();
+        let operations = [
+            "INSERT INTO SNAPSHOT_FILTERING (pk, ck, v, s) VALUES (1, 1, 1, 10)",
+            "INSERT INTO SNAPSHOT_FILTERING (pk, ck, v) VALUES (1, 2, 2)",
+            "INSERT INTO SNAPSHOT_FILTERING (pk, ck, v, s) VALUES (2, 1, 3, 20)",
+        ];
+        for operation in operations {
+            session.query(operation, ()).await.unwrap();
+        }
+
+        // The first row of the partition is rejected, its static column is copied with the next one.
+        let options = ReplicationOptions {
+            row_filter: RowFilter::default().allow_values("ck", ["2"]),
+            ..Default::default()
+        };
+        let snapshot_timestamp =
+            chrono::Duration::microseconds(chrono::Local::now().timestamp_micros());
+        copy_table(
+            &session,
+            &ks_src,
+            &schema.name,
+            &session,
+            &ks_dst,
+            &schema.name,
+            snapshot_timestamp,
+            &options,
+            &SnapshotOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let rows = session
+            .query(
+                format!("SELECT pk, ck, v, s FROM {}.SNAPSHOT_FILTERING", ks_dst),
+                (),
+            )
+            .await
+            .unwrap()
+            .rows_typed::<(i32, i32, Option<i32>, Option<i32>)>()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(rows, vec![(1, 2, Some(2), Some(10))]);
+    }
+
+    #[tokio::test]
+    async fn test_filtering() {
+        let schema = TestTableSchema {
+            name: "FILTERING".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck", "text")],
+            other_columns: vec![("v1", "int"), ("v2", "text")],
+        };
+        let (session, ks_src, ks_dst) =
+            test_replication_with_options(schema.clone(), vec![], vec![], Default::default())
+                .await
+                .unwrap();
+        let options = ReplicationOptions {
+            column_filter: ColumnFilter::Deny(["v2".to_string()].into()),
+            row_filter: RowFilter::default()
+                .allow_values("pk", ["1", "2"])
+                .allow_values("ck", ["a", "b"]),
+            ..Default::default()
+        };
+        let operations = [
+            "INSERT INTO FILTERING (pk, ck, v1, v2) VALUES (1, 'a', 1, 'secret')",
+            "INSERT INTO FILTERING (pk, ck, v1, v2) VALUES (1, 'b', 2, 'secret')",
+            "INSERT INTO FILTERING (pk, ck, v1, v2) VALUES (1, 'c', 3, 'secret')",
+            "INSERT INTO FILTERING (pk, ck, v1, v2) VALUES (2, 'a', 4, 'secret')",
+            "INSERT INTO FILTERING (pk, ck, v1, v2) VALUES (3, 'a', 5, 'secret')",
+            "UPDATE FILTERING SET v1 = 6 WHERE pk = 3 AND ck = 'b'",
+            "DELETE FROM FILTERING WHERE pk = 1 AND ck >= 'b' AND ck <= 'z'",
+            "DELETE FROM FILTERING WHERE pk = 3",
+        ];
+        let mut last_read = (0, 0);
+        for operation in operations {
+            session.query(operation, ()).await.unwrap();
+            replicate(
+                &session,
+                &ks_src,
+                &ks_dst,
+                &schema.name,
+                &mut last_read,
+                options.clone(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let rows = session
+            .query(
+                format!("SELECT pk, ck, v1, v2 FROM {}.FILTERING", ks_dst),
+                (),
+            )
+            .await
+            .unwrap()
+            .rows_typed::<(i32, String, Option<i32>, Option<String>)>()
+            .unwrap()
+            .map(Result::unwrap)
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "a".to_string(), Some(1), None),
+                (2, "a".to_string(), Some(4), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_table_and_column_renaming() {
+        let udt_schema = TestUDTSchema {
+            name: "RENAMING_UDT".to_string(),
+            fields: vec![("a", "int"), ("b", "text")],
+        };
+        let schema = TestTableSchema {
+            name: "RENAMING".to_string(),
+            partition_key: vec![("pk", "int")],
+            clustering_key: vec![("ck1", "int"), ("ck2", "int")],
+            other_columns: vec![
+                ("v1", "int"),
+                ("v2", "list<int>"),
+                ("v3", "RENAMING_UDT"),
+                ("v4", "int static"),
+            ],
+        };
+        let mut schema_queries = get_udt_queries(vec![udt_schema]);
+        schema_queries.push(format!(
+            "{} WITH cdc = {{'enabled' : true}}",
+            get_table_create_query(&schema)
+        ));
+        let (session, ks_src) = prepare_db(&schema_queries, 1).await.unwrap();
+        let ks_dst = unique_name();
+        let options = ReplicationOptions {
+            column_renames: [
+                ("pk", "id"),
+                ("ck2", "position"),
+                ("v1", "value"),
+                ("v3", "details"),
+            ]
+            .into_iter()
+            .map(|(source, dest)| (source.to_string(), dest.to_string()))
+            .collect(),
+            ..Default::default()
+        };
+        create_destination_schema(
+            &session,
+            &ks_src,
+            &session,
+            &ReplicatedTable {
+                source_table: schema.name.clone(),
+                dest_keyspace: ks_dst.clone(),
+                dest_table: "RENAMED".to_string(),
+                column_renames: options.column_renames.clone(),
+            },
+            &options.column_filter,
+            &SchemaCreationOptions {
+                replication: Some(
+                    "{'class': 'SimpleStrategy', 'replication_factor': 1}".to_string(),
+                ),
+            },
+        )
+        .await
+        .unwrap();
+
+        let operations = [
+            "INSERT INTO RENAMING (pk, ck1, ck2, v1, v2, v3, v4) VALUES (1, 1, 1, 1, [1, 2], {a: 1, b: 'a'}, 1)",
+            "INSERT INTO RENAMING (pk, ck1, ck2, v1) VALUES (1, 1, 2, 2) USING TTL 1000",
+            "INSERT INTO RENAMING (pk, ck1, ck2, v1) VALUES (1, 2, 1, 3)",
+            "INSERT INTO RENAMING (pk, ck1, ck2, v1) VALUES (2, 1, 1, 4)",
+            "UPDATE RENAMING SET v2 = v2 + [3], v3.b = 'b' WHERE pk = 1 AND ck1 = 1 AND ck2 = 1",
+            "DELETE v1 FROM RENAMING WHERE pk = 1 AND ck1 = 1 AND ck2 = 1",
+            "DELETE FROM RENAMING WHERE pk = 1 AND ck1 = 1 AND ck2 > 1",
+            "DELETE FROM RENAMING WHERE pk = 1 AND ck1 = 2 AND ck2 = 1",
+            "DELETE FROM RENAMING WHERE pk = 2",
+        ];
+        let mut last_read = (0, 0);
+        for operation in operations {
+            session.query(operation, ()).await.unwrap();
+            replicate_to(
+                &session,
+                &ks_src,
+                &schema.name,
+                &ks_dst,
+                "RENAMED",
+                &mut last_read,
+                options.clone(),
+            )
+            .await
+            .unwrap();
+
+            let original_rows = session
+                .query(
+                    format!(
+                        "SELECT pk, ck1, ck2, v1, v2, v3, v4 FROM {}.RENAMING",
+                        ks_src
+                    ),
+                    (),
+                )
+                .await
+                .unwrap()
+                .rows
+                .unwrap_or_default();
+            let replicated_rows = session
+                .query(
+                    format!(
+                        "SELECT id, ck1, position, value, v2, details, v4 FROM {}.RENAMED",
+                        ks_dst
+                    ),
+                    (),
+                )
+                .await
+                .unwrap()
+                .rows
+                .unwrap_or_default();
+            assert_eq!(original_rows.len(), replicated_rows.len());
+            for (original, replicated) in original_rows.iter().zip(replicated_rows.iter()) {
+                assert!(equal_rows(original, replicated), "{}", operation);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_postimage_replication_requires_postimage() {
+        let (session, ks) = prepare_db(
+            &[
+                "CREATE TABLE WITHOUT_POSTIMAGE (pk int PRIMARY KEY, v int) WITH cdc = {'enabled': true}".to_string(),
+                "CREATE TABLE WITH_POSTIMAGE (pk int PRIMARY KEY, v int) WITH cdc = {'enabled': true, 'postimage': true}".to_string(),
+            ],
+            1,
+        )
+        .await
+        .unwrap();
+        let options = |replication_mode| ReplicationOptions {
+            replication_mode,
+            ..Default::default()
+        };
+
+        for (table, mode, allowed) in [
+            ("WITHOUT_POSTIMAGE", ReplicationMode::Delta, true),
+            ("WITHOUT_POSTIMAGE", ReplicationMode::Postimage, false),
+            ("WITH_POSTIMAGE", ReplicationMode::Postimage, true),
+        ] {
+            let result = check_cdc_options(&session, &ks, table, &options(mode)).await;
+            assert_eq!(result.is_ok(), allowed, "{} {:?}", table, mode);
+        }
+    }
 }
@@ -1,62 +1,341 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time;
 
+use anyhow::anyhow;
 use futures_util::future::RemoteHandle;
 use futures_util::stream::FuturesUnordered;
-use scylla::SessionBuilder;
+use scylla::Session;
+use scylla_cdc::checkpoints::CDCCheckpointSaver;
 use scylla_cdc::log_reader::{CDCLogReader, CDCLogReaderBuilder};
+use tracing::info;
 
-use crate::replicator_consumer::ReplicatorConsumerFactory;
+use crate::destination_schema::{create_destination_schema, SchemaCreationOptions};
+use crate::replicator_consumer::{ReplicationMode, ReplicationOptions, ReplicatorConsumerFactory};
+use crate::snapshot::{copy_table, SnapshotOptions};
+
+/// Saving the progress of the replication and resuming it from the saved checkpoints.
+pub struct CheckpointOptions {
+    /// Maps source table names to the savers of their progress.
+    /// The progress of every table is saved separately, because the tables share the CDC streams.
+    pub savers: HashMap<String, Arc<dyn CDCCheckpointSaver>>,
+    pub pause_between_saves: time::Duration,
+    /// Ignore the saved progress and start as if the tables were replicated for the first time.
+    /// The saved progress is overwritten as the replication advances.
+    pub reset_progress: bool,
+}
+
+/// A source table and the destination table its changes are replicated to.
+#[derive(Clone, Debug)]
+pub struct ReplicatedTable {
+    pub source_table: String,
+    pub dest_keyspace: String,
+    pub dest_table: String,
+    /// Maps names of source columns to names of the destination columns they are replicated to,
+    /// see [`ReplicationOptions::column_renames`].
+    pub column_renames: HashMap<String, String>,
+}
+
+impl ReplicatedTable {
+    // Options of replicating this table, with its column renames.
+    fn replication_options(&self, options: &ReplicationOptions) -> ReplicationOptions {
+        ReplicationOptions {
+            column_renames: self.column_renames.clone(),
+            ..options.clone()
+        }
+    }
+}
+
+/// Options of a [`Replicator`].
+pub struct ReplicatorOptions {
+    /// Start of the replication, the current time by default.
+    pub start_timestamp: Option<chrono::Duration>,
+    pub window_size: time::Duration,
+    pub safety_interval: time::Duration,
+    pub sleep_interval: time::Duration,
+    pub replication: ReplicationOptions,
+    /// Create the destination schema before replicating, see [`create_destination_schema`].
+    pub schema_creation: Option<SchemaCreationOptions>,
+    /// Copy the existing rows of the source tables before replicating their changes.
+    pub snapshot: Option<SnapshotOptions>,
+    pub checkpoints: Option<CheckpointOptions>,
+}
+
+/// Create [`ReplicatorOptions`] replicating from now with the windows of [`CDCLogReaderBuilder`],
+/// without creating the schema, copying rows or saving checkpoints.
+impl Default for ReplicatorOptions {
+    fn default() -> Self {
+        ReplicatorOptions {
+            start_timestamp: None,
+            window_size: time::Duration::from_secs(60),
+            safety_interval: time::Duration::from_secs(30),
+            sleep_interval: time::Duration::from_secs(10),
+            replication: ReplicationOptions::default(),
+            schema_creation: None,
+            snapshot: None,
+            checkpoints: None,
+        }
+    }
+}
 
 pub struct Replicator {
     log_readers: Vec<CDCLogReader>,
 }
 
 impl Replicator {
-    #[allow(clippy::too_many_arguments)]
+    /// Starts replicating every source table with a separate [`CDCLogReader`].
+    ///
+    /// A table with saved progress resumes from its checkpoints,
+    /// from [`ReplicatorOptions::start_timestamp`] only if it is later. Such a table isn't copied by the snapshot,
+    /// unless [`CheckpointOptions::reset_progress`] is set.
+    ///
+    /// With [`ReplicatorOptions::snapshot`], the tables are copied one after another, before any of them is replicated,
+    /// and the replication of the copied tables starts from the time the first copy started,
+    /// so the start timestamp can't be set. The changes made during all the copies
+    /// must still be in the CDC logs when the last copy finishes, otherwise an error is returned.
     pub async fn new(
-        source_uri: String,
+        source_session: Arc<Session>,
         source_keyspace: String,
-        source_tables: Vec<String>,
-        dest_uri: String,
-        dest_keyspace: String,
-        dest_tables: Vec<String>,
-        start_timestamp: chrono::Duration,
-        window_size: time::Duration,
-        safety_interval: time::Duration,
-        sleep_interval: time::Duration,
+        tables: Vec<ReplicatedTable>,
+        dest_session: Arc<Session>,
+        replicator_options: ReplicatorOptions,
     ) -> anyhow::Result<(Self, FuturesUnordered<RemoteHandle<anyhow::Result<()>>>)> {
-        let source_session = Arc::new(SessionBuilder::new().known_node(source_uri).build().await?);
-        let dest_session = Arc::new(SessionBuilder::new().known_node(dest_uri).build().await?);
-        let mut log_readers = vec![];
-        let handles = FuturesUnordered::new();
+        let ReplicatorOptions {
+            start_timestamp,
+            window_size,
+            safety_interval,
+            sleep_interval,
+            replication: options,
+            schema_creation,
+            snapshot,
+            checkpoints,
+        } = replicator_options;
+        if snapshot.is_some() && start_timestamp.is_some() {
+            return Err(anyhow!(
+                "The start timestamp can't be used together with a snapshot"
+            ));
+        }
+        for table in &tables {
+            check_cdc_options(
+                &source_session,
+                &source_keyspace,
+                &table.source_table,
+                &options,
+            )
+            .await?;
+        }
+
+        if let Some(schema_creation) = schema_creation {
+            for table in &tables {
+                create_destination_schema(
+                    &source_session,
+                    &source_keyspace,
+                    &dest_session,
+                    table,
+                    &options.column_filter,
+                    &schema_creation,
+                )
+                .await?;
+            }
+        }
+
+        let mut resumed_tables = HashSet::new();
+        if let Some(checkpoints) = checkpoints.as_ref().filter(|c| !c.reset_progress) {
+            for source_table in tables.iter().map(|table| &table.source_table) {
+                let saver = checkpoint_saver(checkpoints, source_table)?;
+                if saver.load_last_generation().await?.is_some() {
+                    info!(
+                        "Resuming replication of table {} from saved progress",
+                        source_table
+                    );
+                    resumed_tables.insert(source_table.clone());
+                }
+            }
+        }
+
+        let now = chrono::Duration::microseconds(chrono::Local::now().timestamp_micros());
+        let mut snapshot_timestamp = None;
+        if let Some(snapshot) = snapshot {
+            // Changes made during the copy are replicated from the CDC log.
+            // The log is read from slightly before the copy started
+            // in case the clocks of the nodes are not synchronized,
+            // so some of the copied changes are replicated again.
+            // Values are written with the timestamps of their changes, so it doesn't change them,
+            // except for elements appended to non-frozen lists, which may be duplicated.
+            snapshot_timestamp = Some(now);
+            let mut copied_tables = vec![];
+            for table in &tables {
+                if resumed_tables.contains(&table.source_table) {
+                    continue;
+                }
+                copied_tables.push(&table.source_table);
+                copy_table(
+                    &source_session,
+                    &source_keyspace,
+                    &table.source_table,
+                    &dest_session,
+                    &table.dest_keyspace,
+                    &table.dest_table,
+                    now,
+                    &table.replication_options(&options),
+                    &snapshot,
+                )
+                .await?;
+            }
 
-        for (source_table, dest_table) in source_tables.into_iter().zip(dest_tables) {
+            // The changes made during the copy are lost if they expired from the CDC log
+            // before the reading starts.
+            let log_start = now - chrono::Duration::from_std(safety_interval)?;
+            let copy_duration =
+                chrono::Duration::microseconds(chrono::Local::now().timestamp_micros()) - log_start;
+            for source_table in copied_tables {
+                let cdc_options =
+                    fetch_cdc_options(&source_session, &source_keyspace, source_table).await?;
+                check_copy_duration(&source_keyspace, source_table, &cdc_options, copy_duration)?;
+            }
+        }
+
+        let mut log_readers = Vec::new();
+        let handles = FuturesUnordered::new();
+        for table in &tables {
+            let source_table = &table.source_table;
             let factory = ReplicatorConsumerFactory::new(
+                source_session.clone(),
                 dest_session.clone(),
-                dest_keyspace.clone(),
-                dest_table,
+                table.dest_keyspace.clone(),
+                table.dest_table.clone(),
+                table.replication_options(&options),
             )?;
-            let (log_reader, handle) = CDCLogReaderBuilder::new()
+            let table_start_timestamp = if resumed_tables.contains(source_table) {
+                // The saved checkpoints are used unless they are older than the start timestamp.
+                start_timestamp.unwrap_or_else(chrono::Duration::zero)
+            } else if let Some(snapshot_timestamp) = snapshot_timestamp {
+                snapshot_timestamp - chrono::Duration::from_std(safety_interval)?
+            } else {
+                start_timestamp.unwrap_or(now)
+            };
+
+            let mut builder = CDCLogReaderBuilder::new()
                 .session(source_session.clone())
                 .keyspace(&source_keyspace)
-                .table_name(&source_table)
-                .start_timestamp(start_timestamp)
+                .table_name(source_table)
+                .start_timestamp(table_start_timestamp)
                 .window_size(window_size)
                 .safety_interval(safety_interval)
                 .sleep_interval(sleep_interval)
-                .consumer_factory(Arc::new(factory))
-                .build()
-                .await?;
+                .consumer_factory(Arc::new(factory));
+            if let Some(checkpoints) = &checkpoints {
+                builder = builder
+                    .checkpoint_saver(checkpoint_saver(checkpoints, source_table)?)
+                    .should_save_progress(true)
+                    .should_load_progress(!checkpoints.reset_progress)
+                    .pause_between_saves(checkpoints.pause_between_saves);
+            }
+            let (log_reader, handle) = builder.build().await?;
             log_readers.push(log_reader);
             handles.push(handle);
         }
+
         Ok((Replicator { log_readers }, handles))
     }
 
     pub fn stop(&mut self) {
-        for log_reader in &mut self.log_readers {
+        for log_reader in self.log_readers.iter_mut() {
             log_reader.stop();
         }
     }
 }
+
+// Reads the CDC options of the table, such as `postimage` or `ttl`.
+async fn fetch_cdc_options(
+    session: &Session,
+    keyspace: &str,
+    table: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    Ok(session
+        .query(
+            "SELECT cdc FROM system_schema.scylla_tables WHERE keyspace_name = ? AND table_name = ?",
+            (keyspace, table.to_ascii_lowercase()),
+        )
+        .await?
+        .maybe_first_row_typed::<(Option<HashMap<String, String>>,)>()?
+        .and_then(|(options,)| options)
+        .unwrap_or_default())
+}
+
+// Checks that the CDC log of the source table has the rows needed by the replication mode.
+pub(crate) async fn check_cdc_options(
+    session: &Session,
+    keyspace: &str,
+    table: &str,
+    options: &ReplicationOptions,
+) -> anyhow::Result<()> {
+    let cdc_options = fetch_cdc_options(session, keyspace, table).await?;
+    if options.replication_mode == ReplicationMode::Postimage
+        && cdc_options.get("postimage").map(String::as_str) != Some("true")
+    {
+        return Err(anyhow!(
+            "Replicating {}.{} from post-images requires the postimage CDC option enabled on it",
+            keyspace,
+            table
+        ));
+    }
+
+    Ok(())
+}
+
+// Checks that the CDC log of the table keeps the changes longer than the copy took.
+fn check_copy_duration(
+    keyspace: &str,
+    table: &str,
+    cdc_options: &HashMap<String, String>,
+    copy_duration: chrono::Duration,
+) -> anyhow::Result<()> {
+    // TTL 0 means that the changes never expire.
+    let ttl = match cdc_options.get("ttl").map(|ttl| ttl.parse::<i64>()) {
+        Some(Ok(ttl)) if ttl > 0 => chrono::Duration::seconds(ttl),
+        _ => return Ok(()),
+    };
+    if copy_duration >= ttl {
+        return Err(anyhow!(
+            "Copying the tables took {} seconds, longer than the CDC log of {}.{} keeps the changes ({} seconds), \
+            so the changes made during the copy may be lost. Increase the `ttl` CDC option of the table and copy it again",
+            copy_duration.num_seconds(),
+            keyspace,
+            table,
+            ttl.num_seconds()
+        ));
+    }
+
+    Ok(())
+}
+
+fn checkpoint_saver(
+    checkpoints: &CheckpointOptions,
+    source_table: &str,
+) -> anyhow::Result<Arc<dyn CDCCheckpointSaver>> {
+    checkpoints
+        .savers
+        .get(source_table)
+        .cloned()
+        .ok_or_else(|| anyhow!("No checkpoint saver for table {}", source_table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_copy_duration() {
+        let options = |ttl: &str| HashMap::from([("ttl".to_string(), ttl.to_string())]);
+        let check = |cdc_options, seconds| {
+            check_copy_duration("ks", "t", &cdc_options, chrono::Duration::seconds(seconds))
+        };
+
+        assert!(check(options("86400"), 3600).is_ok());
+        assert!(check(options("3600"), 3600).is_err());
+        // The changes never expire.
+        assert!(check(options("0"), 1_000_000).is_ok());
+        assert!(check(HashMap::new(), 1_000_000).is_ok());
+    }
+}
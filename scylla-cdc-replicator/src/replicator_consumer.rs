@@ -4,17 +4,20 @@ use std::sync::Arc;
 use anyhow::anyhow;
 use async_trait::async_trait;
 use itertools::Itertools;
-use scylla::frame::response::result::CqlValue;
+use scylla::batch::{Batch, BatchStatement, BatchType};
 use scylla::frame::response::result::CqlValue::{Map, Set};
-use scylla::frame::value::Value;
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::frame::value::{MaybeUnset, SerializedValues, Value, ValueList};
 use scylla::prepared_statement::PreparedStatement;
 use scylla::query::Query;
 use scylla::transport::topology::{CollectionType, ColumnKind, CqlType, Table};
 use scylla::Session;
-use tracing::warn;
+use tracing::{info, warn};
 
 use scylla_cdc::consumer::*;
 
+use crate::destination_schema::cql_type_name;
+
 struct PreparedStatementCache {
     queries: HashMap<String, PreparedStatement>,
     query_string_maker: Box<dyn (Fn(&str) -> String) + Sync + Send>,
@@ -28,40 +31,55 @@ impl PreparedStatementCache {
         }
     }
 
-    pub async fn run_query(
+    pub async fn get_query(
         &mut self,
         session: &Arc<Session>,
+        str_param: &str,
+    ) -> anyhow::Result<&PreparedStatement> {
+        if !self.queries.contains_key(str_param) {
+            let query = session
+                .prepare((self.query_string_maker)(str_param))
+                .await?;
+            self.queries.insert(str_param.to_string(), query);
+        }
+
+        Ok(&self.queries[str_param])
+    }
+
+    pub async fn run_query(
+        &mut self,
+        batcher: &mut StatementBatcher,
         values: &[impl Value],
         timestamp: i64,
         str_param: &str,
     ) -> anyhow::Result<()> {
-        let query = match self.queries.get_mut(str_param) {
-            Some(query) => query,
-            None => self.queries.entry(str_param.to_string()).or_insert(
-                session
-                    .prepare((self.query_string_maker)(str_param))
-                    .await?,
-            ),
-        };
+        let query = self.get_query(&batcher.session, str_param).await?.clone();
 
-        run_prepared_statement(session, query.clone(), values, timestamp).await
+        batcher
+            .run_prepared_statement(query, values, timestamp)
+            .await
     }
 }
 
 struct DestinationTableParams {
     session: Arc<Session>,
     keyspace_table_name: String,
+    batcher: StatementBatcher,
 }
 
 struct PrecomputedQueries {
     destination_table_params: DestinationTableParams,
+    // Maps names of source columns to names of destination columns.
+    column_renames: HashMap<String, String>,
     insert_query: PreparedStatement,
+    upsert_query: PreparedStatement,
     partition_delete_query: PreparedStatement,
     delete_query: PreparedStatement,
     delete_value_queries: PreparedStatementCache,
     overwrite_queries: PreparedStatementCache,
     update_list_elements_queries: PreparedStatementCache,
     update_map_or_set_elements_queries: PreparedStatementCache,
+    select_queries: PreparedStatementCache,
 }
 
 impl PrecomputedQueries {
@@ -70,9 +88,13 @@ impl PrecomputedQueries {
         dest_keyspace_name: String,
         dest_table_name: String,
         table_schema: &Table,
+        non_key_columns: &[String],
+        column_renames: &HashMap<String, String>,
+        max_batch_size: Option<usize>,
     ) -> anyhow::Result<PrecomputedQueries> {
+        let dest_name = |name: &String| dest_column_name(column_renames, name).to_string();
         // Iterator for both: partition keys and clustering keys.
-        let keys_iter = get_keys_iter(table_schema);
+        let keys_iter = get_keys_iter(table_schema).map(dest_name);
 
         let keyspace_table_name = format!("{}.{}", dest_keyspace_name, dest_table_name);
         // Clone, because the iterator is consumed.
@@ -84,14 +106,25 @@ impl PrecomputedQueries {
                 "INSERT INTO {} ({}) VALUES ({}) USING TTL ?",
                 keyspace_table_name, names, markers
             ))
-            .await
-            .expect("Preparing insert query failed.");
+            .await?;
+
+        let all_columns = keys_iter
+            .clone()
+            .chain(non_key_columns.iter().map(dest_name));
+        let upsert_query = session
+            .prepare(format!(
+                "INSERT INTO {} ({}) VALUES ({}) USING TTL ?",
+                keyspace_table_name,
+                all_columns.clone().join(","),
+                all_columns.map(|_| "?").join(",")
+            ))
+            .await?;
 
         let keys_cond = keys_iter.map(|name| format!("{} = ?", name)).join(" AND ");
         let partition_keys_cond = &table_schema
             .partition_key
             .iter()
-            .map(|name| format!("{} = ?", name))
+            .map(|name| format!("{} = ?", dest_name(name)))
             .join(" AND ");
 
         let partition_delete_query = session
@@ -99,16 +132,14 @@ impl PrecomputedQueries {
                 "DELETE FROM {} WHERE {}",
                 keyspace_table_name, partition_keys_cond
             ))
-            .await
-            .expect("Preparing partition delete query failed.");
+            .await?;
 
         let delete_query = session
             .prepare(format!(
                 "DELETE FROM {} WHERE {}",
                 keyspace_table_name, keys_cond
             ))
-            .await
-            .expect("Preparing delete query failed.");
+            .await?;
 
         let (c_ks_t, c_k_c) = (keyspace_table_name.clone(), keys_cond.clone());
         let delete_value_queries = PreparedStatementCache::new(move |column_name| {
@@ -144,20 +175,29 @@ impl PrecomputedQueries {
             )
         });
 
+        let (c_ks_t, c_k_c) = (keyspace_table_name.clone(), keys_cond.clone());
+        let select_queries = PreparedStatementCache::new(move |column_names| {
+            format!("SELECT {} FROM {} WHERE {}", column_names, c_ks_t, c_k_c)
+        });
+
         let destination_table_params = DestinationTableParams {
+            batcher: StatementBatcher::new(session.clone(), max_batch_size),
             session,
             keyspace_table_name,
         };
 
         Ok(PrecomputedQueries {
             destination_table_params,
+            column_renames: column_renames.clone(),
             insert_query,
+            upsert_query,
             partition_delete_query,
             delete_query,
             delete_value_queries,
             overwrite_queries,
             update_list_elements_queries,
             update_map_or_set_elements_queries,
+            select_queries,
         })
     }
 
@@ -166,33 +206,31 @@ impl PrecomputedQueries {
         values: &[impl Value],
         timestamp: i64,
     ) -> anyhow::Result<()> {
-        run_prepared_statement(
-            &self.destination_table_params.session,
-            self.partition_delete_query.clone(),
-            values,
-            timestamp,
-        )
-        .await
+        self.destination_table_params
+            .batcher
+            .run_prepared_statement(self.partition_delete_query.clone(), values, timestamp)
+            .await
     }
 
     async fn insert_value(&mut self, values: &[impl Value], timestamp: i64) -> anyhow::Result<()> {
-        run_prepared_statement(
-            &self.destination_table_params.session,
-            self.insert_query.clone(),
-            values,
-            timestamp,
-        )
-        .await
+        self.destination_table_params
+            .batcher
+            .run_prepared_statement(self.insert_query.clone(), values, timestamp)
+            .await
+    }
+
+    async fn upsert_row(&mut self, values: &[impl Value], timestamp: i64) -> anyhow::Result<()> {
+        self.destination_table_params
+            .batcher
+            .run_prepared_statement(self.upsert_query.clone(), values, timestamp)
+            .await
     }
 
     async fn delete_row(&mut self, values: &[impl Value], timestamp: i64) -> anyhow::Result<()> {
-        run_prepared_statement(
-            &self.destination_table_params.session,
-            self.delete_query.clone(),
-            values,
-            timestamp,
-        )
-        .await
+        self.destination_table_params
+            .batcher
+            .run_prepared_statement(self.delete_query.clone(), values, timestamp)
+            .await
     }
 
     async fn overwrite_value(
@@ -203,10 +241,10 @@ impl PrecomputedQueries {
     ) -> anyhow::Result<()> {
         self.overwrite_queries
             .run_query(
-                &self.destination_table_params.session,
+                &mut self.destination_table_params.batcher,
                 values,
                 timestamp,
-                column_name,
+                dest_column_name(&self.column_renames, column_name),
             )
             .await
     }
@@ -222,10 +260,10 @@ impl PrecomputedQueries {
         // https://docs.scylladb.com/using-scylla/cdc/cdc-advanced-types/#collection-wide-tombstones-and-timestamps
         self.delete_value_queries
             .run_query(
-                &self.destination_table_params.session,
+                &mut self.destination_table_params.batcher,
                 values,
                 timestamp,
-                column_name,
+                dest_column_name(&self.column_renames, column_name),
             )
             .await
     }
@@ -238,10 +276,10 @@ impl PrecomputedQueries {
     ) -> anyhow::Result<()> {
         self.update_map_or_set_elements_queries
             .run_query(
-                &self.destination_table_params.session,
+                &mut self.destination_table_params.batcher,
                 values,
                 timestamp,
-                column_name,
+                dest_column_name(&self.column_renames, column_name),
             )
             .await
     }
@@ -271,51 +309,210 @@ impl PrecomputedQueries {
         // In case of deleting, we simply set the value to null.
         self.update_list_elements_queries
             .run_query(
-                &self.destination_table_params.session,
+                &mut self.destination_table_params.batcher,
                 values,
                 timestamp,
-                column_name,
+                dest_column_name(&self.column_renames, column_name),
             )
             .await
     }
 
     async fn update_udt_elements(
         &mut self,
-        element_name: &str,
+        column_name: &str,
+        field_name: &str,
         values: &[impl Value],
         timestamp: i64,
     ) -> anyhow::Result<()> {
+        let element_name = format!(
+            "{}.{}",
+            dest_column_name(&self.column_renames, column_name),
+            field_name
+        );
         self.overwrite_queries
             .run_query(
-                &self.destination_table_params.session,
+                &mut self.destination_table_params.batcher,
                 values,
                 timestamp,
-                element_name,
+                &element_name,
             )
             .await
     }
+
+    // Reads the given columns of a row of the destination table.
+    async fn select_columns(
+        &mut self,
+        values: &[impl Value],
+        column_names: &[&String],
+    ) -> anyhow::Result<Option<Row>> {
+        // Statements of the current CDC batch might modify the row.
+        self.destination_table_params.batcher.flush().await?;
+
+        let column_names = column_names
+            .iter()
+            .map(|name| dest_column_name(&self.column_renames, name))
+            .join(",");
+        let session = &self.destination_table_params.session;
+        let query = self
+            .select_queries
+            .get_query(session, &column_names)
+            .await?;
+        let rows = session.execute(query, values).await?.rows;
+
+        Ok(rows.unwrap_or_default().into_iter().next())
+    }
+
+    fn set_partition_key(&mut self, partition_key: Vec<Option<CqlValue>>) {
+        self.destination_table_params
+            .batcher
+            .set_partition_key(partition_key);
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        self.destination_table_params.batcher.flush().await
+    }
 }
 
-async fn run_prepared_statement(
-    session: &Arc<Session>,
-    mut query: PreparedStatement,
-    values: &[impl Value],
+// Statements of a single partition, waiting to be sent as one batch.
+struct PendingBatch {
+    partition_key: Vec<Option<CqlValue>>,
     timestamp: i64,
-) -> anyhow::Result<()> {
-    query.set_timestamp(Some(timestamp));
-    session.execute(&query, values).await?;
+    statements: Vec<BatchStatement>,
+    values: Vec<SerializedValues>,
+    // Size of the serialized values of the statements in bytes.
+    size: usize,
+}
 
-    Ok(())
+// Runs statements on the destination table.
+// If batching is enabled, statements are collected until `flush` is called
+// and sent as one unlogged batch per partition.
+struct StatementBatcher {
+    session: Arc<Session>,
+    // `None` if batching is disabled.
+    max_batch_size: Option<usize>,
+    // Partition key of the replicated row, which the next statements modify.
+    partition_key: Vec<Option<CqlValue>>,
+    batches: Vec<PendingBatch>,
 }
 
-async fn run_statement(
-    session: &Arc<Session>,
-    mut query: Query,
-    values: &[impl Value],
-    timestamp: i64,
-) -> anyhow::Result<()> {
-    query.set_timestamp(Some(timestamp));
-    session.query(query, values).await?;
+impl StatementBatcher {
+    fn new(session: Arc<Session>, max_batch_size: Option<usize>) -> StatementBatcher {
+        StatementBatcher {
+            session,
+            max_batch_size,
+            partition_key: vec![],
+            batches: vec![],
+        }
+    }
+
+    fn set_partition_key(&mut self, partition_key: Vec<Option<CqlValue>>) {
+        self.partition_key = partition_key;
+    }
+
+    async fn run_prepared_statement(
+        &mut self,
+        mut query: PreparedStatement,
+        values: &[impl Value],
+        timestamp: i64,
+    ) -> anyhow::Result<()> {
+        if self.max_batch_size.is_some() {
+            return self.add_statement(query.into(), values, timestamp).await;
+        }
+
+        query.set_timestamp(Some(timestamp));
+        self.session.execute(&query, values).await?;
+
+        Ok(())
+    }
+
+    async fn run_statement(
+        &mut self,
+        mut query: Query,
+        values: &[impl Value],
+        timestamp: i64,
+    ) -> anyhow::Result<()> {
+        if self.max_batch_size.is_some() {
+            return self.add_statement(query.into(), values, timestamp).await;
+        }
+
+        query.set_timestamp(Some(timestamp));
+        self.session.query(query, values).await?;
+
+        Ok(())
+    }
+
+    async fn add_statement(
+        &mut self,
+        statement: BatchStatement,
+        values: &[impl Value],
+        timestamp: i64,
+    ) -> anyhow::Result<()> {
+        let values = values.serialized()?.into_owned();
+        let size = values
+            .iter()
+            .map(|value| 4 + value.map_or(0, <[u8]>::len))
+            .sum::<usize>();
+        let max_batch_size = self.max_batch_size.unwrap_or_default();
+
+        let position = self.batches.iter().position(|batch| {
+            batch.partition_key == self.partition_key && batch.timestamp == timestamp
+        });
+        if let Some(position) = position {
+            if self.batches[position].size + size <= max_batch_size {
+                let batch = &mut self.batches[position];
+                batch.statements.push(statement);
+                batch.values.push(values);
+                batch.size += size;
+                return Ok(());
+            }
+
+            // The batch would be too big, so it's sent without the statement.
+            send_batch(&self.session, &self.batches[position]).await?;
+            self.batches.remove(position);
+        }
+
+        self.batches.push(PendingBatch {
+            partition_key: self.partition_key.clone(),
+            timestamp,
+            statements: vec![statement],
+            values: vec![values],
+            size,
+        });
+
+        Ok(())
+    }
+
+    // Sends all collected statements.
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        while let Some(batch) = self.batches.first() {
+            send_batch(&self.session, batch).await?;
+            self.batches.remove(0);
+        }
+
+        Ok(())
+    }
+}
+
+async fn send_batch(session: &Session, pending: &PendingBatch) -> anyhow::Result<()> {
+    match (pending.statements.as_slice(), pending.values.as_slice()) {
+        // There is no need to batch a single statement.
+        ([BatchStatement::PreparedStatement(query)], [values]) => {
+            let mut query = query.clone();
+            query.set_timestamp(Some(pending.timestamp));
+            session.execute(&query, values).await?;
+        }
+        ([BatchStatement::Query(query)], [values]) => {
+            let mut query = query.clone();
+            query.set_timestamp(Some(pending.timestamp));
+            session.query(query, values).await?;
+        }
+        _ => {
+            let mut batch =
+                Batch::new_with_statements(BatchType::Unlogged, pending.statements.clone());
+            batch.set_timestamp(Some(pending.timestamp));
+            session.batch(&batch, &pending.values).await?;
+        }
+    }
 
     Ok(())
 }
@@ -325,48 +522,469 @@ struct SourceTableData {
     non_key_columns: Vec<String>,
 }
 
+/// Policy of handling conflicts detected with pre-images of the replicated changes.
+///
+/// A change is in conflict with the destination table if a column modified by the change
+/// has a different value in the destination row than in the pre-image of the change,
+/// i.e. the destination row diverged from the source row.
+/// A column that already has the value written by the change is not in conflict,
+/// so replicating the same changes again doesn't report conflicts.
+///
+/// Detecting conflicts requires the `preimage` CDC option enabled on the source table.
+/// Only columns of native, tuple and frozen types are compared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Pre-images are not compared with the destination table.
+    #[default]
+    Ignore,
+    /// Logs a warning and applies the change.
+    Report,
+    /// Logs a warning and doesn't apply the change, keeping the destination row as it is.
+    Skip,
+    /// Stops the replication with an error.
+    Halt,
+}
+
+/// Rows of the CDC log used to replicate changes of rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationMode {
+    /// Changes are applied column by column from delta rows.
+    #[default]
+    Delta,
+    /// Every inserted or updated row is written as a whole from its post-image,
+    /// with a single INSERT statement using the timestamp and TTL of the change.
+    /// Requires the `postimage` CDC option enabled on the source table,
+    /// which is checked when the replication starts.
+    ///
+    /// All non-null columns of the row get the timestamp of the change,
+    /// including columns not modified by it. Elements of non-frozen lists get new timeuuids.
+    /// Null columns are written only if the change modified them.
+    /// Deletions are still applied from delta rows.
+    Postimage,
+}
+
+/// Default maximum size of values of the statements sent in one batch, in bytes.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 64 * 1024;
+
+/// Columns of the source table replicated to the destination table.
+/// Partition and clustering key columns are always replicated.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColumnFilter {
+    /// All columns are replicated.
+    #[default]
+    All,
+    /// Only the listed columns are replicated.
+    Allow(HashSet<String>),
+    /// All columns except the listed ones are replicated.
+    Deny(HashSet<String>),
+}
+
+impl ColumnFilter {
+    /// Returns whether the non-key column is replicated.
+    pub fn is_replicated(&self, column_name: &str) -> bool {
+        match self {
+            ColumnFilter::All => true,
+            ColumnFilter::Allow(columns) => columns.contains(column_name),
+            ColumnFilter::Deny(columns) => !columns.contains(column_name),
+        }
+    }
+}
+
+/// Predicate over values of partition and clustering key columns of the replicated rows.
+///
+/// A row is replicated if, for every column with allowed values,
+/// the value of the column is one of them.
+/// Values are compared by their textual representation, e.g. `42`, `abc`
+/// or a hyphenated UUID; keys of other than textual, numeric, boolean,
+/// UUID and inet types match no values.
+///
+/// Deletions of partitions and clustering ranges are checked only against
+/// the partition key, since all rows they delete from the destination table passed the filter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RowFilter {
+    allowed_values: HashMap<String, HashSet<String>>,
+}
+
+impl RowFilter {
+    /// Adds a condition that `column_name` has one of `values`.
+    /// Adding a condition for a column again replaces its values.
+    pub fn allow_values(
+        mut self,
+        column_name: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.allowed_values.insert(
+            column_name.into(),
+            values.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Returns names of the columns with allowed values.
+    pub fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.allowed_values.keys().map(String::as_str)
+    }
+
+    // Returns whether the row with given key values passes the filter.
+    // Conditions of columns missing in `values` or having null values are not checked.
+    pub(crate) fn matches<'a>(
+        &self,
+        values: impl Iterator<Item = (&'a str, Option<&'a CqlValue>)>,
+    ) -> bool {
+        values
+            .filter_map(|(name, value)| Some((self.allowed_values.get(name)?, value?)))
+            .all(|(allowed, value)| {
+                key_value_to_string(value).is_some_and(|value| allowed.contains(&value))
+            })
+    }
+}
+
+/// Options of replicating changes to the destination table.
+#[derive(Clone, Debug)]
+pub struct ReplicationOptions {
+    pub conflict_policy: ConflictPolicy,
+    pub replication_mode: ReplicationMode,
+    /// Statements replicating a single CDC batch are sent as one unlogged batch per partition,
+    /// so every change is applied atomically within its partition.
+    /// If the size of values of the statements in a batch would exceed `max_batch_size` bytes,
+    /// the batch is split, and a statement exceeding it alone is sent separately.
+    /// `None` disables batching, every statement is sent separately.
+    pub max_batch_size: Option<usize>,
+    /// Whether columns added to the source table are added to the destination table.
+    /// Otherwise, only columns present in both tables are replicated.
+    pub propagate_schema_changes: bool,
+    /// Whether propagating schema changes also drops columns of the destination table
+    /// missing in the source table, including columns added only to the destination table.
+    pub drop_removed_columns: bool,
+    pub column_filter: ColumnFilter,
+    pub row_filter: RowFilter,
+    /// Maps names of source columns to names of the destination columns they are replicated to.
+    /// Other columns are replicated to columns of the same names.
+    /// Column and row filters refer to the names of source columns.
+    pub column_renames: HashMap<String, String>,
+}
+
+/// Create [`ReplicationOptions`] replicating delta rows of all columns and rows in batches
+/// of at most [`DEFAULT_MAX_BATCH_SIZE`] bytes, ignoring conflicts
+/// and not propagating schema changes.
+impl Default for ReplicationOptions {
+    fn default() -> Self {
+        ReplicationOptions {
+            conflict_policy: ConflictPolicy::default(),
+            replication_mode: ReplicationMode::default(),
+            max_batch_size: Some(DEFAULT_MAX_BATCH_SIZE),
+            propagate_schema_changes: false,
+            drop_removed_columns: false,
+            column_filter: ColumnFilter::default(),
+            row_filter: RowFilter::default(),
+            column_renames: HashMap::new(),
+        }
+    }
+}
+
+// Values from a pre-image, waiting for the change that follows it in the same batch.
+struct PreImage {
+    keys: Vec<Option<CqlValue>>,
+    values: HashMap<String, Option<CqlValue>>,
+}
+
+// A change of a row waiting for its post-image.
+struct PendingChange {
+    keys: Vec<Option<CqlValue>>,
+    ttl: Option<i64>,
+    // Non-key columns set, deleted or with elements removed by the change.
+    modified_columns: HashSet<String>,
+    // False if the change was skipped because of a conflict.
+    apply: bool,
+}
+
 pub(crate) struct ReplicatorConsumer {
     source_table_data: SourceTableData,
     precomputed_queries: PrecomputedQueries,
+    options: ReplicationOptions,
+    source_session: Arc<Session>,
+    dest_keyspace_name: String,
+    dest_table_name: String,
+    // Shared by the consumers of the table, so only one of them changes its schema at a time.
+    schema_change_lock: Arc<tokio::sync::Mutex<()>>,
+
+    // Names of the columns of the source table found in the CDC log,
+    // `None` until the first row is consumed.
+    source_columns: Option<HashSet<String>>,
+    // Fingerprint of the CDC log schema of the last consumed row.
+    source_fingerprint: Option<u64>,
 
     // Stores data for left side range delete while waiting for its right counterpart.
     left_range_included: bool,
     left_range_values: Vec<Option<CqlValue>>,
+
+    // Stores pre-images of the current batch while waiting for their changes.
+    preimages: Vec<PreImage>,
+
+    // Stores changes of the current batch while waiting for their post-images.
+    pending_changes: Vec<PendingChange>,
 }
 
 impl ReplicatorConsumer {
     pub(crate) async fn new(
-        session: Arc<Session>,
+        source_session: Arc<Session>,
+        dest_session: Arc<Session>,
         dest_keyspace_name: String,
         dest_table_name: String,
         table_schema: Table,
-    ) -> ReplicatorConsumer {
-        // Collect names of columns that are not clustering or partition key.
-        let non_key_columns = table_schema
-            .columns
-            .iter()
-            .filter(|column| {
-                column.1.kind != ColumnKind::Clustering && column.1.kind != ColumnKind::PartitionKey
-            })
-            .map(|column| column.0.clone())
-            .collect::<Vec<String>>();
-
-        let precomputed_queries =
-            PrecomputedQueries::new(session, dest_keyspace_name, dest_table_name, &table_schema)
-                .await
-                .expect("Preparing precomputed queries failed.");
+        options: ReplicationOptions,
+        schema_change_lock: Arc<tokio::sync::Mutex<()>>,
+    ) -> anyhow::Result<ReplicatorConsumer> {
+        let non_key_columns = get_non_key_columns(&table_schema)
+            .into_iter()
+            .filter(|name| options.column_filter.is_replicated(name))
+            .collect::<Vec<_>>();
+
+        let precomputed_queries = PrecomputedQueries::new(
+            dest_session,
+            dest_keyspace_name.clone(),
+            dest_table_name.clone(),
+            &table_schema,
+            &non_key_columns,
+            &options.column_renames,
+            options.max_batch_size,
+        )
+        .await?;
 
         let source_table_data = SourceTableData {
             table_schema,
             non_key_columns,
         };
 
-        ReplicatorConsumer {
+        Ok(ReplicatorConsumer {
             source_table_data,
             precomputed_queries,
+            options,
+            source_session,
+            dest_keyspace_name,
+            dest_table_name,
+            schema_change_lock,
+            source_columns: None,
+            source_fingerprint: None,
             left_range_included: false,
             left_range_values: vec![],
+            preimages: vec![],
+            pending_changes: vec![],
+        })
+    }
+
+    // Checks if the columns of the source table changed since the last consumed row.
+    // If so, the schema of the destination table is refreshed (and updated, if propagating
+    // schema changes is enabled), and the statements are prepared again.
+    async fn check_schema(&mut self, data: &CDCRow<'_>) -> anyhow::Result<()> {
+        let fingerprint = data.schema_fingerprint();
+        if self.source_fingerprint == Some(fingerprint) {
+            return Ok(());
         }
+        if let Some(source_columns) = &self.source_columns {
+            if source_columns.len() == data.get_non_cdc_column_names().count()
+                && data
+                    .get_non_cdc_column_names()
+                    .all(|name| source_columns.contains(name))
+            {
+                self.source_fingerprint = Some(fingerprint);
+                return Ok(());
+            }
+        }
+
+        let source_columns = data
+            .get_non_cdc_column_names()
+            .map(str::to_string)
+            .collect::<HashSet<_>>();
+        let dest_columns = &self.source_table_data.table_schema.columns;
+        let is_schema_changed = self.source_columns.is_some()
+            || source_columns.len() != dest_columns.len()
+            || source_columns
+                .iter()
+                .any(|name| !dest_columns.contains_key(name));
+
+        if is_schema_changed {
+            info!(
+                "Columns of the table {}.{} changed, refreshing the schema of {}.{}.",
+                data.keyspace(),
+                data.table_name(),
+                self.dest_keyspace_name,
+                self.dest_table_name
+            );
+            self.precomputed_queries.flush().await?;
+            if self.options.propagate_schema_changes {
+                self.propagate_schema_change(data.keyspace(), data.table_name())
+                    .await?;
+            }
+            self.refresh_table_schema().await?;
+        }
+
+        let table_schema = &self.source_table_data.table_schema;
+        for name in source_columns.iter() {
+            if !table_schema.columns.contains_key(name)
+                && self.options.column_filter.is_replicated(name)
+            {
+                warn!(
+                    "Column {} doesn't exist in {}.{} and won't be replicated.",
+                    name, self.dest_keyspace_name, self.dest_table_name
+                );
+            }
+        }
+        self.source_table_data.non_key_columns = get_non_key_columns(table_schema)
+            .into_iter()
+            .filter(|name| {
+                source_columns.contains(name) && self.options.column_filter.is_replicated(name)
+            })
+            .collect();
+        if is_schema_changed {
+            // Prepare statements for the replicated columns.
+            self.precomputed_queries = PrecomputedQueries::new(
+                self.precomputed_queries
+                    .destination_table_params
+                    .session
+                    .clone(),
+                self.dest_keyspace_name.clone(),
+                self.dest_table_name.clone(),
+                table_schema,
+                &self.source_table_data.non_key_columns,
+                &self.options.column_renames,
+                self.options.max_batch_size,
+            )
+            .await?;
+        }
+        self.source_columns = Some(source_columns);
+        self.source_fingerprint = Some(fingerprint);
+
+        Ok(())
+    }
+
+    // Adds columns of the source table missing in the destination table
+    // and, if enabled, drops columns of the destination table missing in the source table.
+    // Other consumers of the table may have changed its schema already,
+    // so it is compared with the current schema of the destination table.
+    async fn propagate_schema_change(
+        &mut self,
+        source_keyspace: &str,
+        source_table: &str,
+    ) -> anyhow::Result<()> {
+        let _lock = self.schema_change_lock.lock().await;
+        self.source_session.refresh_metadata().await?;
+        let source_schema = get_table_schema(&self.source_session, source_keyspace, source_table)?;
+        let dest_session = self
+            .precomputed_queries
+            .destination_table_params
+            .session
+            .clone();
+        dest_session.refresh_metadata().await?;
+        let dest_schema = get_dest_table_schema(
+            &dest_session,
+            &self.dest_keyspace_name,
+            &self.dest_table_name,
+            &self.options.column_renames,
+        )?;
+        let keyspace_table_name = format!("{}.{}", self.dest_keyspace_name, self.dest_table_name);
+
+        for (name, column) in source_schema.columns.iter() {
+            if !dest_schema.columns.contains_key(name)
+                && self.options.column_filter.is_replicated(name)
+            {
+                let name = dest_column_name(&self.options.column_renames, name);
+                info!("Adding column {} to {}.", name, keyspace_table_name);
+                dest_session
+                    .query(
+                        format!(
+                            "ALTER TABLE {} ADD {} {}",
+                            keyspace_table_name,
+                            name,
+                            cql_type_name(&column.type_)
+                        ),
+                        (),
+                    )
+                    .await?;
+            }
+        }
+        for name in dest_schema.columns.keys() {
+            if self.options.drop_removed_columns && !source_schema.columns.contains_key(name) {
+                let name = dest_column_name(&self.options.column_renames, name);
+                info!("Dropping column {} from {}.", name, keyspace_table_name);
+                dest_session
+                    .query(
+                        format!("ALTER TABLE {} DROP {}", keyspace_table_name, name),
+                        (),
+                    )
+                    .await?;
+            }
+        }
+        dest_session.await_schema_agreement().await?;
+
+        Ok(())
+    }
+
+    async fn refresh_table_schema(&mut self) -> anyhow::Result<()> {
+        let session = &self.precomputed_queries.destination_table_params.session;
+        session.refresh_metadata().await?;
+        self.source_table_data.table_schema = get_dest_table_schema(
+            session,
+            &self.dest_keyspace_name,
+            &self.dest_table_name,
+            &self.options.column_renames,
+        )?;
+
+        Ok(())
+    }
+
+    // Replicates a change of a row that passed the row filter.
+    async fn apply_change(&mut self, data: CDCRow<'_>) -> anyhow::Result<()> {
+        self.check_schema(&data).await?;
+        let partition_key = self
+            .source_table_data
+            .table_schema
+            .partition_key
+            .iter()
+            .map(|name| data.get_value(name).clone())
+            .collect();
+        self.precomputed_queries.set_partition_key(partition_key);
+
+        match data.operation {
+            OperationType::RowUpdate | OperationType::RowInsert
+                if self.options.replication_mode == ReplicationMode::Postimage =>
+            {
+                self.wait_for_postimage(data).await?
+            }
+            OperationType::RowUpdate => self.update(data).await?,
+            OperationType::RowInsert => self.insert(data).await?,
+            OperationType::RowDelete => self.delete_row(data).await?,
+            OperationType::PartitionDelete => self.delete_partition(data).await?,
+            OperationType::RowRangeDelExclLeft => self.delete_row_range_left(data, false),
+            OperationType::RowRangeDelInclLeft => self.delete_row_range_left(data, true),
+            OperationType::RowRangeDelExclRight => self.delete_row_range_right(data, false).await?,
+            OperationType::RowRangeDelInclRight => self.delete_row_range_right(data, true).await?,
+            OperationType::PreImage => self.store_preimage(data),
+            OperationType::PostImage => self.upsert_postimage(data).await?,
+        }
+
+        Ok(())
+    }
+
+    // Checks if the row modified by the change passes the row filter.
+    fn is_replicated_row(&self, data: &CDCRow<'_>) -> bool {
+        let table_schema = &self.source_table_data.table_schema;
+        // Deleted ranges are identified only by the partition key.
+        let keys_count = match data.operation {
+            OperationType::RowRangeDelExclLeft
+            | OperationType::RowRangeDelInclLeft
+            | OperationType::RowRangeDelExclRight
+            | OperationType::RowRangeDelInclRight => table_schema.partition_key.len(),
+            _ => table_schema.partition_key.len() + table_schema.clustering_key.len(),
+        };
+
+        self.options.row_filter.matches(
+            get_keys_iter(table_schema)
+                .take(keys_count)
+                .map(|name| (name.as_str(), data.get_value(name).as_ref())),
+        )
     }
 
     fn get_timestamp(data: &CDCRow<'_>) -> i64 {
@@ -510,11 +1128,10 @@ impl ReplicatorConsumer {
 
         for (i, (field, value)) in udt_fields.iter().enumerate() {
             if value.is_some() || deleted_ids.contains(&i) {
-                let element_name = format!("{}.{}", column_name, field);
                 // Order of values: ttl, updated element, pk condition values.
                 values_for_update[1] = value.as_ref();
                 self.precomputed_queries
-                    .update_udt_elements(&element_name, values_for_update, timestamp)
+                    .update_udt_elements(column_name, field, values_for_update, timestamp)
                     .await?;
             }
         }
@@ -581,7 +1198,12 @@ impl ReplicatorConsumer {
         let keys_equality_cond = table_schema
             .partition_key
             .iter()
-            .map(|name| format!("{} = ?", name))
+            .map(|name| {
+                format!(
+                    "{} = ?",
+                    dest_column_name(&self.options.column_renames, name)
+                )
+            })
             .join(" AND ");
 
         let mut conditions = vec![keys_equality_cond];
@@ -614,13 +1236,11 @@ impl ReplicatorConsumer {
             conditions.join(" AND ")
         ));
 
-        run_statement(
-            &self.precomputed_queries.destination_table_params.session,
-            query,
-            &query_values,
-            timestamp,
-        )
-        .await
+        self.precomputed_queries
+            .destination_table_params
+            .batcher
+            .run_statement(query, &query_values, timestamp)
+            .await
     }
 
     fn add_range_condition<'a>(
@@ -655,9 +1275,10 @@ impl ReplicatorConsumer {
             "({}) {} ({})",
             table_schema.clustering_key[..first_null_index]
                 .iter()
+                .map(|name| dest_column_name(&self.options.column_renames, name))
                 .join(","),
             relation,
-            std::iter::repeat("?").take(first_null_index).join(",")
+            std::iter::repeat_n("?", first_null_index).join(",")
         );
 
         let query_values = values[..first_null_index].to_vec();
@@ -712,7 +1333,187 @@ impl ReplicatorConsumer {
         Ok(())
     }
 
+    // Stores values of the pre-image, to compare them with the destination row
+    // before applying the change.
+    fn store_preimage(&mut self, mut data: CDCRow<'_>) {
+        if self.options.conflict_policy == ConflictPolicy::Ignore {
+            return;
+        }
+
+        let table_schema = &self.source_table_data.table_schema;
+        let keys = get_keys_iter(table_schema)
+            .map(|name| data.take_value(name))
+            .collect();
+        let values = self
+            .source_table_data
+            .non_key_columns
+            .iter()
+            .filter(|name| is_overwritten_as_whole(&table_schema.columns[*name].type_))
+            .map(|name| (name.clone(), data.take_value(name)))
+            .collect();
+
+        self.preimages.push(PreImage { keys, values });
+    }
+
+    // Returns names of the columns modified by the change
+    // whose values in the destination row differ from the pre-image.
+    async fn find_conflicts(&mut self, data: &CDCRow<'_>) -> anyhow::Result<Vec<String>> {
+        let (_, _, keys) = self.get_common_cdc_row_data(data);
+        let preimage = match self
+            .preimages
+            .iter()
+            .position(|preimage| keys_match(&preimage.keys, &keys))
+        {
+            Some(position) => self.preimages.swap_remove(position),
+            None => return Ok(vec![]),
+        };
+
+        let modified_columns = preimage
+            .values
+            .keys()
+            .filter(|name| data.get_value(name).is_some() || data.is_value_deleted(name))
+            .collect::<Vec<_>>();
+        if modified_columns.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let destination_values = match self
+            .precomputed_queries
+            .select_columns(&keys, &modified_columns)
+            .await?
+        {
+            Some(row) => row.columns,
+            // The row doesn't exist in the destination table.
+            None => vec![None; modified_columns.len()],
+        };
+
+        Ok(modified_columns
+            .into_iter()
+            .zip(destination_values.iter())
+            .filter(|(name, value)| {
+                !values_equal(value, &preimage.values[*name])
+                    && !values_equal(value, data.get_value(name))
+            })
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    // Handles conflicts of the change with the destination table according to the conflict policy.
+    // Returns whether the change should be applied.
+    async fn resolve_conflicts(&mut self, data: &CDCRow<'_>) -> anyhow::Result<bool> {
+        let conflicts = self.find_conflicts(data).await?;
+        if conflicts.is_empty() {
+            return Ok(true);
+        }
+
+        let (_, _, keys) = self.get_common_cdc_row_data(data);
+        let description = format!(
+            "Columns {} of the row {:?} in {} diverged from the source table",
+            conflicts.join(", "),
+            keys,
+            self.precomputed_queries
+                .destination_table_params
+                .keyspace_table_name
+        );
+        match self.options.conflict_policy {
+            ConflictPolicy::Ignore => Ok(true),
+            ConflictPolicy::Report => {
+                warn!("{}, applying the change.", description);
+                Ok(true)
+            }
+            ConflictPolicy::Skip => {
+                warn!("{}, skipping the change.", description);
+                Ok(false)
+            }
+            ConflictPolicy::Halt => Err(anyhow!(description)),
+        }
+    }
+
+    // Resolves conflicts of the change and waits for its post-image to apply it.
+    async fn wait_for_postimage(&mut self, data: CDCRow<'_>) -> anyhow::Result<()> {
+        let apply = self.resolve_conflicts(&data).await?;
+        let (_, _, keys) = self.get_common_cdc_row_data(&data);
+        let keys = keys.into_iter().map(|key| key.cloned()).collect();
+        let modified_columns = self
+            .source_table_data
+            .non_key_columns
+            .iter()
+            .filter(|name| {
+                data.get_value(name).is_some()
+                    || data.is_value_deleted(name)
+                    || !data.get_deleted_elements(name).is_empty()
+            })
+            .cloned()
+            .collect();
+        self.pending_changes.push(PendingChange {
+            keys,
+            ttl: data.ttl,
+            modified_columns,
+            apply,
+        });
+
+        Ok(())
+    }
+
+    // Writes the whole row from its post-image.
+    // Null columns are written only if the change modified them,
+    // so writing the row doesn't create tombstones of columns that were never set.
+    async fn upsert_postimage(&mut self, mut data: CDCRow<'_>) -> anyhow::Result<()> {
+        if self.options.replication_mode != ReplicationMode::Postimage {
+            return Ok(());
+        }
+
+        let (_, timestamp, keys) = self.get_common_cdc_row_data(&data);
+        let (ttl, modified_columns) = match self
+            .pending_changes
+            .iter()
+            .position(|change| keys_match(&change.keys, &keys))
+        {
+            Some(position) => {
+                let change = self.pending_changes.swap_remove(position);
+                if !change.apply {
+                    return Ok(());
+                }
+                (change.ttl, change.modified_columns)
+            }
+            None => (data.ttl, HashSet::new()),
+        };
+
+        let table_schema = &self.source_table_data.table_schema;
+        let mut values = get_keys_iter(table_schema)
+            .map(|name| MaybeUnset::Set(data.take_value(name)))
+            .collect::<Vec<_>>();
+        for column_name in &self.source_table_data.non_key_columns {
+            let value = data.take_value(column_name);
+            if value.is_none() && !modified_columns.contains(column_name) {
+                values.push(MaybeUnset::Unset);
+                continue;
+            }
+            values.push(MaybeUnset::Set(
+                match table_schema.columns[column_name].type_ {
+                    CqlType::Collection {
+                        frozen: false,
+                        type_: CollectionType::List(_),
+                    } => value.map(list_from_log_value),
+                    _ => value,
+                },
+            ));
+        }
+        // If data is inserted without TTL, setting it to 0 deletes existing TTL.
+        values.push(MaybeUnset::Set(Some(
+            CqlValue::Int(ttl.unwrap_or(0) as i32),
+        )));
+
+        self.precomputed_queries
+            .upsert_row(&values, timestamp)
+            .await
+    }
+
     async fn update_or_insert(&mut self, data: CDCRow<'_>, is_insert: bool) -> anyhow::Result<()> {
+        if !self.resolve_conflicts(&data).await? {
+            return Ok(());
+        }
+
         let (ttl, timestamp, values) = self.get_common_cdc_row_data(&data);
 
         if is_insert {
@@ -810,6 +1611,85 @@ impl ReplicatorConsumer {
     }
 }
 
+// Returns names of columns that are not clustering or partition key.
+fn get_non_key_columns(table_schema: &Table) -> Vec<String> {
+    table_schema
+        .columns
+        .iter()
+        .filter(|column| {
+            column.1.kind != ColumnKind::Clustering && column.1.kind != ColumnKind::PartitionKey
+        })
+        .map(|column| column.0.clone())
+        .collect()
+}
+
+// Returns the schema of the table from the metadata fetched by the session.
+fn get_table_schema(
+    session: &Session,
+    keyspace_name: &str,
+    table_name: &str,
+) -> anyhow::Result<Table> {
+    Ok(session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .get(keyspace_name)
+        .ok_or_else(|| anyhow!("Keyspace not found"))?
+        .tables
+        .get(&table_name.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("Table not found"))?
+        .clone())
+}
+
+// Returns the schema of the destination table with renamed columns named as in the source table.
+fn get_dest_table_schema(
+    session: &Session,
+    keyspace_name: &str,
+    table_name: &str,
+    column_renames: &HashMap<String, String>,
+) -> anyhow::Result<Table> {
+    let source_names: HashMap<&str, &str> = column_renames
+        .iter()
+        .map(|(source, dest)| (dest.as_str(), source.as_str()))
+        .collect();
+    let table_schema = get_table_schema(session, keyspace_name, table_name)?;
+
+    Ok(rename_columns(&table_schema, |name| {
+        source_names.get(name).copied().unwrap_or(name).to_string()
+    }))
+}
+
+// Returns the table schema with columns renamed by the given function.
+pub(crate) fn rename_columns(table_schema: &Table, rename: impl Fn(&str) -> String) -> Table {
+    Table {
+        columns: table_schema
+            .columns
+            .iter()
+            .map(|(name, column)| (rename(name), column.clone()))
+            .collect(),
+        partition_key: table_schema
+            .partition_key
+            .iter()
+            .map(|name| rename(name))
+            .collect(),
+        clustering_key: table_schema
+            .clustering_key
+            .iter()
+            .map(|name| rename(name))
+            .collect(),
+        partitioner: table_schema.partitioner.clone(),
+    }
+}
+
+// Returns the name of the destination column the source column is replicated to.
+pub(crate) fn dest_column_name<'a>(
+    column_renames: &'a HashMap<String, String>,
+    column_name: &'a str,
+) -> &'a str {
+    column_renames
+        .get(column_name)
+        .map_or(column_name, String::as_str)
+}
+
 fn get_keys_iter(table_schema: &Table) -> impl std::iter::Iterator<Item = &String> + Clone {
     table_schema
         .partition_key
@@ -817,6 +1697,102 @@ fn get_keys_iter(table_schema: &Table) -> impl std::iter::Iterator<Item = &Strin
         .chain(table_schema.clustering_key.iter())
 }
 
+fn keys_match(stored_keys: &[Option<CqlValue>], keys: &[Option<&CqlValue>]) -> bool {
+    stored_keys
+        .iter()
+        .map(Option::as_ref)
+        .eq(keys.iter().copied())
+}
+
+// Non-frozen lists are stored in the CDC log as maps from timeuuids of the elements to their values.
+fn list_from_log_value(value: CqlValue) -> CqlValue {
+    match value {
+        Map(elements) => CqlValue::List(elements.into_iter().map(|(_, value)| value).collect()),
+        value => value,
+    }
+}
+
+// Whether values of the column are always written as a whole.
+fn is_overwritten_as_whole(column_type: &CqlType) -> bool {
+    matches!(
+        column_type,
+        CqlType::Native(_)
+            | CqlType::Tuple(_)
+            | CqlType::Collection { frozen: true, .. }
+            | CqlType::UserDefinedType { frozen: true, .. }
+    )
+}
+
+// Compares values ignoring keyspaces of user defined types,
+// because they differ between the source and the destination table.
+fn values_equal(left: &Option<CqlValue>, right: &Option<CqlValue>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => cql_values_equal(left, right),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn cql_values_equal(left: &CqlValue, right: &CqlValue) -> bool {
+    match (left, right) {
+        (CqlValue::List(left), CqlValue::List(right)) | (Set(left), Set(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|(left, right)| cql_values_equal(left, right))
+        }
+        (Map(left), Map(right)) => {
+            left.len() == right.len()
+                && left.iter().zip(right).all(|(left, right)| {
+                    cql_values_equal(&left.0, &right.0) && cql_values_equal(&left.1, &right.1)
+                })
+        }
+        (CqlValue::Tuple(left), CqlValue::Tuple(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|(left, right)| values_equal(left, right))
+        }
+        (
+            CqlValue::UserDefinedType {
+                fields: left_fields,
+                ..
+            },
+            CqlValue::UserDefinedType {
+                fields: right_fields,
+                ..
+            },
+        ) => {
+            left_fields.len() == right_fields.len()
+                && left_fields
+                    .iter()
+                    .zip(right_fields)
+                    .all(|(left, right)| left.0 == right.0 && values_equal(&left.1, &right.1))
+        }
+        _ => left == right,
+    }
+}
+
+// Returns the textual representation of a key value matched by the row filter.
+fn key_value_to_string(value: &CqlValue) -> Option<String> {
+    match value {
+        CqlValue::Ascii(value) | CqlValue::Text(value) => Some(value.clone()),
+        CqlValue::Boolean(value) => Some(value.to_string()),
+        CqlValue::TinyInt(value) => Some(value.to_string()),
+        CqlValue::SmallInt(value) => Some(value.to_string()),
+        CqlValue::Int(value) => Some(value.to_string()),
+        CqlValue::BigInt(value) => Some(value.to_string()),
+        CqlValue::Varint(value) => Some(value.to_string()),
+        CqlValue::Float(value) => Some(value.to_string()),
+        CqlValue::Double(value) => Some(value.to_string()),
+        CqlValue::Uuid(value) | CqlValue::Timeuuid(value) => Some(value.to_string()),
+        CqlValue::Inet(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
 fn take_clustering_keys_values(table_schema: &Table, data: &mut CDCRow) -> Vec<Option<CqlValue>> {
     table_schema
         .clustering_key
@@ -828,16 +1804,15 @@ fn take_clustering_keys_values(table_schema: &Table, data: &mut CDCRow) -> Vec<O
 #[async_trait]
 impl Consumer for ReplicatorConsumer {
     async fn consume_cdc(&mut self, data: CDCRow<'_>) -> anyhow::Result<()> {
-        match data.operation {
-            OperationType::RowUpdate => self.update(data).await?,
-            OperationType::RowInsert => self.insert(data).await?,
-            OperationType::RowDelete => self.delete_row(data).await?,
-            OperationType::PartitionDelete => self.delete_partition(data).await?,
-            OperationType::RowRangeDelExclLeft => self.delete_row_range_left(data, false),
-            OperationType::RowRangeDelInclLeft => self.delete_row_range_left(data, true),
-            OperationType::RowRangeDelExclRight => self.delete_row_range_right(data, false).await?,
-            OperationType::RowRangeDelInclRight => self.delete_row_range_right(data, true).await?,
-            op => warn!("This type of operation - {:?} - is not supported yet.", op),
+        let end_of_batch = data.end_of_batch;
+        if self.is_replicated_row(&data) {
+            self.apply_change(data).await?;
+        }
+
+        if end_of_batch {
+            self.precomputed_queries.flush().await?;
+            self.preimages.clear();
+            self.pending_changes.clear();
         }
 
         Ok(())
@@ -845,35 +1820,63 @@ impl Consumer for ReplicatorConsumer {
 }
 
 pub struct ReplicatorConsumerFactory {
+    source_session: Arc<Session>,
     session: Arc<Session>,
     dest_keyspace_name: String,
     dest_table_name: String,
     table_schema: Table,
+    options: ReplicationOptions,
+    schema_change_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl ReplicatorConsumerFactory {
     /// Creates a new instance of `ReplicatorConsumerFactory`.
-    /// Fetching schema metadata must be enabled in the session.
+    /// Fetching schema metadata must be enabled in both sessions.
     pub fn new(
+        source_session: Arc<Session>,
         session: Arc<Session>,
         dest_keyspace_name: String,
         dest_table_name: String,
+        options: ReplicationOptions,
     ) -> anyhow::Result<ReplicatorConsumerFactory> {
-        let table_schema = session
-            .get_cluster_data()
-            .get_keyspace_info()
-            .get(&dest_keyspace_name)
-            .ok_or_else(|| anyhow!("Keyspace not found"))?
-            .tables
-            .get(&dest_table_name.to_ascii_lowercase())
-            .ok_or_else(|| anyhow!("Table not found"))?
-            .clone();
+        let table_schema = get_dest_table_schema(
+            &session,
+            &dest_keyspace_name,
+            &dest_table_name,
+            &options.column_renames,
+        )?;
+        let keys = get_keys_iter(&table_schema).collect::<HashSet<_>>();
+        if let Some(name) = options
+            .row_filter
+            .column_names()
+            .find(|name| !keys.contains(&name.to_string()))
+        {
+            return Err(anyhow!(
+                "Row filter column {} is not a key column of {}.{}",
+                name,
+                dest_keyspace_name,
+                dest_table_name
+            ));
+        }
+        if let ColumnFilter::Deny(columns) = &options.column_filter {
+            if let Some(name) = columns.iter().find(|name| keys.contains(name)) {
+                return Err(anyhow!(
+                    "Key column {} of {}.{} can't be excluded from replication",
+                    name,
+                    dest_keyspace_name,
+                    dest_table_name
+                ));
+            }
+        }
 
         Ok(ReplicatorConsumerFactory {
+            source_session,
             session,
             dest_keyspace_name,
             dest_table_name,
             table_schema,
+            options,
+            schema_change_lock: Arc::new(tokio::sync::Mutex::new(())),
         })
     }
 }
@@ -883,12 +1886,17 @@ impl ConsumerFactory for ReplicatorConsumerFactory {
     async fn new_consumer(&self) -> Box<dyn Consumer> {
         Box::new(
             ReplicatorConsumer::new(
+                self.source_session.clone(),
                 self.session.clone(),
                 self.dest_keyspace_name.clone(),
                 self.dest_table_name.clone(),
                 self.table_schema.clone(),
+                self.options.clone(),
+                Arc::clone(&self.schema_change_lock),
             )
-            .await,
+            .await
+            // `new_consumer` can't return errors, so failing to prepare the statements panics here.
+            .expect("Creating the replicator consumer failed."),
         )
     }
 }
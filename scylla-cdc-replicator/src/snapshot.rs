@@ -0,0 +1,392 @@
+//! Copying all rows of a table to the destination table,
+//! before replicating the changes made after the copy started.
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use futures_util::{StreamExt, TryStreamExt};
+use itertools::Itertools;
+use scylla::batch::{Batch, BatchType};
+use scylla::frame::response::result::CqlValue;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::topology::{ColumnKind, CqlType, NativeType, Table};
+use scylla::Session;
+use tracing::info;
+
+use crate::replicator_consumer::{dest_column_name, ReplicationOptions, RowFilter};
+
+const DEFAULT_PARALLELISM: usize = 8;
+const DEFAULT_TOKEN_RANGES: usize = 256;
+
+/// Options of copying existing rows of the source tables before replicating changes.
+#[derive(Clone, Debug)]
+pub struct SnapshotOptions {
+    /// Number of token ranges of a table copied concurrently.
+    /// Tables are copied one after another.
+    pub parallelism: usize,
+    /// Number of token ranges the token ring is split into.
+    pub token_ranges: usize,
+}
+
+/// Create [`SnapshotOptions`] copying 8 of 256 token ranges concurrently.
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            parallelism: DEFAULT_PARALLELISM,
+            token_ranges: DEFAULT_TOKEN_RANGES,
+        }
+    }
+}
+
+/// Copies all rows of the source table to the destination table, scanning token ranges in parallel.
+///
+/// Values are written with their original `WRITETIME` and `TTL`.
+/// `WRITETIME` and `TTL` can't be read for non-frozen collections and user defined types,
+/// so they are written with `snapshot_timestamp` and without TTL.
+/// Row markers are written without TTL, with the oldest `WRITETIME` of the row's values.
+/// `snapshot_timestamp` should be the time the copy started,
+/// so that changes made during the copy can be replicated from the CDC log afterwards.
+/// Elements of non-frozen lists changed during the copy may be duplicated.
+///
+/// Only columns and rows passing the filters of `replication_options` are copied,
+/// to the columns they are renamed to.
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_table(
+    source_session: &Arc<Session>,
+    source_keyspace: &str,
+    source_table: &str,
+    dest_session: &Arc<Session>,
+    dest_keyspace: &str,
+    dest_table: &str,
+    snapshot_timestamp: chrono::Duration,
+    replication_options: &ReplicationOptions,
+    options: &SnapshotOptions,
+) -> anyhow::Result<()> {
+    if options.parallelism == 0 || options.token_ranges == 0 {
+        return Err(anyhow!(
+            "parallelism and the number of token ranges must be positive"
+        ));
+    }
+
+    let schema = source_session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .get(source_keyspace)
+        .ok_or_else(|| anyhow!("Keyspace {} not found", source_keyspace))?
+        .tables
+        .get(&source_table.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("Table {}.{} not found", source_keyspace, source_table))?
+        .clone();
+    let snapshot_timestamp = snapshot_timestamp
+        .num_microseconds()
+        .ok_or_else(|| anyhow!("Snapshot timestamp out of range"))?;
+
+    let source_keyspace_table_name = format!("{}.{}", source_keyspace, source_table);
+    let dest_keyspace_table_name = format!("{}.{}", dest_keyspace, dest_table);
+    info!(
+        "Copying {} to {}.",
+        source_keyspace_table_name, dest_keyspace_table_name
+    );
+    let copier = Arc::new(
+        TableCopier::new(
+            source_session,
+            &source_keyspace_table_name,
+            dest_session,
+            &dest_keyspace_table_name,
+            schema,
+            snapshot_timestamp,
+            replication_options,
+        )
+        .await?,
+    );
+
+    futures_util::stream::iter(token_ranges(options.token_ranges))
+        .map(|(start, end)| {
+            let copier = copier.clone();
+            async move { copier.copy_range(start, end).await }
+        })
+        .buffer_unordered(options.parallelism)
+        .try_collect::<Vec<()>>()
+        .await?;
+    info!("Copied {}.", source_keyspace_table_name);
+
+    Ok(())
+}
+
+// Splits the Murmur3 token ring into inclusive ranges of equal size.
+fn token_ranges(count: usize) -> Vec<(i64, i64)> {
+    let width = (u64::MAX / count as u64) as i128;
+    (0..count as i128)
+        .map(|i| {
+            let start = i64::MIN as i128 + i * width;
+            let end = if i == count as i128 - 1 {
+                i64::MAX as i128
+            } else {
+                start + width - 1
+            };
+            (start as i64, end as i64)
+        })
+        .collect()
+}
+
+// Copies rows of token ranges of a single table.
+struct TableCopier {
+    source_session: Arc<Session>,
+    dest_session: Arc<Session>,
+    schema: Table,
+    keys: Vec<String>,
+    snapshot_timestamp: i64,
+    row_filter: RowFilter,
+    select_query: PreparedStatement,
+    insert_query: PreparedStatement,
+    // Columns whose WRITETIME and TTL can be read, with the queries writing them.
+    timed_columns: Vec<(String, PreparedStatement)>,
+    // Non-frozen collections and user defined types, with the queries writing them.
+    untimed_columns: Vec<(String, PreparedStatement)>,
+}
+
+impl TableCopier {
+    async fn new(
+        source_session: &Arc<Session>,
+        source_keyspace_table_name: &str,
+        dest_session: &Arc<Session>,
+        dest_keyspace_table_name: &str,
+        schema: Table,
+        snapshot_timestamp: i64,
+        replication_options: &ReplicationOptions,
+    ) -> anyhow::Result<TableCopier> {
+        let dest_name =
+            |name: &String| dest_column_name(&replication_options.column_renames, name).to_string();
+        let keys = schema
+            .partition_key
+            .iter()
+            .chain(schema.clustering_key.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let (timed, untimed): (Vec<_>, Vec<_>) = schema
+            .columns
+            .iter()
+            .filter(|(name, column)| {
+                column.kind != ColumnKind::PartitionKey
+                    && column.kind != ColumnKind::Clustering
+                    && replication_options.column_filter.is_replicated(name)
+            })
+            .sorted_by_key(|(name, _)| *name)
+            .partition(|(_, column)| match &column.type_ {
+                CqlType::Collection { frozen, .. } | CqlType::UserDefinedType { frozen, .. } => {
+                    *frozen
+                }
+                _ => true,
+            });
+        if timed
+            .iter()
+            .any(|(_, column)| matches!(column.type_, CqlType::Native(NativeType::Counter)))
+        {
+            return Err(anyhow!("Copying tables with counters is not supported"));
+        }
+
+        let selectors = keys
+            .iter()
+            .cloned()
+            .chain(timed.iter().flat_map(|(name, _)| {
+                [
+                    name.to_string(),
+                    format!("WRITETIME({})", name),
+                    format!("TTL({})", name),
+                ]
+            }))
+            .chain(untimed.iter().map(|(name, _)| name.to_string()))
+            .join(", ");
+        let token = format!("token({})", schema.partition_key.join(", "));
+        let select_query = source_session
+            .prepare(format!(
+                "SELECT {} FROM {} WHERE {} >= ? AND {} <= ?",
+                selectors, source_keyspace_table_name, token, token
+            ))
+            .await?;
+
+        let insert_query = dest_session
+            .prepare(format!(
+                "INSERT INTO {} ({}) VALUES ({}) USING TIMESTAMP ?",
+                dest_keyspace_table_name,
+                keys.iter().map(dest_name).join(", "),
+                keys.iter().map(|_| "?").join(", ")
+            ))
+            .await?;
+
+        let mut timed_columns = vec![];
+        let mut untimed_columns = vec![];
+        for (columns, using, is_timed) in [
+            (timed, "USING TIMESTAMP ? AND TTL ?", true),
+            (untimed, "USING TIMESTAMP ?", false),
+        ] {
+            for (name, column) in columns {
+                // Static columns are identified only by the partition key.
+                let condition = match column.kind {
+                    ColumnKind::Static => &schema.partition_key[..],
+                    _ => &keys[..],
+                }
+                .iter()
+                .map(|name| format!("{} = ?", dest_name(name)))
+                .join(" AND ");
+                let query = dest_session
+                    .prepare(format!(
+                        "UPDATE {} {} SET {} = ? WHERE {}",
+                        dest_keyspace_table_name,
+                        using,
+                        dest_name(name),
+                        condition
+                    ))
+                    .await?;
+                if is_timed {
+                    timed_columns.push((name.clone(), query));
+                } else {
+                    untimed_columns.push((name.clone(), query));
+                }
+            }
+        }
+
+        Ok(TableCopier {
+            source_session: source_session.clone(),
+            dest_session: dest_session.clone(),
+            schema,
+            keys,
+            snapshot_timestamp,
+            row_filter: replication_options.row_filter.clone(),
+            select_query,
+            insert_query,
+            timed_columns,
+            untimed_columns,
+        })
+    }
+
+    async fn copy_range(&self, start: i64, end: i64) -> anyhow::Result<()> {
+        let mut rows = self
+            .source_session
+            .execute_iter(self.select_query.clone(), (start, end))
+            .await?;
+        let mut last_partition_key = None;
+
+        while let Some(row) = rows.next().await {
+            let columns = row?.columns;
+            let partition_key = &columns[..self.schema.partition_key.len()];
+            // Static columns are the same in all rows of a partition,
+            // so they are copied with its first row passing the row filter.
+            let copy_static = last_partition_key.as_deref() != Some(partition_key);
+            if self.copy_row(&columns, copy_static).await? {
+                last_partition_key = Some(partition_key.to_vec());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns `false` if the row was rejected by the row filter.
+    async fn copy_row(
+        &self,
+        columns: &[Option<CqlValue>],
+        copy_static: bool,
+    ) -> anyhow::Result<bool> {
+        let (keys, values) = columns.split_at(self.keys.len());
+        let partition_key = &keys[..self.schema.partition_key.len()];
+        let key_values = self
+            .keys
+            .iter()
+            .map(String::as_str)
+            .zip(keys.iter().map(Option::as_ref));
+        if !self.row_filter.matches(key_values) {
+            return Ok(false);
+        }
+        // Clustering key is null if the partition has only static columns.
+        let is_static_row = keys.iter().any(Option::is_none);
+        let (timed_values, untimed_values) = values.split_at(3 * self.timed_columns.len());
+
+        let mut batch = Batch::new(BatchType::Unlogged);
+        let mut batch_values: Vec<Vec<Option<CqlValue>>> = vec![];
+        let mut marker_timestamp = None;
+
+        let timed_values = self.timed_columns.iter().zip(timed_values.chunks(3));
+        for ((name, query), chunk) in timed_values {
+            let (value, writetime, ttl) = match chunk {
+                [Some(value), Some(CqlValue::BigInt(writetime)), ttl] => (value, *writetime, ttl),
+                _ => continue,
+            };
+            let is_static = self.schema.columns[name].kind == ColumnKind::Static;
+            if (is_static && !copy_static) || (!is_static && is_static_row) {
+                continue;
+            }
+            if !is_static {
+                marker_timestamp =
+                    Some(marker_timestamp.map_or(writetime, |ts: i64| ts.min(writetime)));
+            }
+
+            let ttl = ttl.clone().unwrap_or(CqlValue::Int(0));
+            let condition = if is_static { partition_key } else { keys };
+            batch.append_statement(query.clone());
+            batch_values.push(
+                [
+                    Some(CqlValue::BigInt(writetime)),
+                    Some(ttl),
+                    Some(value.clone()),
+                ]
+                .into_iter()
+                .chain(condition.iter().cloned())
+                .collect(),
+            );
+        }
+
+        for ((name, query), value) in self.untimed_columns.iter().zip(untimed_values) {
+            let is_static = self.schema.columns[name].kind == ColumnKind::Static;
+            if value.is_none() || (is_static && !copy_static) || (!is_static && is_static_row) {
+                continue;
+            }
+
+            let condition = if is_static { partition_key } else { keys };
+            batch.append_statement(query.clone());
+            batch_values.push(
+                [
+                    Some(CqlValue::BigInt(self.snapshot_timestamp)),
+                    value.clone(),
+                ]
+                .into_iter()
+                .chain(condition.iter().cloned())
+                .collect(),
+            );
+        }
+
+        if !is_static_row {
+            batch.append_statement(self.insert_query.clone());
+            batch_values.push(
+                keys.iter()
+                    .cloned()
+                    .chain([Some(CqlValue::BigInt(
+                        marker_timestamp.unwrap_or(self.snapshot_timestamp),
+                    ))])
+                    .collect(),
+            );
+        }
+
+        if !batch_values.is_empty() {
+            self.dest_session.batch(&batch, batch_values).await?;
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_ranges() {
+        assert_eq!(token_ranges(1), vec![(i64::MIN, i64::MAX)]);
+
+        let ranges = token_ranges(7);
+        assert_eq!(ranges.len(), 7);
+        assert_eq!(ranges.first().unwrap().0, i64::MIN);
+        assert_eq!(ranges.last().unwrap().1, i64::MAX);
+        for (previous, next) in ranges.iter().tuple_windows() {
+            assert_eq!(previous.1 + 1, next.0);
+        }
+    }
+}
@@ -3,6 +3,7 @@ use scylla::cql_to_rust::{FromCqlVal, FromCqlValError};
 use scylla::frame::response::result::CqlValue;
 use scylla::frame::value::{Timestamp, Value, ValueTooBig};
 use scylla::FromRow;
+use std::cmp::Ordering;
 use std::fmt;
 
 /// A struct representing a timestamp of a stream generation.
@@ -51,3 +52,77 @@ impl StreamID {
         StreamID { id: stream_id }
     }
 }
+
+/// A struct representing a position of a row in the CDC log:
+/// its stream, `cdc$time` and `cdc$batch_seq_no`.
+/// Positions of the rows of one stream are ordered the same way as the rows in the CDC log,
+/// so rows of different changes sharing a timestamp have different positions.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RowPosition {
+    pub stream_id: StreamID,
+    pub time: uuid::Uuid,
+    pub batch_seq_no: i32,
+}
+
+impl Ord for RowPosition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.stream_id
+            .cmp(&other.stream_id)
+            .then_with(|| compare_timeuuids(&self.time, &other.time))
+            .then_with(|| self.batch_seq_no.cmp(&other.batch_seq_no))
+    }
+}
+
+impl PartialOrd for RowPosition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Compares timeuuids the way Scylla orders them:
+// by their timestamps, then by the remaining bytes compared as signed integers.
+fn compare_timeuuids(a: &uuid::Uuid, b: &uuid::Uuid) -> Ordering {
+    let timestamp = |time: &uuid::Uuid| time.get_timestamp().map(|ts| ts.to_rfc4122().0);
+    let lower_bytes =
+        |time: &uuid::Uuid| -> [i8; 8] { std::array::from_fn(|i| time.as_bytes()[8 + i] as i8) };
+
+    timestamp(a)
+        .cmp(&timestamp(b))
+        .then_with(|| lower_bytes(a).cmp(&lower_bytes(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_position_order() {
+        let context = uuid::v1::Context::new(0);
+        let time = |seconds: u64| {
+            uuid::Uuid::new_v1(
+                uuid::v1::Timestamp::from_unix(&context, seconds, 0),
+                &[0; 6],
+            )
+        };
+        let position = |stream: u8, time: uuid::Uuid, batch_seq_no: i32| RowPosition {
+            stream_id: StreamID::new(vec![stream]),
+            time,
+            batch_seq_no,
+        };
+        let (first, second, later) = (time(10), time(10), time(20));
+
+        assert!(position(1, first, 1) < position(1, first, 2));
+        // Different changes with the same timestamp are ordered by the clock sequence.
+        assert!(position(1, first, 5) < position(1, second, 0));
+        assert!(position(1, second, 5) < position(1, later, 0));
+        assert!(position(1, later, 0) < position(2, first, 0));
+
+        // The bytes after the timestamp are compared as signed integers.
+        let mut bytes = *first.as_bytes();
+        bytes[15] = 0x7f;
+        let positive = uuid::Uuid::from_bytes(bytes);
+        bytes[15] = 0x80;
+        let negative = uuid::Uuid::from_bytes(bytes);
+        assert!(position(1, negative, 0) < position(1, positive, 0));
+    }
+}
@@ -3,7 +3,8 @@ use crate::cdc_types::{GenerationTimestamp, StreamID};
 use anyhow;
 use async_trait::async_trait;
 use futures::future::RemoteHandle;
-use futures::FutureExt;
+use futures::{FutureExt, StreamExt};
+use scylla::batch::{Batch, BatchType};
 use scylla::frame::response::result::CqlValue::Timestamp;
 use scylla::prepared_statement::PreparedStatement;
 use scylla::Session;
@@ -12,36 +13,62 @@ use std::time::Duration;
 use tokio::time::sleep;
 use tracing::warn;
 
+#[cfg(feature = "file-checkpoint-saver")]
+mod file_checkpoint_saver;
+#[cfg(feature = "sqlite-checkpoint-saver")]
+mod sqlite_checkpoint_saver;
+
+#[cfg(feature = "file-checkpoint-saver")]
+pub use file_checkpoint_saver::FileCheckpointSaver;
+#[cfg(feature = "sqlite-checkpoint-saver")]
+pub use sqlite_checkpoint_saver::SqliteCheckpointSaver;
+
 #[non_exhaustive]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Checkpoint {
+    /// All rows of the stream with `cdc$time` before this timestamp were consumed.
     pub timestamp: Duration,
     pub stream_id: StreamID,
     pub generation: GenerationTimestamp,
 }
 
 /// Spawns a new task that periodically saves checkpoints.
-/// Takes vector of tracked by a [`StreamReader`](crate::stream_reader::StreamReader) stream IDs.
-/// Receives value of the last checkpoint via channel.
+/// Receives the last checkpoints of the streams tracked by a [`StreamReader`](crate::stream_reader::StreamReader)
+/// via channel, one checkpoint per stream.
 /// We are using [`tokio::sync::watch`] channel, because we only need the latest value.
+/// Only checkpoints that changed since they were last saved are written, all at once.
 /// Returns handle to check on this newly created task.
 pub(crate) fn start_saving_checkpoints(
-    tracked_streams: Vec<StreamID>,
     checkpoint_saver: Arc<dyn CDCCheckpointSaver>,
-    receiver: tokio::sync::watch::Receiver<Checkpoint>,
+    receiver: tokio::sync::watch::Receiver<Vec<Checkpoint>>,
     saving_period: Duration,
 ) -> RemoteHandle<()> {
     let (fut, handle) = async move {
+        // The checkpoints of the streams are always sent in the same order.
+        let mut saved_checkpoints: Vec<Checkpoint> = vec![];
         loop {
-            for stream in tracked_streams.iter() {
-                let mut checkpoint = receiver.borrow().clone();
-                checkpoint.stream_id = stream.clone();
-
-                if checkpoint_saver.save_checkpoint(&checkpoint).await.is_err() {
-                    warn!(
-                        "Saving checkpoint for stream 0x{} failed.",
-                        hex::encode(&checkpoint.stream_id.id)
-                    );
+            let checkpoints = receiver.borrow().clone();
+            let changed_checkpoints: Vec<Checkpoint> = checkpoints
+                .iter()
+                .enumerate()
+                .filter(|(i, checkpoint)| saved_checkpoints.get(*i) != Some(checkpoint))
+                .map(|(_, checkpoint)| checkpoint.clone())
+                .collect();
+            if !changed_checkpoints.is_empty() {
+                match checkpoint_saver
+                    .save_checkpoints(&changed_checkpoints)
+                    .await
+                {
+                    Ok(()) => saved_checkpoints = checkpoints,
+                    Err(err) => {
+                        warn!(
+                            "Saving checkpoints of {} streams failed: {}",
+                            changed_checkpoints.len(),
+                            err
+                        );
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::checkpoint_save_failed();
+                    }
                 }
             }
 
@@ -60,6 +87,14 @@ pub(crate) fn start_saving_checkpoints(
 pub trait CDCCheckpointSaver: Send + Sync {
     /// Saves given checkpoint.
     async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> anyhow::Result<()>;
+    /// Saves given checkpoints of different streams.
+    /// The default implementation saves them one by one with [`CDCCheckpointSaver::save_checkpoint`].
+    async fn save_checkpoints(&self, checkpoints: &[Checkpoint]) -> anyhow::Result<()> {
+        for checkpoint in checkpoints {
+            self.save_checkpoint(checkpoint).await?;
+        }
+        Ok(())
+    }
     /// When a new generation comes into effect, it saves it into the checkpoint table.
     async fn save_new_generation(&self, generation: &GenerationTimestamp) -> anyhow::Result<()>;
     /// Loads last seen generation from the checkpoint table.
@@ -71,46 +106,81 @@ pub trait CDCCheckpointSaver: Send + Sync {
     ) -> anyhow::Result<Option<chrono::Duration>>;
 }
 
+/// Identifies the progress saved by a [`TableBackedCheckpointSaver`]:
+/// the application reading the CDC log and the table whose log is read.
+///
+/// Stream IDs are shared by the CDC logs of all tables in a cluster,
+/// so readers of different tables, or different applications reading the same table,
+/// must use different namespaces to share a checkpoint table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckpointNamespace {
+    pub application_id: String,
+    pub keyspace: String,
+    pub table: String,
+}
+
+impl CheckpointNamespace {
+    pub fn new(
+        application_id: impl Into<String>,
+        keyspace: impl Into<String>,
+        table: impl Into<String>,
+    ) -> Self {
+        CheckpointNamespace {
+            application_id: application_id.into(),
+            keyspace: keyspace.into(),
+            table: table.into(),
+        }
+    }
+}
+
 /// Default implementation for [`CDCCheckpointSaver`] trait.
 /// Saves checkpoints in a special table using ScyllaDB.
 /// Along with checkpoints for each StreamReader, the table contains
 /// a special row used for storing the latest generation.
+///
+/// Checkpoints of every [`CheckpointNamespace`] are kept in a separate partition of the table.
+/// Tables created by older versions, keyed only by the stream ID, can't be used directly;
+/// their checkpoints can be copied with [`TableBackedCheckpointSaver::import_legacy_checkpoints`].
 pub struct TableBackedCheckpointSaver {
     session: Arc<Session>,
     checkpoint_table: String,
+    namespace: CheckpointNamespace,
     make_checkpoint_stmt: PreparedStatement,
 }
 
 impl TableBackedCheckpointSaver {
-    /// Creates new [`TableBackedCheckpointSaver`].
+    /// Creates new [`TableBackedCheckpointSaver`] saving checkpoints of `namespace`.
     /// Will create a table if `keyspace.table_name` doesn't exists.
     /// Created checkpoints will have Time To Live equal to 7 days.
     pub async fn new_with_default_ttl(
         session: Arc<Session>,
         keyspace: &str,
         table_name: &str,
+        namespace: CheckpointNamespace,
     ) -> anyhow::Result<Self> {
         const DEFAULT_TTL: i64 = 604800; // 7 days
-        TableBackedCheckpointSaver::new(session, keyspace, table_name, DEFAULT_TTL).await
+        TableBackedCheckpointSaver::new(session, keyspace, table_name, DEFAULT_TTL, namespace).await
     }
 
-    /// Creates new [`TableBackedCheckpointSaver`].
+    /// Creates new [`TableBackedCheckpointSaver`] saving checkpoints of `namespace`.
     /// Will create a table if `keyspace.table_name` doesn't exists.
     pub async fn new(
         session: Arc<Session>,
         keyspace: &str,
         table_name: &str,
         ttl: i64,
+        namespace: CheckpointNamespace,
     ) -> anyhow::Result<Self> {
         let checkpoint_table = format!("{}.{}", keyspace, table_name);
 
+        TableBackedCheckpointSaver::check_legacy_layout(&session, keyspace, table_name).await?;
         TableBackedCheckpointSaver::create_checkpoints_table(&session, &checkpoint_table).await?;
 
         let make_checkpoint_stmt = session
             .prepare(format!(
                 "UPDATE {} USING TTL {}
                 SET generation = ?, time = ?
-                WHERE stream_id = ?",
+                WHERE application_id = ? AND keyspace_name = ? AND table_name = ? AND stream_id = ?",
                 checkpoint_table, ttl
             ))
             .await?;
@@ -118,12 +188,42 @@ impl TableBackedCheckpointSaver {
         let cp_saver = TableBackedCheckpointSaver {
             session,
             checkpoint_table,
+            namespace,
             make_checkpoint_stmt,
         };
 
         Ok(cp_saver)
     }
 
+    // Fails if the table exists with the layout of older versions, keyed only by the stream ID.
+    async fn check_legacy_layout(
+        session: &Arc<Session>,
+        keyspace: &str,
+        table_name: &str,
+    ) -> anyhow::Result<()> {
+        let columns = session
+            .query(
+                "SELECT column_name FROM system_schema.columns
+                WHERE keyspace_name = ? AND table_name = ?",
+                (keyspace, table_name),
+            )
+            .await?
+            .rows_typed_or_empty::<(String,)>()
+            .map(|row| row.map(|(column,)| column))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !columns.is_empty() && !columns.iter().any(|column| column == "application_id") {
+            return Err(anyhow::anyhow!(
+                "Checkpoint table {}.{} has the layout of an older version without namespaces. \
+                Use a new table and copy the checkpoints with `import_legacy_checkpoints`",
+                keyspace,
+                table_name
+            ));
+        }
+
+        Ok(())
+    }
+
     // Creates new table with specified schema in the given keyspace with provided name.
     // If a table with such name already exists, doesn't do anything.
     async fn create_checkpoints_table(
@@ -137,8 +237,56 @@ impl TableBackedCheckpointSaver {
 
         Ok(())
     }
+
+    /// Copies the checkpoints and the generation from `keyspace.table_name`,
+    /// a checkpoint table of an older version keyed only by the stream ID,
+    /// to the namespace of this saver. Returns the number of copied rows.
+    ///
+    /// The legacy table is left unchanged. Its checkpoints are shared by all tables whose CDC logs
+    /// were read with it, so they can be imported into the namespace of each of these tables.
+    pub async fn import_legacy_checkpoints(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+    ) -> anyhow::Result<usize> {
+        let mut rows = self
+            .session
+            .query_iter(
+                format!(
+                    "SELECT stream_id, generation, time FROM {}.{}",
+                    keyspace, table_name
+                ),
+                &[],
+            )
+            .await?
+            .into_typed::<(StreamID, Option<chrono::Duration>, Option<chrono::Duration>)>();
+
+        let mut imported = 0;
+        while let Some(row) = rows.next().await {
+            let (stream_id, generation, time) = row?;
+            self.session
+                .execute(
+                    &self.make_checkpoint_stmt,
+                    (
+                        generation.map(Timestamp),
+                        time.map(Timestamp),
+                        &self.namespace.application_id,
+                        &self.namespace.keyspace,
+                        &self.namespace.table,
+                        stream_id,
+                    ),
+                )
+                .await?;
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
 }
 
+// Keeps the batches of checkpoints below the default batch size warning threshold of Scylla.
+const CHECKPOINTS_PER_BATCH: usize = 64;
+
 fn get_default_generation_pk() -> StreamID {
     StreamID { id: vec![0] }
 }
@@ -146,9 +294,13 @@ fn get_default_generation_pk() -> StreamID {
 fn get_checkpoint_table_schema(table_name: &str) -> String {
     format!(
         "CREATE TABLE IF NOT EXISTS {} (
-            stream_id blob PRIMARY KEY,
+            application_id text,
+            keyspace_name text,
+            table_name text,
+            stream_id blob,
             generation timestamp,
-            time timestamp)",
+            time timestamp,
+            PRIMARY KEY ((application_id, keyspace_name, table_name), stream_id))",
         table_name
     )
 }
@@ -162,13 +314,43 @@ impl CDCCheckpointSaver for TableBackedCheckpointSaver {
         self.session
             .execute(
                 &self.make_checkpoint_stmt,
-                (&checkpoint.generation, timestamp, &checkpoint.stream_id),
+                (
+                    &checkpoint.generation,
+                    timestamp,
+                    &self.namespace.application_id,
+                    &self.namespace.keyspace,
+                    &self.namespace.table,
+                    &checkpoint.stream_id,
+                ),
             )
             .await?;
 
         Ok(())
     }
 
+    /// Writes the checkpoints in unlogged batches.
+    /// All checkpoints of the namespace are in the same partition, so the batches are cheap.
+    async fn save_checkpoints(&self, checkpoints: &[Checkpoint]) -> anyhow::Result<()> {
+        for chunk in checkpoints.chunks(CHECKPOINTS_PER_BATCH) {
+            let mut batch = Batch::new(BatchType::Unlogged);
+            let mut values = Vec::with_capacity(chunk.len());
+            for checkpoint in chunk {
+                batch.append_statement(self.make_checkpoint_stmt.clone());
+                values.push((
+                    &checkpoint.generation,
+                    Timestamp(chrono::Duration::from_std(checkpoint.timestamp)?),
+                    &self.namespace.application_id,
+                    &self.namespace.keyspace,
+                    &self.namespace.table,
+                    &checkpoint.stream_id,
+                ));
+            }
+            self.session.batch(&batch, values).await?;
+        }
+
+        Ok(())
+    }
+
     async fn save_new_generation(&self, generation: &GenerationTimestamp) -> anyhow::Result<()> {
         let marked_stream_id = get_default_generation_pk();
         let dummy_timestamp = Timestamp(chrono::Duration::max_value());
@@ -176,7 +358,14 @@ impl CDCCheckpointSaver for TableBackedCheckpointSaver {
         self.session
             .execute(
                 &self.make_checkpoint_stmt,
-                (generation, dummy_timestamp, marked_stream_id),
+                (
+                    generation,
+                    dummy_timestamp,
+                    &self.namespace.application_id,
+                    &self.namespace.keyspace,
+                    &self.namespace.table,
+                    marked_stream_id,
+                ),
             )
             .await?;
 
@@ -190,10 +379,16 @@ impl CDCCheckpointSaver for TableBackedCheckpointSaver {
             .query(
                 format!(
                     "SELECT generation FROM {}
-                    WHERE stream_id = ?",
+                    WHERE application_id = ? AND keyspace_name = ? AND table_name = ?
+                    AND stream_id = ?",
                     self.checkpoint_table
                 ),
-                (get_default_generation_pk(),),
+                (
+                    &self.namespace.application_id,
+                    &self.namespace.keyspace,
+                    &self.namespace.table,
+                    get_default_generation_pk(),
+                ),
             )
             .await?
             .maybe_first_row_typed::<GenerationTimestamp>()?;
@@ -211,11 +406,16 @@ impl CDCCheckpointSaver for TableBackedCheckpointSaver {
             .query(
                 format!(
                     "SELECT time FROM {}
-                    WHERE stream_id = ?
-                    LIMIT 1",
+                    WHERE application_id = ? AND keyspace_name = ? AND table_name = ?
+                    AND stream_id = ?",
                     self.checkpoint_table
                 ),
-                (stream_id,),
+                (
+                    &self.namespace.application_id,
+                    &self.namespace.keyspace,
+                    &self.namespace.table,
+                    stream_id,
+                ),
             )
             .await?
             .maybe_first_row_typed::<(chrono::Duration,)>()?
@@ -226,31 +426,54 @@ impl CDCCheckpointSaver for TableBackedCheckpointSaver {
 #[cfg(test)]
 mod tests {
     use crate::cdc_types::{GenerationTimestamp, StreamID};
-    use crate::checkpoints::{CDCCheckpointSaver, Checkpoint, TableBackedCheckpointSaver};
+    use crate::checkpoints::{
+        start_saving_checkpoints, CDCCheckpointSaver, Checkpoint, CheckpointNamespace,
+        TableBackedCheckpointSaver,
+    };
+    use async_trait::async_trait;
     use rand::prelude::*;
     use scylla::{IntoTypedRows, Session};
     use scylla_cdc_test_utils::{prepare_db, unique_name};
     use std::ops::Add;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
-    async fn setup() -> (Arc<Session>, String, Arc<TableBackedCheckpointSaver>) {
-        const DEFAULT_TTL: i64 = 300;
+    const DEFAULT_TTL: i64 = 300;
+
+    fn test_namespace() -> CheckpointNamespace {
+        CheckpointNamespace::new("test", "ks", "t")
+    }
+
+    async fn setup() -> (
+        Arc<Session>,
+        String,
+        String,
+        Arc<TableBackedCheckpointSaver>,
+    ) {
         let (session, ks) = prepare_db(&[], 1).await.unwrap();
         let table_name = unique_name();
 
         let cp_saver = Arc::new(
-            TableBackedCheckpointSaver::new(session.clone(), &ks, &table_name, DEFAULT_TTL)
-                .await
-                .unwrap(),
+            TableBackedCheckpointSaver::new(
+                session.clone(),
+                &ks,
+                &table_name,
+                DEFAULT_TTL,
+                test_namespace(),
+            )
+            .await
+            .unwrap(),
         );
 
-        (session, table_name, cp_saver)
+        (session, ks, table_name, cp_saver)
     }
 
     async fn get_checkpoints(session: &Arc<Session>, table: &str) -> Vec<Checkpoint> {
         session
-            .query(format!("SELECT * FROM {}", table), ())
+            .query(
+                format!("SELECT stream_id, generation, time FROM {}", table),
+                (),
+            )
             .await
             .unwrap()
             .rows()
@@ -270,7 +493,7 @@ mod tests {
     #[tokio::test]
     async fn test_save_checkpoint_multiple_times() {
         const N: usize = 20;
-        let (session, table_name, cp_saver) = setup().await;
+        let (session, _, table_name, cp_saver) = setup().await;
 
         let mut checkpoint = Checkpoint {
             timestamp: Duration::from_secs(128),
@@ -311,7 +534,7 @@ mod tests {
     #[tokio::test]
     async fn test_save_generation_multiple_times() {
         const N: usize = 20;
-        let (session, table_name, cp_saver) = setup().await;
+        let (session, _, table_name, cp_saver) = setup().await;
 
         let mut generation = GenerationTimestamp {
             timestamp: chrono::Duration::zero(),
@@ -341,7 +564,7 @@ mod tests {
     async fn test_save_checkpoint_multiple_streams() {
         const N_OF_IDS: u8 = 200;
 
-        let (_, _, cp_saver) = setup().await;
+        let (_, _, _, cp_saver) = setup().await;
 
         let mut checkpoints = Vec::<Checkpoint>::with_capacity(N_OF_IDS as usize);
 
@@ -370,4 +593,196 @@ mod tests {
             assert_eq!(saved_checkpoint.to_std().unwrap(), cp.timestamp);
         }
     }
+
+    fn test_checkpoint(stream: u8, seconds: u64) -> Checkpoint {
+        Checkpoint {
+            timestamp: Duration::from_secs(seconds),
+            stream_id: StreamID {
+                id: vec![1, stream],
+            },
+            generation: GenerationTimestamp {
+                timestamp: chrono::Duration::seconds(1),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_sharing_table() {
+        let (session, ks, table_name, cp_saver) = setup().await;
+        let other_saver = TableBackedCheckpointSaver::new(
+            session.clone(),
+            &ks,
+            &table_name,
+            DEFAULT_TTL,
+            CheckpointNamespace::new("test", "ks", "other_table"),
+        )
+        .await
+        .unwrap();
+
+        // Both namespaces save checkpoints of the same stream.
+        cp_saver
+            .save_checkpoint(&test_checkpoint(1, 100))
+            .await
+            .unwrap();
+        other_saver
+            .save_checkpoint(&test_checkpoint(1, 200))
+            .await
+            .unwrap();
+        other_saver
+            .save_new_generation(&GenerationTimestamp {
+                timestamp: chrono::Duration::seconds(5),
+            })
+            .await
+            .unwrap();
+
+        let stream_id = test_checkpoint(1, 0).stream_id;
+        assert_eq!(
+            cp_saver.load_last_checkpoint(&stream_id).await.unwrap(),
+            Some(chrono::Duration::seconds(100))
+        );
+        assert_eq!(
+            other_saver.load_last_checkpoint(&stream_id).await.unwrap(),
+            Some(chrono::Duration::seconds(200))
+        );
+        assert_eq!(cp_saver.load_last_generation().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_import_legacy_checkpoints() {
+        let (session, ks, table_name, cp_saver) = setup().await;
+        let legacy_table = format!("{}_legacy", table_name);
+        session
+            .query(
+                format!(
+                    "CREATE TABLE {} (stream_id blob PRIMARY KEY, generation timestamp, time timestamp)",
+                    legacy_table
+                ),
+                (),
+            )
+            .await
+            .unwrap();
+        session
+            .query(
+                format!(
+                    "INSERT INTO {} (stream_id, generation, time) VALUES (0x00, 5000, 9000)",
+                    legacy_table
+                ),
+                (),
+            )
+            .await
+            .unwrap();
+        session
+            .query(
+                format!(
+                    "INSERT INTO {} (stream_id, generation, time) VALUES (0x0101, 5000, 7000)",
+                    legacy_table
+                ),
+                (),
+            )
+            .await
+            .unwrap();
+
+        // The legacy layout isn't used directly.
+        assert!(TableBackedCheckpointSaver::new(
+            session.clone(),
+            &ks,
+            &legacy_table,
+            DEFAULT_TTL,
+            test_namespace(),
+        )
+        .await
+        .is_err());
+
+        let imported = cp_saver
+            .import_legacy_checkpoints(&ks, &legacy_table)
+            .await
+            .unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(
+            cp_saver.load_last_generation().await.unwrap(),
+            Some(GenerationTimestamp {
+                timestamp: chrono::Duration::seconds(5)
+            })
+        );
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&test_checkpoint(1, 0).stream_id)
+                .await
+                .unwrap(),
+            Some(chrono::Duration::seconds(7))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_checkpoints_in_batches() {
+        let (_, _, _, cp_saver) = setup().await;
+        let checkpoints: Vec<Checkpoint> = (0..200)
+            .map(|i| test_checkpoint(i, 100 + i as u64))
+            .collect();
+
+        cp_saver.save_checkpoints(&checkpoints).await.unwrap();
+
+        for cp in &checkpoints {
+            let saved_checkpoint = cp_saver
+                .load_last_checkpoint(&cp.stream_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(saved_checkpoint.to_std().unwrap(), cp.timestamp);
+        }
+    }
+
+    // Records the checkpoints passed to every call of `save_checkpoints`.
+    #[derive(Default)]
+    struct RecordingCheckpointSaver {
+        saves: Mutex<Vec<Vec<Checkpoint>>>,
+    }
+
+    #[async_trait]
+    impl CDCCheckpointSaver for RecordingCheckpointSaver {
+        async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+            self.save_checkpoints(std::slice::from_ref(checkpoint))
+                .await
+        }
+
+        async fn save_checkpoints(&self, checkpoints: &[Checkpoint]) -> anyhow::Result<()> {
+            self.saves.lock().unwrap().push(checkpoints.to_vec());
+            Ok(())
+        }
+
+        async fn save_new_generation(&self, _: &GenerationTimestamp) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn load_last_generation(&self) -> anyhow::Result<Option<GenerationTimestamp>> {
+            Ok(None)
+        }
+
+        async fn load_last_checkpoint(
+            &self,
+            _: &StreamID,
+        ) -> anyhow::Result<Option<chrono::Duration>> {
+            Ok(None)
+        }
+    }
+
+    // The clock is paused, so the sleeps advance it only when all tasks are idle.
+    #[tokio::test(start_paused = true)]
+    async fn test_saving_only_changed_checkpoints() {
+        const SAVING_PERIOD: Duration = Duration::from_secs(10);
+        let cp_saver = Arc::new(RecordingCheckpointSaver::default());
+        let checkpoints = vec![test_checkpoint(1, 100), test_checkpoint(2, 100)];
+        let (sender, receiver) = tokio::sync::watch::channel(checkpoints.clone());
+
+        let _handle = start_saving_checkpoints(cp_saver.clone(), receiver, SAVING_PERIOD);
+        // Wake up between the saves, so the order of the sleeping tasks doesn't matter.
+        tokio::time::sleep(SAVING_PERIOD * 5 / 2).await;
+        sender.send_modify(|checkpoints| checkpoints[1].timestamp = Duration::from_secs(200));
+        tokio::time::sleep(SAVING_PERIOD * 3).await;
+
+        let saves = cp_saver.saves.lock().unwrap().clone();
+        let mut moved_checkpoint = test_checkpoint(2, 200);
+        moved_checkpoint.generation = checkpoints[1].generation.clone();
+        assert_eq!(saves, vec![checkpoints, vec![moved_checkpoint]]);
+    }
 }
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::cdc_types::{GenerationTimestamp, StreamID};
+use crate::checkpoints::{CDCCheckpointSaver, Checkpoint};
+
+// Contents of the checkpoint file. Timestamps are stored in milliseconds.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct CheckpointDocument {
+    generation: Option<i64>,
+    // Maps hex-encoded stream ID to its last checkpoint.
+    checkpoints: BTreeMap<String, StreamCheckpoint>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StreamCheckpoint {
+    generation: i64,
+    time: i64,
+}
+
+/// Implementation of [`CDCCheckpointSaver`] trait storing checkpoints in a local JSON file.
+/// The whole document is kept in memory and rewritten on every save:
+/// it is written to a temporary file first, which then replaces the original one,
+/// so the file always contains a complete document, even if the process crashes during a save.
+/// The document in memory is updated only after the file is replaced.
+///
+/// Available with the `file-checkpoint-saver` feature.
+pub struct FileCheckpointSaver {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    document: Mutex<CheckpointDocument>,
+}
+
+impl FileCheckpointSaver {
+    /// Creates new [`FileCheckpointSaver`].
+    /// Loads the checkpoints from the file at `path` if it exists.
+    pub async fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        let document = match tokio::fs::read(&path).await {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(FileCheckpointSaver {
+            path,
+            tmp_path: tmp_path.into(),
+            document: Mutex::new(document),
+        })
+    }
+
+    async fn write_document(&self, document: &CheckpointDocument) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec(document)?;
+
+        let mut file = tokio::fs::File::create(&self.tmp_path).await?;
+        file.write_all(&contents).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&self.tmp_path, &self.path).await?;
+        // The rename is durable only after the directory is synced.
+        #[cfg(unix)]
+        {
+            let directory = match self.path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            tokio::fs::File::open(directory).await?.sync_all().await?;
+        }
+
+        Ok(())
+    }
+
+    // Writes the document changed by `update` and replaces the one in memory if it succeeds.
+    async fn update_document(
+        &self,
+        update: impl FnOnce(&mut CheckpointDocument) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut document = self.document.lock().await;
+        let mut updated = document.clone();
+        update(&mut updated)?;
+
+        self.write_document(&updated).await?;
+        *document = updated;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CDCCheckpointSaver for FileCheckpointSaver {
+    async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+        self.save_checkpoints(std::slice::from_ref(checkpoint))
+            .await
+    }
+
+    /// Replaces the file once for all the checkpoints.
+    async fn save_checkpoints(&self, checkpoints: &[Checkpoint]) -> anyhow::Result<()> {
+        self.update_document(|document| {
+            for checkpoint in checkpoints {
+                document.checkpoints.insert(
+                    checkpoint.stream_id.to_string(),
+                    StreamCheckpoint {
+                        generation: checkpoint.generation.timestamp.num_milliseconds(),
+                        time: chrono::Duration::from_std(checkpoint.timestamp)?.num_milliseconds(),
+                    },
+                );
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_new_generation(&self, generation: &GenerationTimestamp) -> anyhow::Result<()> {
+        self.update_document(|document| {
+            document.generation = Some(generation.timestamp.num_milliseconds());
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_last_generation(&self) -> anyhow::Result<Option<GenerationTimestamp>> {
+        let document = self.document.lock().await;
+
+        Ok(document.generation.map(|generation| GenerationTimestamp {
+            timestamp: chrono::Duration::milliseconds(generation),
+        }))
+    }
+
+    async fn load_last_checkpoint(
+        &self,
+        stream_id: &StreamID,
+    ) -> anyhow::Result<Option<chrono::Duration>> {
+        let document = self.document.lock().await;
+
+        Ok(document
+            .checkpoints
+            .get(&stream_id.to_string())
+            .map(|checkpoint| chrono::Duration::milliseconds(checkpoint.time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "scylla_cdc_checkpoints_{}.json",
+            rand::random::<u64>()
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_after_restart() {
+        let path = temp_path();
+        let cp_saver = FileCheckpointSaver::new(&path).await.unwrap();
+        let generation = GenerationTimestamp {
+            timestamp: chrono::Duration::seconds(100),
+        };
+
+        assert_eq!(cp_saver.load_last_generation().await.unwrap(), None);
+        cp_saver.save_new_generation(&generation).await.unwrap();
+
+        for i in 0..10 {
+            let checkpoint = Checkpoint {
+                timestamp: Duration::from_secs(200 + i),
+                stream_id: StreamID::new(vec![0, i as u8]),
+                generation: generation.clone(),
+            };
+            cp_saver.save_checkpoint(&checkpoint).await.unwrap();
+        }
+        // Overwrite the checkpoint of one of the streams.
+        let checkpoint = Checkpoint {
+            timestamp: Duration::from_secs(300),
+            stream_id: StreamID::new(vec![0, 0]),
+            generation: generation.clone(),
+        };
+        cp_saver.save_checkpoint(&checkpoint).await.unwrap();
+        drop(cp_saver);
+
+        let cp_saver = FileCheckpointSaver::new(&path).await.unwrap();
+        assert_eq!(
+            cp_saver.load_last_generation().await.unwrap(),
+            Some(generation)
+        );
+        for i in 0..10u8 {
+            let expected = if i == 0 { 300 } else { 200 + i as i64 };
+            assert_eq!(
+                cp_saver
+                    .load_last_checkpoint(&StreamID::new(vec![0, i]))
+                    .await
+                    .unwrap(),
+                Some(chrono::Duration::seconds(expected))
+            );
+        }
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&StreamID::new(vec![1]))
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(!cp_saver.tmp_path.exists());
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_write() {
+        let path = temp_path()
+            .join("no_such_directory")
+            .join("checkpoints.json");
+        let cp_saver = FileCheckpointSaver::new(&path).await.unwrap();
+        let checkpoint = Checkpoint {
+            timestamp: Duration::from_secs(200),
+            stream_id: StreamID::new(vec![1]),
+            generation: GenerationTimestamp {
+                timestamp: chrono::Duration::seconds(100),
+            },
+        };
+
+        // Nothing that failed to be written is returned from memory.
+        assert!(cp_saver
+            .save_new_generation(&checkpoint.generation)
+            .await
+            .is_err());
+        assert!(cp_saver.save_checkpoint(&checkpoint).await.is_err());
+        assert_eq!(cp_saver.load_last_generation().await.unwrap(), None);
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&checkpoint.stream_id)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_file() {
+        let path = temp_path();
+        tokio::fs::write(&path, b"{not json").await.unwrap();
+
+        assert!(FileCheckpointSaver::new(&path).await.is_err());
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
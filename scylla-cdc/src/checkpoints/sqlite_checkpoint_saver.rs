@@ -0,0 +1,281 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::cdc_types::{GenerationTimestamp, StreamID};
+use crate::checkpoints::{get_default_generation_pk, CDCCheckpointSaver, Checkpoint};
+
+/// Implementation of [`CDCCheckpointSaver`] trait storing checkpoints in an embedded SQLite database.
+/// Uses a table keyed by the stream ID, like older versions of
+/// [`TableBackedCheckpointSaver`](crate::checkpoints::TableBackedCheckpointSaver),
+/// including the special row used for storing the latest generation.
+/// The progress of different readers must be kept in different tables.
+/// Timestamps are stored in milliseconds.
+///
+/// Available with the `sqlite-checkpoint-saver` feature.
+pub struct SqliteCheckpointSaver {
+    connection: Arc<Mutex<Connection>>,
+    checkpoint_table: String,
+}
+
+impl SqliteCheckpointSaver {
+    /// Creates new [`SqliteCheckpointSaver`] using the database file at `path`.
+    /// Will create the database and a table if `table_name` doesn't exist.
+    pub async fn new(path: impl AsRef<Path>, table_name: &str) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let connection = tokio::task::spawn_blocking(move || Connection::open(path)).await??;
+
+        SqliteCheckpointSaver::with_connection(connection, table_name).await
+    }
+
+    /// Creates new [`SqliteCheckpointSaver`] using the given connection.
+    /// Will create a table if `table_name` doesn't exist.
+    pub async fn with_connection(connection: Connection, table_name: &str) -> anyhow::Result<Self> {
+        let cp_saver = SqliteCheckpointSaver {
+            connection: Arc::new(Mutex::new(connection)),
+            checkpoint_table: table_name.to_string(),
+        };
+
+        let schema = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                stream_id BLOB PRIMARY KEY,
+                generation INTEGER,
+                time INTEGER)",
+            cp_saver.checkpoint_table
+        );
+        cp_saver
+            .run(move |connection| {
+                connection.execute(&schema, [])?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(cp_saver)
+    }
+
+    // Runs the given function on the connection without blocking the runtime.
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| anyhow::anyhow!("SQLite connection mutex is poisoned"))?;
+            f(&connection)
+        })
+        .await?
+    }
+
+    async fn upsert(&self, stream_id: &StreamID, generation: i64, time: i64) -> anyhow::Result<()> {
+        self.upsert_all(vec![(stream_id.id.clone(), generation, time)])
+            .await
+    }
+
+    // Upserts rows of (stream ID, generation, time) in one transaction.
+    async fn upsert_all(&self, rows: Vec<(Vec<u8>, i64, i64)>) -> anyhow::Result<()> {
+        let query = format!(
+            "INSERT INTO {} (stream_id, generation, time) VALUES (?1, ?2, ?3)
+            ON CONFLICT(stream_id) DO UPDATE SET generation = ?2, time = ?3",
+            self.checkpoint_table
+        );
+
+        self.run(move |connection| {
+            let transaction = connection.unchecked_transaction()?;
+            {
+                let mut statement = transaction.prepare_cached(&query)?;
+                for (stream_id, generation, time) in rows {
+                    statement.execute(params![stream_id, generation, time])?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn select(&self, column: &str, stream_id: &StreamID) -> anyhow::Result<Option<i64>> {
+        let query = format!(
+            "SELECT {} FROM {} WHERE stream_id = ?1",
+            column, self.checkpoint_table
+        );
+        let stream_id = stream_id.id.clone();
+
+        self.run(move |connection| {
+            Ok(connection
+                .query_row(&query, params![stream_id], |row| row.get(0))
+                .optional()?)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl CDCCheckpointSaver for SqliteCheckpointSaver {
+    async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+        self.upsert(
+            &checkpoint.stream_id,
+            checkpoint.generation.timestamp.num_milliseconds(),
+            chrono::Duration::from_std(checkpoint.timestamp)?.num_milliseconds(),
+        )
+        .await
+    }
+
+    /// Upserts all the checkpoints in one transaction.
+    async fn save_checkpoints(&self, checkpoints: &[Checkpoint]) -> anyhow::Result<()> {
+        let rows = checkpoints
+            .iter()
+            .map(|checkpoint| {
+                Ok((
+                    checkpoint.stream_id.id.clone(),
+                    checkpoint.generation.timestamp.num_milliseconds(),
+                    chrono::Duration::from_std(checkpoint.timestamp)?.num_milliseconds(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.upsert_all(rows).await
+    }
+
+    async fn save_new_generation(&self, generation: &GenerationTimestamp) -> anyhow::Result<()> {
+        self.upsert(
+            &get_default_generation_pk(),
+            generation.timestamp.num_milliseconds(),
+            i64::MAX,
+        )
+        .await
+    }
+
+    async fn load_last_generation(&self) -> anyhow::Result<Option<GenerationTimestamp>> {
+        Ok(self
+            .select("generation", &get_default_generation_pk())
+            .await?
+            .map(|generation| GenerationTimestamp {
+                timestamp: chrono::Duration::milliseconds(generation),
+            }))
+    }
+
+    async fn load_last_checkpoint(
+        &self,
+        stream_id: &StreamID,
+    ) -> anyhow::Result<Option<chrono::Duration>> {
+        Ok(self
+            .select("time", stream_id)
+            .await?
+            .map(chrono::Duration::milliseconds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const TEST_TABLE: &str = "checkpoints";
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let connection = Connection::open_in_memory().unwrap();
+        let cp_saver = SqliteCheckpointSaver::with_connection(connection, TEST_TABLE)
+            .await
+            .unwrap();
+        let generation = GenerationTimestamp {
+            timestamp: chrono::Duration::seconds(100),
+        };
+
+        assert_eq!(cp_saver.load_last_generation().await.unwrap(), None);
+        cp_saver.save_new_generation(&generation).await.unwrap();
+        assert_eq!(
+            cp_saver.load_last_generation().await.unwrap(),
+            Some(generation.clone())
+        );
+
+        let mut checkpoint = Checkpoint {
+            timestamp: Duration::from_secs(128),
+            stream_id: StreamID::new(vec![1, 1, 1, 1]),
+            generation,
+        };
+        for _ in 0..5 {
+            checkpoint.timestamp += Duration::from_secs(32);
+            cp_saver.save_checkpoint(&checkpoint).await.unwrap();
+        }
+
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&checkpoint.stream_id)
+                .await
+                .unwrap(),
+            Some(chrono::Duration::from_std(checkpoint.timestamp).unwrap())
+        );
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&StreamID::new(vec![2]))
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_checkpoints() {
+        let connection = Connection::open_in_memory().unwrap();
+        let cp_saver = SqliteCheckpointSaver::with_connection(connection, TEST_TABLE)
+            .await
+            .unwrap();
+        let checkpoints: Vec<Checkpoint> = (0..100u8)
+            .map(|i| Checkpoint {
+                timestamp: Duration::from_secs(100 + i as u64),
+                stream_id: StreamID::new(vec![1, i]),
+                generation: GenerationTimestamp {
+                    timestamp: chrono::Duration::seconds(100),
+                },
+            })
+            .collect();
+
+        cp_saver.save_checkpoints(&checkpoints).await.unwrap();
+
+        for checkpoint in &checkpoints {
+            assert_eq!(
+                cp_saver
+                    .load_last_checkpoint(&checkpoint.stream_id)
+                    .await
+                    .unwrap(),
+                Some(chrono::Duration::from_std(checkpoint.timestamp).unwrap())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_after_restart() {
+        let path = std::env::temp_dir().join(format!(
+            "scylla_cdc_checkpoints_{}.db",
+            rand::random::<u64>()
+        ));
+        let checkpoint = Checkpoint {
+            timestamp: Duration::from_secs(128),
+            stream_id: StreamID::new(vec![1]),
+            generation: GenerationTimestamp {
+                timestamp: chrono::Duration::seconds(100),
+            },
+        };
+
+        let cp_saver = SqliteCheckpointSaver::new(&path, TEST_TABLE).await.unwrap();
+        cp_saver.save_checkpoint(&checkpoint).await.unwrap();
+        drop(cp_saver);
+
+        let cp_saver = SqliteCheckpointSaver::new(&path, TEST_TABLE).await.unwrap();
+        assert_eq!(
+            cp_saver
+                .load_last_checkpoint(&checkpoint.stream_id)
+                .await
+                .unwrap(),
+            Some(chrono::Duration::seconds(128))
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
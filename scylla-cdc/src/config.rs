@@ -0,0 +1,525 @@
+//! Loading settings of applications reading the CDC log from TOML or YAML files,
+//! with overrides from environment variables.
+//!
+//! Available with the `config` feature.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time;
+
+use anyhow::{anyhow, bail, Context};
+use scylla::{Session, SessionBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::info;
+
+use crate::checkpoints::{CDCCheckpointSaver, CheckpointNamespace, TableBackedCheckpointSaver};
+use crate::log_reader::CDCLogReaderBuilder;
+
+/// Loads settings from the file at `path` and the environment variables starting with `env_prefix`.
+///
+/// The format of the file is chosen by its extension: `.toml`, `.yaml` or `.yml`.
+/// If `path` is `None`, the settings are loaded only from the environment variables.
+///
+/// The rest of the name of an environment variable after `env_prefix` is the path of the overridden setting,
+/// with the names of nested settings separated by `__`, case insensitive.
+/// For example, with the prefix `APP_`, `APP_SOURCE__PASSWORD` overrides the `password` setting
+/// in the `source` section. Values are parsed as JSON if possible, e.g. `60` or `["a", "b"]`,
+/// and used as strings otherwise. Numbers and booleans overriding string settings are used as strings,
+/// so e.g. `APP_SOURCE__PASSWORD=123456` sets the password to `"123456"`.
+pub fn load<T: DeserializeOwned>(path: Option<&Path>, env_prefix: &str) -> anyhow::Result<T> {
+    load_with_vars(path, env_prefix, std::env::vars())
+}
+
+fn load_with_vars<T: DeserializeOwned>(
+    path: Option<&Path>,
+    env_prefix: &str,
+    vars: impl Iterator<Item = (String, String)>,
+) -> anyhow::Result<T> {
+    let mut settings = match path {
+        Some(path) => parse_file(path)?,
+        None => Value::Object(Map::new()),
+    };
+    // Numbers and booleans set by the variables, which are used as strings if they fail to deserialize.
+    let mut scalars = Vec::new();
+    for (name, value) in vars {
+        if let Some(setting_path) = name.strip_prefix(env_prefix) {
+            let setting_path = setting_path
+                .split("__")
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>();
+            let parsed = match serde_json::from_str(&value) {
+                Ok(Value::Null) | Err(_) => Value::String(value),
+                Ok(parsed @ (Value::Number(_) | Value::Bool(_))) => {
+                    scalars.push((setting_path.clone(), value));
+                    parsed
+                }
+                Ok(parsed) => parsed,
+            };
+            set_setting(&mut settings, &setting_path, parsed);
+        }
+    }
+
+    let source = path.map_or_else(
+        || "environment variables".to_string(),
+        |path| path.display().to_string(),
+    );
+    loop {
+        let err = match serde_path_to_error::deserialize(&settings) {
+            Ok(config) => return Ok(config),
+            Err(err) => err,
+        };
+        let err_path = err.path().to_string();
+        let scalar = scalars
+            .iter()
+            .position(|(setting_path, _)| setting_path.join(".") == err_path);
+        match scalar {
+            Some(scalar) => {
+                let (setting_path, value) = scalars.swap_remove(scalar);
+                set_setting(&mut settings, &setting_path, Value::String(value));
+            }
+            None if err_path == "." => {
+                bail!("Invalid configuration in {}: {}", source, err.inner())
+            }
+            None => bail!(
+                "Invalid configuration in {}: {}: {}",
+                source,
+                err_path,
+                err.inner()
+            ),
+        }
+    }
+}
+
+fn parse_file(path: &Path) -> anyhow::Result<Value> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read configuration file {}", path.display()))?;
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let settings = match extension {
+        Some("toml") => toml::from_str(&contents).map_err(|err| anyhow!("{}", err)),
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|err| anyhow!("{}", err)),
+        _ => bail!(
+            "Unsupported format of configuration file {}, expected .toml, .yaml or .yml",
+            path.display()
+        ),
+    }
+    .with_context(|| format!("Failed to parse configuration file {}", path.display()))?;
+
+    // An empty YAML document is null.
+    Ok(match settings {
+        Value::Null => Value::Object(Map::new()),
+        settings => settings,
+    })
+}
+
+// Sets the value of the nested setting, creating the missing sections.
+fn set_setting(settings: &mut Value, path: &[String], value: Value) {
+    match path {
+        [] => *settings = value,
+        [name, rest @ ..] => {
+            if !settings.is_object() {
+                *settings = Value::Object(Map::new());
+            }
+            let section = settings
+                .as_object_mut()
+                .unwrap()
+                .entry(name.clone())
+                .or_insert(Value::Null);
+            set_setting(section, rest, value);
+        }
+    }
+}
+
+/// Settings of connecting to a cluster.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Addresses of the nodes the session connects to first.
+    pub hosts: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl SessionConfig {
+    /// Checks if the settings are complete, `name` is the name of their section used in errors.
+    pub fn validate(&self, name: &str) -> anyhow::Result<()> {
+        if self.hosts.is_empty() {
+            bail!("{}.hosts: no hosts were provided", name);
+        }
+        if self.username.is_some() != self.password.is_some() {
+            bail!("{}: username and password must be provided together", name);
+        }
+        Ok(())
+    }
+
+    /// Connects to the cluster.
+    pub async fn build(&self) -> anyhow::Result<Session> {
+        let mut builder = SessionBuilder::new().known_nodes(&self.hosts);
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.user(username, password);
+        }
+        Ok(builder.build().await?)
+    }
+}
+
+/// Settings of reading windows of the CDC log, in seconds.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimingConfig {
+    pub window_size: f64,
+    pub safety_interval: f64,
+    pub sleep_interval: f64,
+}
+
+/// Create [`TimingConfig`] with the defaults of [`CDCLogReaderBuilder`].
+impl Default for TimingConfig {
+    fn default() -> Self {
+        TimingConfig {
+            window_size: 60.,
+            safety_interval: 30.,
+            sleep_interval: 10.,
+        }
+    }
+}
+
+impl TimingConfig {
+    /// Checks if the intervals are valid, `name` is the name of their section used in errors.
+    pub fn validate(&self, name: &str) -> anyhow::Result<()> {
+        for (setting, value) in [
+            ("window_size", self.window_size),
+            ("safety_interval", self.safety_interval),
+            ("sleep_interval", self.sleep_interval),
+        ] {
+            if !value.is_finite() || value < 0. {
+                bail!(
+                    "{}.{}: expected a non-negative number of seconds, got {}",
+                    name,
+                    setting,
+                    value
+                );
+            }
+        }
+        if self.window_size == 0. {
+            bail!("{}.window_size: must be positive", name);
+        }
+        Ok(())
+    }
+
+    /// Sets the intervals of the builder.
+    pub fn configure(&self, builder: CDCLogReaderBuilder) -> CDCLogReaderBuilder {
+        builder
+            .window_size(time::Duration::from_secs_f64(self.window_size))
+            .safety_interval(time::Duration::from_secs_f64(self.safety_interval))
+            .sleep_interval(time::Duration::from_secs_f64(self.sleep_interval))
+    }
+}
+
+/// Settings of saving the progress of reading the CDC log.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckpointConfig {
+    /// Pause between saving two consecutive checkpoints, in seconds.
+    #[serde(default = "default_save_interval")]
+    pub save_interval: f64,
+    pub saver: CheckpointSaverConfig,
+}
+
+fn default_save_interval() -> f64 {
+    10.
+}
+
+/// Storage of the checkpoints.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum CheckpointSaverConfig {
+    /// Table in the cluster the CDC log is read from, see [`TableBackedCheckpointSaver`].
+    Table {
+        /// Keyspace of the table, the keyspace of the CDC log by default.
+        keyspace: Option<String>,
+        table: String,
+        /// Time to live of the checkpoints in seconds, 7 days by default.
+        ttl: Option<i64>,
+        /// Application ID in the namespace of the checkpoints, chosen by the application by default.
+        /// Applications reading the same CDC log and sharing the table must use different IDs.
+        application_id: Option<String>,
+        /// Checkpoint table of an older version, as `keyspace.table` or `table`,
+        /// whose checkpoints are imported if no progress is saved in the namespace yet.
+        import_from: Option<String>,
+    },
+    /// Local JSON file, requires the `file-checkpoint-saver` feature.
+    File { path: PathBuf },
+    /// Table in a local SQLite database, requires the `sqlite-checkpoint-saver` feature.
+    Sqlite { path: PathBuf, table: String },
+}
+
+impl CheckpointConfig {
+    /// Creates the settings of saving checkpoints with `saver`, every 10 seconds.
+    pub fn new(saver: CheckpointSaverConfig) -> Self {
+        CheckpointConfig {
+            save_interval: default_save_interval(),
+            saver,
+        }
+    }
+
+    /// Checks if the settings are valid, `name` is the name of their section used in errors.
+    pub fn validate(&self, name: &str) -> anyhow::Result<()> {
+        if !self.save_interval.is_finite() || self.save_interval < 0. {
+            bail!(
+                "{}.save_interval: expected a non-negative number of seconds, got {}",
+                name,
+                self.save_interval
+            );
+        }
+        match &self.saver {
+            CheckpointSaverConfig::Table { ttl: Some(ttl), .. } if *ttl <= 0 => {
+                bail!("{}.saver.ttl: must be positive", name)
+            }
+            CheckpointSaverConfig::File { .. } if !cfg!(feature = "file-checkpoint-saver") => {
+                bail!(
+                    "{}.saver: saving checkpoints to a file requires the `file-checkpoint-saver` feature",
+                    name
+                )
+            }
+            CheckpointSaverConfig::Sqlite { .. } if !cfg!(feature = "sqlite-checkpoint-saver") => {
+                bail!(
+                    "{}.saver: saving checkpoints to SQLite requires the `sqlite-checkpoint-saver` feature",
+                    name
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Pause between saving two consecutive checkpoints.
+    pub fn pause_between_saves(&self) -> time::Duration {
+        time::Duration::from_secs_f64(self.save_interval)
+    }
+
+    /// Creates the checkpoint saver, using `session` and `default_keyspace`
+    /// for checkpoints saved in a table of the cluster, in `namespace`.
+    pub async fn build_saver(
+        &self,
+        session: &Arc<Session>,
+        default_keyspace: &str,
+        mut namespace: CheckpointNamespace,
+    ) -> anyhow::Result<Arc<dyn CDCCheckpointSaver>> {
+        match &self.saver {
+            CheckpointSaverConfig::Table {
+                keyspace,
+                table,
+                ttl,
+                application_id,
+                import_from,
+            } => {
+                let keyspace = keyspace.as_deref().unwrap_or(default_keyspace);
+                if let Some(application_id) = application_id {
+                    namespace.application_id = application_id.clone();
+                }
+                let saver = match ttl {
+                    Some(ttl) => {
+                        TableBackedCheckpointSaver::new(
+                            session.clone(),
+                            keyspace,
+                            table,
+                            *ttl,
+                            namespace,
+                        )
+                        .await?
+                    }
+                    None => {
+                        TableBackedCheckpointSaver::new_with_default_ttl(
+                            session.clone(),
+                            keyspace,
+                            table,
+                            namespace,
+                        )
+                        .await?
+                    }
+                };
+                if let Some(import_from) = import_from {
+                    if saver.load_last_generation().await?.is_none() {
+                        let (import_keyspace, import_table) = import_from
+                            .split_once('.')
+                            .unwrap_or((keyspace, import_from));
+                        let imported = saver
+                            .import_legacy_checkpoints(import_keyspace, import_table)
+                            .await?;
+                        info!(
+                            "Imported {} checkpoints from {}.{}",
+                            imported, import_keyspace, import_table
+                        );
+                    }
+                }
+                Ok(Arc::new(saver))
+            }
+            #[cfg(feature = "file-checkpoint-saver")]
+            CheckpointSaverConfig::File { path } => Ok(Arc::new(
+                crate::checkpoints::FileCheckpointSaver::new(path).await?,
+            )),
+            #[cfg(feature = "sqlite-checkpoint-saver")]
+            CheckpointSaverConfig::Sqlite { path, table } => Ok(Arc::new(
+                crate::checkpoints::SqliteCheckpointSaver::new(path, table).await?,
+            )),
+            #[allow(unreachable_patterns)]
+            _ => Err(anyhow!(
+                "The checkpoint saver is not available, enable its feature"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct TestConfig {
+        keyspace: String,
+        source: SessionConfig,
+        timing: TimingConfig,
+        checkpoints: Option<CheckpointConfig>,
+    }
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_load_toml_and_yaml() {
+        let toml_path = write_file(
+            "config.toml",
+            r#"
+                keyspace = "ks"
+
+                [source]
+                hosts = ["127.0.0.1:9042"]
+
+                [timing]
+                window_size = 5
+
+                [checkpoints]
+                saver = { type = "table", table = "checkpoints" }
+            "#,
+        );
+        let yaml_path = write_file(
+            "config.yaml",
+            r#"
+                keyspace: ks
+                source:
+                  hosts: ["127.0.0.1:9042"]
+                timing:
+                  window_size: 5
+                checkpoints:
+                  saver:
+                    type: table
+                    table: checkpoints
+            "#,
+        );
+
+        for path in [toml_path, yaml_path] {
+            let config: TestConfig = load_with_vars(Some(&path), "TEST_", vars(&[])).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(config.keyspace, "ks");
+            assert_eq!(config.source.hosts, vec!["127.0.0.1:9042"]);
+            config.source.validate("source").unwrap();
+            assert_eq!(config.timing.window_size, 5.);
+            assert_eq!(config.timing.safety_interval, 30.);
+            let checkpoints = config.checkpoints.unwrap();
+            assert_eq!(checkpoints.save_interval, 10.);
+            assert!(matches!(
+                checkpoints.saver,
+                CheckpointSaverConfig::Table { keyspace: None, ref table, ttl: None, .. } if table == "checkpoints"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let path = write_file("overrides.toml", "keyspace = \"ks\"\n");
+        let config: TestConfig = load_with_vars(
+            Some(&path),
+            "TEST_",
+            vars(&[
+                ("TEST_KEYSPACE", "other"),
+                ("TEST_SOURCE__HOSTS", r#"["a", "b"]"#),
+                ("TEST_SOURCE__PASSWORD", r#""123""#),
+                ("TEST_TIMING__SLEEP_INTERVAL", "1.5"),
+                ("OTHER_KEYSPACE", "ignored"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.keyspace, "other");
+        assert_eq!(config.source.hosts, vec!["a", "b"]);
+        assert_eq!(config.source.password.as_deref(), Some("123"));
+        assert_eq!(config.timing.sleep_interval, 1.5);
+        assert!(config.source.validate("source").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_of_string_settings() {
+        // Values that aren't strings in JSON are still used as strings by string settings.
+        let config: TestConfig = load_with_vars(
+            None,
+            "TEST_",
+            vars(&[
+                ("TEST_KEYSPACE", "2024"),
+                ("TEST_SOURCE__USERNAME", "true"),
+                ("TEST_SOURCE__PASSWORD", "123456"),
+                ("TEST_TIMING__WINDOW_SIZE", "15"),
+            ]),
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(config.keyspace, "2024");
+        assert_eq!(config.source.username.as_deref(), Some("true"));
+        assert_eq!(config.source.password.as_deref(), Some("123456"));
+        assert_eq!(config.timing.window_size, 15.);
+
+        let err = load_with_vars::<TestConfig>(
+            None,
+            "TEST_",
+            vars(&[("TEST_TIMING__WINDOW_SIZE", "abc")]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("timing.window_size"));
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let path = write_file("unknown.toml", "[timing]\nwindow = 5\n");
+        let err = load_with_vars::<TestConfig>(Some(&path), "TEST_", vars(&[])).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err
+            .to_string()
+            .contains("timing.window: unknown field `window`"));
+
+        let err = load_with_vars::<TestConfig>(
+            None,
+            "TEST_",
+            vars(&[("TEST_CHECKPOINTS__SAVER__TYPE", "cassandra")]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown variant `cassandra`"));
+
+        let path = write_file("config.json", "{}");
+        assert!(load_with_vars::<TestConfig>(Some(&path), "TEST_", vars(&[])).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let timing = TimingConfig {
+            window_size: 0.,
+            ..Default::default()
+        };
+        assert!(timing.validate("timing").is_err());
+    }
+}
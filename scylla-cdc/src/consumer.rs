@@ -1,12 +1,20 @@
 //! A module representing the logic behind consuming the data.
-use crate::cdc_types::StreamID;
+use crate::cdc_types::{RowPosition, StreamID};
+use crate::error_policy::{DeadLetterSink, ErrorPolicy, PolicyConsumer};
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use num_enum::TryFromPrimitive;
+use scylla::cql_to_rust::FromCqlValError;
 use scylla::frame::response::result::CqlValue::Set;
 use scylla::frame::response::result::{ColumnSpec, CqlValue, Row};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Formatter;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+pub use scylla_cdc_macros::FromCDCRow;
 
 /// Trait used to represent a user-defined callback
 /// used for processing read CDC rows.
@@ -20,6 +28,20 @@ use std::fmt::Formatter;
 #[async_trait]
 pub trait Consumer: Send {
     async fn consume_cdc(&mut self, data: CDCRow<'_>) -> anyhow::Result<()>;
+
+    /// Returns the position (see [`CDCRow::position`]) of the last row passed to [`Consumer::consume_cdc`]
+    /// that was durably processed, together with all the rows passed before it.
+    /// `None` means that no row has been acknowledged yet.
+    /// A position of a row that wasn't passed to the consumer acknowledges the rows of its stream
+    /// consumed up to that position, together with all the rows passed before them.
+    ///
+    /// Used only if saving acknowledged progress is enabled with
+    /// [`CDCLogReaderBuilder::save_only_acknowledged_progress`](crate::log_reader::CDCLogReaderBuilder::save_only_acknowledged_progress).
+    /// The reader checks it after every window and saves the end of the last window,
+    /// whose rows were all acknowledged, as a checkpoint.
+    fn acknowledged_position(&self) -> Option<RowPosition> {
+        None
+    }
 }
 
 /// Trait used to represent a factory of [`Consumer`] instances.
@@ -30,6 +52,34 @@ pub trait ConsumerFactory: Sync + Send {
     async fn new_consumer(&self) -> Box<dyn Consumer>;
 }
 
+/// Trait used to represent a user-defined callback
+/// used for processing whole changes read from the CDC log.
+/// Unlike [`Consumer`], it receives all rows describing one change at once,
+/// grouped into a [`CDCChange`].
+///
+/// To use it with [`CDCLogReaderBuilder`](crate::log_reader::CDCLogReaderBuilder),
+/// wrap its factory in a [`ChangeConsumerFactoryAdapter`].
+#[async_trait]
+pub trait ChangeConsumer: Send {
+    async fn consume_change(&mut self, change: CDCChange<'_>) -> anyhow::Result<()>;
+
+    /// Returns the position (see [`CDCChange::position`]) of the last change
+    /// passed to [`ChangeConsumer::consume_change`] that was durably processed,
+    /// together with all the changes passed before it.
+    /// See [`Consumer::acknowledged_position`] for details.
+    fn acknowledged_position(&self) -> Option<RowPosition> {
+        None
+    }
+}
+
+/// Trait used to represent a factory of [`ChangeConsumer`] instances.
+/// For rules about creating new consumers,
+/// please refer to the documentation of ['Consumer'].
+#[async_trait]
+pub trait ChangeConsumerFactory: Sync + Send {
+    async fn new_consumer(&self) -> Box<dyn ChangeConsumer>;
+}
+
 /// Represents different types of CDC operations.
 /// For more information, see [the CDC documentation](<https://docs.scylladb.com/using-scylla/cdc/cdc-log-table/#operation-column>).
 #[derive(Clone, Debug, Eq, PartialEq, TryFromPrimitive)]
@@ -72,9 +122,11 @@ const OPERATION_NAME: &str = "cdc$operation";
 const TTL_NAME: &str = "cdc$ttl";
 const IS_DELETED_PREFIX: &str = "cdc$deleted_";
 const ARE_ELEMENTS_DELETED_PREFIX: &str = "cdc$deleted_elements_";
+const CDC_LOG_TABLE_SUFFIX: &str = "_scylla_cdc_log";
 
 /// A structure used to map names of columns in the CDC
 /// to their indices in an internal vector.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CDCRowSchema {
     // The usize values are indices of given values in the Row.columns vector.
     pub(crate) stream_id: usize,
@@ -84,6 +136,10 @@ pub struct CDCRowSchema {
     pub(crate) operation: usize,
     pub(crate) ttl: usize,
 
+    // Keyspace and name of the logged table, the rows come from.
+    pub(crate) keyspace: String,
+    pub(crate) table_name: String,
+
     // These HashMaps map names of columns in the observed table to matching columns in the CDC table.
     // The usize value is an index of the column in an internal vector inside of the CDCRow struct.
 
@@ -94,6 +150,9 @@ pub struct CDCRowSchema {
     // Maps name of a collection column to a column that tells
     // if elements from this collection were deleted.
     pub(crate) deleted_el_mapping: HashMap<String, usize>,
+
+    // Hash of the names and types of all the columns, in their order.
+    pub(crate) fingerprint: u64,
 }
 
 impl CDCRowSchema {
@@ -108,10 +167,27 @@ impl CDCRowSchema {
         let mut deleted_mapping: HashMap<String, usize> = HashMap::new();
         let mut deleted_el_mapping: HashMap<String, usize> = HashMap::new();
 
+        let (keyspace, table_name) = specs
+            .first()
+            .map(|spec| {
+                let log_table_name = spec.table_spec.table_name.as_str();
+                (
+                    spec.table_spec.ks_name.clone(),
+                    log_table_name
+                        .strip_suffix(CDC_LOG_TABLE_SUFFIX)
+                        .unwrap_or(log_table_name)
+                        .to_string(),
+                )
+            })
+            .unwrap_or_default();
+
         let mut j = 0;
+        let mut hasher = DefaultHasher::new();
 
         // Hashmaps will have indices of data in a new vector without the hardcoded values.
         for (i, spec) in specs.iter().enumerate() {
+            spec.name.hash(&mut hasher);
+            format!("{:?}", spec.typ).hash(&mut hasher);
             match spec.name.as_str() {
                 STREAM_ID_NAME => stream_id = i,
                 TIME_NAME => time = i,
@@ -140,9 +216,12 @@ impl CDCRowSchema {
             end_of_batch,
             operation,
             ttl,
+            keyspace,
+            table_name,
             mapping,
             deleted_mapping,
             deleted_el_mapping,
+            fingerprint: hasher.finish(),
         }
     }
 }
@@ -166,8 +245,8 @@ pub struct CDCRow<'schema> {
     schema: &'schema CDCRowSchema,
 }
 
-impl CDCRow<'_> {
-    pub fn from_row(row: Row, schema: &CDCRowSchema) -> CDCRow {
+impl<'schema> CDCRow<'schema> {
+    pub fn from_row(row: Row, schema: &CDCRowSchema) -> CDCRow<'_> {
         // If cdc read was successful, these default values will not be used.
         let mut stream_id_vec = vec![];
         let mut time = uuid::Uuid::default();
@@ -210,6 +289,20 @@ impl CDCRow<'_> {
         }
     }
 
+    // Copies the row, so it can be passed to the consumer again.
+    pub(crate) fn duplicate(&self) -> CDCRow<'schema> {
+        CDCRow {
+            stream_id: self.stream_id.clone(),
+            time: self.time,
+            batch_seq_no: self.batch_seq_no,
+            end_of_batch: self.end_of_batch,
+            operation: self.operation.clone(),
+            ttl: self.ttl,
+            data: self.data.clone(),
+            schema: self.schema,
+        }
+    }
+
     /// Allows to get a value from the column that corresponds to the logged table.
     /// Returns `None` if the value is `null`.
     /// Panics if the column does not exist in this table.
@@ -298,14 +391,383 @@ impl CDCRow<'_> {
     pub fn get_non_cdc_column_names(&self) -> impl Iterator<Item = &str> {
         self.schema.mapping.keys().map(|column| column.as_str())
     }
+
+    /// Returns a fingerprint of the columns of the CDC log the row was read with.
+    /// Rows read with the same columns have equal fingerprints,
+    /// so comparing them is a cheap way to notice a change of the schema.
+    pub fn schema_fingerprint(&self) -> u64 {
+        self.schema.fingerprint
+    }
+
+    /// Returns the position of the row in the CDC log.
+    pub fn position(&self) -> RowPosition {
+        RowPosition {
+            stream_id: self.stream_id.clone(),
+            time: self.time,
+            batch_seq_no: self.batch_seq_no,
+        }
+    }
+
+    /// Returns the keyspace of the logged table the row comes from.
+    pub fn keyspace(&self) -> &str {
+        &self.schema.keyspace
+    }
+
+    /// Returns the name of the logged table the row comes from.
+    pub fn table_name(&self) -> &str {
+        &self.schema.table_name
+    }
+
+    /// Converts the row into a type implementing [`FromCDCRow`].
+    /// Returns an error naming the column if it doesn't exist
+    /// or its value doesn't match the type of the field.
+    pub fn into_typed<T: FromCDCRow>(self) -> Result<T, FromCDCRowError> {
+        T::from_cdc_row(self)
+    }
+}
+
+/// Trait used to convert a [`CDCRow`] into a user-defined type.
+/// It can be derived for structs with named fields using `#[derive(FromCDCRow)]`.
+///
+/// By default, every field is read from the column of the logged table with the same name.
+/// Fields of type `Option<T>` are set to `None` if the value was not set in the operation,
+/// fields of other types require the value to be present.
+/// The source of a field can be changed with the following attributes:
+/// * `#[cdc(rename = "column")]` reads the value of the given column,
+/// * `#[cdc(deleted = "column")]` reads if the value of the given column was deleted
+///   (see [`CDCRow::is_value_deleted`]), the field has to be of type `bool`,
+/// * `#[cdc(deleted_elements = "column")]` reads elements deleted from the given collection
+///   (see [`CDCRow::get_deleted_elements`]), e.g. into a `Vec<T>`,
+/// * `#[cdc(stream_id)]`, `#[cdc(time)]`, `#[cdc(batch_seq_no)]`, `#[cdc(end_of_batch)]`,
+///   `#[cdc(operation)]` and `#[cdc(ttl)]` read the given metadata of the row.
+///
+/// # Example
+/// ```
+/// use scylla_cdc::consumer::{CDCRow, FromCDCRow, OperationType};
+///
+/// #[derive(FromCDCRow)]
+/// struct Change {
+///     #[cdc(operation)]
+///     operation: OperationType,
+///     pk: i32,
+///     ck: i32,
+///     v: Option<i32>,
+///     #[cdc(deleted = "v")]
+///     v_deleted: bool,
+///     #[cdc(rename = "vs")]
+///     added_elements: Option<Vec<i32>>,
+///     #[cdc(deleted_elements = "vs")]
+///     removed_elements: Vec<i32>,
+/// }
+///
+/// fn handle(data: CDCRow<'_>) -> anyhow::Result<()> {
+///     let change: Change = data.into_typed()?;
+///     println!("{} on pk = {}, ck = {}", change.operation, change.pk, change.ck);
+///     Ok(())
+/// }
+/// ```
+pub trait FromCDCRow: Sized {
+    fn from_cdc_row(row: CDCRow<'_>) -> Result<Self, FromCDCRowError>;
+}
+
+/// Error returned when a [`CDCRow`] can't be converted to a type implementing [`FromCDCRow`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FromCDCRowError {
+    ColumnNotFound(String),
+    BadCqlVal {
+        err: FromCqlValError,
+        column: String,
+    },
+}
+
+impl fmt::Display for FromCDCRowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FromCDCRowError::ColumnNotFound(column) => {
+                write!(f, "Column {} not found in the CDC log", column)
+            }
+            FromCDCRowError::BadCqlVal { err, column } => {
+                write!(f, "{} in the column {}", err, column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FromCDCRowError {}
+
+/// Represents a single change recorded in the CDC log,
+/// i.e. all rows from one stream that share `cdc$time`,
+/// from the row with `cdc$batch_seq_no` equal to 0 up to the row marked with `cdc$end_of_batch`.
+/// The pre-image and post-image rows are present only if they are enabled for the logged table.
+/// For more information, see [the CDC documentation](<https://docs.scylladb.com/using-scylla/cdc/cdc-log-table/>).
+pub struct CDCChange<'schema> {
+    pub preimage: Option<CDCRow<'schema>>,
+    pub delta: Vec<CDCRow<'schema>>,
+    pub postimage: Option<CDCRow<'schema>>,
+}
+
+impl CDCChange<'_> {
+    /// Returns the position of the last row of the change in the CDC log.
+    pub fn position(&self) -> RowPosition {
+        self.preimage
+            .iter()
+            .chain(&self.delta)
+            .chain(&self.postimage)
+            .max_by_key(|row| row.batch_seq_no)
+            .expect("a change consists of at least one row")
+            .position()
+    }
+}
+
+// Owned contents of a CDCRow, stored by ChangeConsumerAdapter
+// until the whole change is received.
+#[derive(Clone)]
+struct BufferedRow {
+    stream_id: StreamID,
+    time: uuid::Uuid,
+    batch_seq_no: i32,
+    end_of_batch: bool,
+    operation: OperationType,
+    ttl: Option<i64>,
+    data: Vec<Option<CqlValue>>,
+}
+
+impl BufferedRow {
+    fn new(row: CDCRow<'_>) -> BufferedRow {
+        BufferedRow {
+            stream_id: row.stream_id,
+            time: row.time,
+            batch_seq_no: row.batch_seq_no,
+            end_of_batch: row.end_of_batch,
+            operation: row.operation,
+            ttl: row.ttl,
+            data: row.data,
+        }
+    }
+
+    fn into_cdc_row(self, schema: &CDCRowSchema) -> CDCRow<'_> {
+        CDCRow {
+            stream_id: self.stream_id,
+            time: self.time,
+            batch_seq_no: self.batch_seq_no,
+            end_of_batch: self.end_of_batch,
+            operation: self.operation,
+            ttl: self.ttl,
+            data: self.data,
+            schema,
+        }
+    }
+}
+
+fn build_change(rows: Vec<BufferedRow>, schema: &CDCRowSchema) -> CDCChange<'_> {
+    let mut change = CDCChange {
+        preimage: None,
+        delta: Vec::with_capacity(rows.len()),
+        postimage: None,
+    };
+
+    for row in rows {
+        let row = row.into_cdc_row(schema);
+        match row.operation {
+            OperationType::PreImage => change.preimage = Some(row),
+            OperationType::PostImage => change.postimage = Some(row),
+            _ => change.delta.push(row),
+        }
+    }
+
+    change
+}
+
+/// Adapter allowing a [`ChangeConsumer`] to be used as a [`Consumer`].
+/// Rows are buffered until the row marked with `cdc$end_of_batch` is received,
+/// then the whole change is passed to the wrapped consumer.
+///
+/// Errors of the wrapped consumer are handled by the adapter's [`ErrorPolicy`],
+/// so a failed change is retried, skipped or passed to the dead letter sink as a whole.
+/// The reader consuming rows through the adapter should use [`ErrorPolicy::halt()`].
+pub struct ChangeConsumerAdapter {
+    consumer: Box<dyn ChangeConsumer>,
+    error_policy: ErrorPolicy,
+    // Copy of the schema of buffered rows, as they can't outlive the original one.
+    schema: Option<CDCRowSchema>,
+    buffered_rows: Vec<BufferedRow>,
+}
+
+impl ChangeConsumerAdapter {
+    pub fn new(consumer: Box<dyn ChangeConsumer>) -> ChangeConsumerAdapter {
+        ChangeConsumerAdapter {
+            consumer,
+            error_policy: ErrorPolicy::halt(),
+            schema: None,
+            buffered_rows: vec![],
+        }
+    }
+
+    /// Set the policy of handling changes that the wrapped consumer failed to consume.
+    /// By default, the error is returned, which stops the reader.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    // Passes the buffered change to the consumer and handles its errors according to the error policy.
+    async fn flush_change(&mut self) -> anyhow::Result<()> {
+        let rows = std::mem::take(&mut self.buffered_rows);
+        let time = rows[0].time;
+        let mut consumer = BufferedChangeConsumer {
+            consumer: &mut self.consumer,
+            schema: self.schema.as_ref().unwrap(),
+        };
+        self.error_policy.run(&mut consumer, time, rows).await
+    }
+}
+
+// Passes the buffered rows of a change to the wrapped consumer.
+struct BufferedChangeConsumer<'a> {
+    consumer: &'a mut Box<dyn ChangeConsumer>,
+    schema: &'a CDCRowSchema,
+}
+
+impl PolicyConsumer<Vec<BufferedRow>> for BufferedChangeConsumer<'_> {
+    const KIND: &'static str = "change";
+
+    fn consume<'a>(&'a mut self, rows: Vec<BufferedRow>) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        Vec<BufferedRow>: 'a,
+    {
+        self.consumer
+            .consume_change(build_change(rows, self.schema))
+    }
+
+    fn duplicate(rows: &Vec<BufferedRow>) -> Vec<BufferedRow> {
+        rows.clone()
+    }
+
+    fn send_dead_letter<'a>(
+        &'a mut self,
+        sink: &'a dyn DeadLetterSink,
+        rows: Vec<BufferedRow>,
+        error: &'a anyhow::Error,
+    ) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        Vec<BufferedRow>: 'a,
+    {
+        Box::pin(async move {
+            for row in rows {
+                sink.send_dead_letter(row.into_cdc_row(self.schema), error)
+                    .await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Passes single rows to the consumer.
+impl<'schema> PolicyConsumer<CDCRow<'schema>> for Box<dyn Consumer> {
+    const KIND: &'static str = "row";
+
+    fn consume<'a>(&'a mut self, row: CDCRow<'schema>) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        'schema: 'a,
+    {
+        self.consume_cdc(row)
+    }
+
+    fn duplicate(row: &CDCRow<'schema>) -> CDCRow<'schema> {
+        row.duplicate()
+    }
+
+    fn send_dead_letter<'a>(
+        &'a mut self,
+        sink: &'a dyn DeadLetterSink,
+        row: CDCRow<'schema>,
+        error: &'a anyhow::Error,
+    ) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        'schema: 'a,
+    {
+        sink.send_dead_letter(row, error)
+    }
+}
+
+#[async_trait]
+impl Consumer for ChangeConsumerAdapter {
+    async fn consume_cdc(&mut self, data: CDCRow<'_>) -> anyhow::Result<()> {
+        if let Some(first) = self.buffered_rows.first() {
+            if first.stream_id != data.stream_id || first.time != data.time {
+                let (stream_id, time) = (first.stream_id.clone(), first.time);
+                self.buffered_rows.clear();
+                anyhow::bail!(
+                    "Received a row of stream {} with cdc$time {} before the end of the change of stream {} with cdc$time {}",
+                    data.stream_id,
+                    data.time,
+                    stream_id,
+                    time
+                );
+            }
+        }
+
+        // All rows of one change are read with the same schema,
+        // so it has to be copied only when a new change starts.
+        if self.buffered_rows.is_empty() && self.schema.as_ref() != Some(data.schema) {
+            self.schema = Some(data.schema.clone());
+        }
+
+        let end_of_batch = data.end_of_batch;
+        self.buffered_rows.push(BufferedRow::new(data));
+
+        if end_of_batch {
+            self.flush_change().await?;
+        }
+
+        Ok(())
+    }
+
+    fn acknowledged_position(&self) -> Option<RowPosition> {
+        self.consumer.acknowledged_position()
+    }
+}
+
+/// Adapter allowing a [`ChangeConsumerFactory`] to be used as a [`ConsumerFactory`].
+/// Every created consumer is wrapped in a [`ChangeConsumerAdapter`].
+pub struct ChangeConsumerFactoryAdapter {
+    factory: Arc<dyn ChangeConsumerFactory>,
+    error_policy: ErrorPolicy,
+}
+
+impl ChangeConsumerFactoryAdapter {
+    pub fn new(factory: Arc<dyn ChangeConsumerFactory>) -> ChangeConsumerFactoryAdapter {
+        ChangeConsumerFactoryAdapter {
+            factory,
+            error_policy: ErrorPolicy::halt(),
+        }
+    }
+
+    /// Set the error policy of the created adapters,
+    /// see [`ChangeConsumerAdapter::with_error_policy`].
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+}
+
+#[async_trait]
+impl ConsumerFactory for ChangeConsumerFactoryAdapter {
+    async fn new_consumer(&self) -> Box<dyn Consumer> {
+        Box::new(
+            ChangeConsumerAdapter::new(self.factory.new_consumer().await)
+                .with_error_policy(self.error_policy.clone()),
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error_policy::DeadLetterSink;
+    use scylla::frame::response::result::{ColumnType, TableSpec};
     use scylla::Session;
     use scylla_cdc_test_utils::prepare_db;
-    use std::sync::Arc;
     // These tests should be indifferent to things like number of Scylla nodes,
     // so if run separately, they can be tested on one Scylla instance.
 
@@ -564,4 +1026,360 @@ mod tests {
 
         assert!(empty_vec.is_empty());
     }
+
+    fn make_column_spec(name: &str, typ: ColumnType) -> ColumnSpec {
+        ColumnSpec {
+            table_spec: TableSpec {
+                ks_name: "ks".to_string(),
+                table_name: TEST_SINGLE_VALUE_CDC_TABLE.to_string(),
+            },
+            name: name.to_string(),
+            typ,
+        }
+    }
+
+    // Returns specs of columns in the CDC log of the single value table.
+    fn make_single_value_column_specs() -> Vec<ColumnSpec> {
+        vec![
+            make_column_spec("cdc$stream_id", ColumnType::Blob),
+            make_column_spec("cdc$time", ColumnType::Timeuuid),
+            make_column_spec("cdc$batch_seq_no", ColumnType::Int),
+            make_column_spec("cdc$end_of_batch", ColumnType::Boolean),
+            make_column_spec("cdc$operation", ColumnType::TinyInt),
+            make_column_spec("cdc$ttl", ColumnType::BigInt),
+            make_column_spec("pk", ColumnType::Int),
+            make_column_spec("ck", ColumnType::Int),
+            make_column_spec("v", ColumnType::Int),
+            make_column_spec("cdc$deleted_v", ColumnType::Boolean),
+        ]
+    }
+
+    fn make_single_value_row(
+        time: uuid::Uuid,
+        batch_seq_no: i32,
+        end_of_batch: bool,
+        operation: OperationType,
+        v: i32,
+    ) -> Row {
+        Row {
+            columns: vec![
+                Some(CqlValue::Blob(vec![1, 2, 3])),
+                Some(CqlValue::Timeuuid(time)),
+                Some(CqlValue::Int(batch_seq_no)),
+                Some(CqlValue::Boolean(end_of_batch)),
+                Some(CqlValue::TinyInt(operation as i8)),
+                None,
+                Some(CqlValue::Int(1)),
+                Some(CqlValue::Int(2)),
+                Some(CqlValue::Int(v)),
+                None,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_schema_fingerprint() {
+        let specs = make_single_value_column_specs();
+        let mut added_column_specs = specs.clone();
+        added_column_specs.push(make_column_spec("v2", ColumnType::Text));
+
+        let fingerprint = |specs: &[ColumnSpec]| CDCRowSchema::new(specs).fingerprint;
+        assert_eq!(fingerprint(&specs), fingerprint(&specs));
+        assert_ne!(fingerprint(&specs), fingerprint(&added_column_specs));
+    }
+
+    type ReceivedChange = (Option<i32>, Vec<(OperationType, i32)>, Option<i32>);
+
+    // Records every received change and fails to consume the first `failures` of them.
+    struct RecordingChangeConsumer {
+        received_changes: Arc<std::sync::Mutex<Vec<ReceivedChange>>>,
+        failures: usize,
+    }
+
+    #[async_trait]
+    impl ChangeConsumer for RecordingChangeConsumer {
+        async fn consume_change(&mut self, change: CDCChange<'_>) -> anyhow::Result<()> {
+            let get_v = |row: &CDCRow<'_>| row.get_value("v").as_ref().unwrap().as_int().unwrap();
+            let received = (
+                change.preimage.as_ref().map(get_v),
+                change
+                    .delta
+                    .iter()
+                    .map(|row| (row.operation.clone(), get_v(row)))
+                    .collect(),
+                change.postimage.as_ref().map(get_v),
+            );
+            self.received_changes.lock().unwrap().push(received);
+            if self.failures > 0 {
+                self.failures -= 1;
+                anyhow::bail!("Failed change");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_change_consumer_adapter_groups_rows() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let received_changes = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut adapter = ChangeConsumerAdapter::new(Box::new(RecordingChangeConsumer {
+            received_changes: Arc::clone(&received_changes),
+            failures: 0,
+        }));
+
+        let first_time = uuid::Uuid::from_u128(1);
+        let second_time = uuid::Uuid::from_u128(2);
+        let rows = vec![
+            make_single_value_row(first_time, 0, false, OperationType::PreImage, 1),
+            make_single_value_row(first_time, 1, false, OperationType::RowUpdate, 2),
+            make_single_value_row(first_time, 2, false, OperationType::RowUpdate, 3),
+            make_single_value_row(first_time, 3, true, OperationType::PostImage, 3),
+            make_single_value_row(second_time, 0, true, OperationType::RowInsert, 4),
+        ];
+
+        for (i, row) in rows.into_iter().enumerate() {
+            adapter
+                .consume_cdc(CDCRow::from_row(row, &schema))
+                .await
+                .unwrap();
+            // The first change can't be passed before its last row is consumed.
+            if i < 3 {
+                assert!(received_changes.lock().unwrap().is_empty());
+            }
+        }
+
+        assert_eq!(
+            *received_changes.lock().unwrap(),
+            vec![
+                (
+                    Some(1),
+                    vec![(OperationType::RowUpdate, 2), (OperationType::RowUpdate, 3)],
+                    Some(3)
+                ),
+                (None, vec![(OperationType::RowInsert, 4)], None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_consumer_adapter_rejects_unfinished_changes() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let received_changes = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut adapter = ChangeConsumerAdapter::new(Box::new(RecordingChangeConsumer {
+            received_changes: Arc::clone(&received_changes),
+            failures: 0,
+        }));
+
+        let first_time = uuid::Uuid::from_u128(1);
+        let second_time = uuid::Uuid::from_u128(2);
+        let unfinished = make_single_value_row(first_time, 0, false, OperationType::RowUpdate, 1);
+        let next = make_single_value_row(second_time, 0, true, OperationType::RowUpdate, 2);
+
+        adapter
+            .consume_cdc(CDCRow::from_row(unfinished, &schema))
+            .await
+            .unwrap();
+        assert!(adapter
+            .consume_cdc(CDCRow::from_row(next, &schema))
+            .await
+            .is_err());
+        assert!(received_changes.lock().unwrap().is_empty());
+    }
+
+    #[derive(Default)]
+    struct TestDeadLetterSink {
+        rows: std::sync::Mutex<Vec<(OperationType, i32)>>,
+    }
+
+    #[async_trait]
+    impl DeadLetterSink for TestDeadLetterSink {
+        async fn send_dead_letter(
+            &self,
+            row: CDCRow<'_>,
+            _error: &anyhow::Error,
+        ) -> anyhow::Result<()> {
+            let v = row.get_value("v").as_ref().unwrap().as_int().unwrap();
+            self.rows.lock().unwrap().push((row.operation, v));
+            Ok(())
+        }
+    }
+
+    // Passes rows of a change with a pre-image and a post-image to the adapter.
+    async fn consume_test_change(
+        adapter: &mut ChangeConsumerAdapter,
+        schema: &CDCRowSchema,
+        time: uuid::Uuid,
+    ) -> anyhow::Result<()> {
+        let rows = vec![
+            make_single_value_row(time, 0, false, OperationType::PreImage, 1),
+            make_single_value_row(time, 1, false, OperationType::RowUpdate, 2),
+            make_single_value_row(time, 2, true, OperationType::PostImage, 2),
+        ];
+        for row in rows {
+            adapter.consume_cdc(CDCRow::from_row(row, schema)).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_change_consumer_adapter_error_policy() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let time = uuid::Uuid::from_u128(1);
+        let whole_change = (Some(1), vec![(OperationType::RowUpdate, 2)], Some(2));
+
+        // A retry passes the whole change again.
+        let received_changes = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut adapter = ChangeConsumerAdapter::new(Box::new(RecordingChangeConsumer {
+            received_changes: Arc::clone(&received_changes),
+            failures: 1,
+        }))
+        .with_error_policy(ErrorPolicy::halt().with_retries(1, std::time::Duration::ZERO));
+        consume_test_change(&mut adapter, &schema, time)
+            .await
+            .unwrap();
+        assert_eq!(
+            *received_changes.lock().unwrap(),
+            vec![whole_change.clone(), whole_change.clone()]
+        );
+
+        // All rows of a change that can't be consumed are passed to the dead letter sink.
+        let sink = Arc::new(TestDeadLetterSink::default());
+        let received_changes = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut adapter = ChangeConsumerAdapter::new(Box::new(RecordingChangeConsumer {
+            received_changes: Arc::clone(&received_changes),
+            failures: 2,
+        }))
+        .with_error_policy(
+            ErrorPolicy::dead_letter(sink.clone()).with_retries(1, std::time::Duration::ZERO),
+        );
+        consume_test_change(&mut adapter, &schema, time)
+            .await
+            .unwrap();
+        assert_eq!(
+            *received_changes.lock().unwrap(),
+            vec![whole_change.clone(), whole_change]
+        );
+        assert_eq!(
+            *sink.rows.lock().unwrap(),
+            vec![
+                (OperationType::PreImage, 1),
+                (OperationType::RowUpdate, 2),
+                (OperationType::PostImage, 2)
+            ]
+        );
+
+        // Without a policy, the error is returned.
+        let mut adapter = ChangeConsumerAdapter::new(Box::new(RecordingChangeConsumer {
+            received_changes: Arc::new(std::sync::Mutex::new(vec![])),
+            failures: 1,
+        }));
+        assert!(consume_test_change(&mut adapter, &schema, time)
+            .await
+            .is_err());
+    }
+
+    #[derive(FromCDCRow, Debug, PartialEq)]
+    struct SingleValueChange {
+        #[cdc(operation)]
+        operation: OperationType,
+        #[cdc(batch_seq_no)]
+        batch_seq_no: i32,
+        pk: i32,
+        #[cdc(rename = "ck")]
+        clustering_key: i32,
+        v: Option<i32>,
+        #[cdc(deleted = "v")]
+        v_deleted: bool,
+    }
+
+    #[derive(FromCDCRow, Debug)]
+    struct MissingColumnChange {
+        #[allow(dead_code)]
+        no_such_column: Option<i32>,
+    }
+
+    #[derive(FromCDCRow, Debug)]
+    struct WrongTypeChange {
+        #[allow(dead_code)]
+        v: Option<String>,
+    }
+
+    #[test]
+    fn test_into_typed() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let row = make_single_value_row(
+            uuid::Uuid::from_u128(1),
+            3,
+            true,
+            OperationType::RowUpdate,
+            7,
+        );
+
+        let change: SingleValueChange = CDCRow::from_row(row, &schema).into_typed().unwrap();
+
+        assert_eq!(
+            change,
+            SingleValueChange {
+                operation: OperationType::RowUpdate,
+                batch_seq_no: 3,
+                pk: 1,
+                clustering_key: 2,
+                v: Some(7),
+                v_deleted: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_row_table_name() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let row = make_single_value_row(
+            uuid::Uuid::from_u128(1),
+            0,
+            true,
+            OperationType::RowInsert,
+            7,
+        );
+        let row = CDCRow::from_row(row, &schema);
+
+        assert_eq!(row.keyspace(), "ks");
+        assert_eq!(row.table_name(), "single_value");
+    }
+
+    #[test]
+    fn test_into_typed_errors() {
+        let schema = CDCRowSchema::new(&make_single_value_column_specs());
+        let make_row = || {
+            let row = make_single_value_row(
+                uuid::Uuid::from_u128(1),
+                0,
+                true,
+                OperationType::RowInsert,
+                7,
+            );
+            CDCRow::from_row(row, &schema)
+        };
+
+        assert_eq!(
+            make_row().into_typed::<MissingColumnChange>().unwrap_err(),
+            FromCDCRowError::ColumnNotFound("no_such_column".to_string())
+        );
+        assert_eq!(
+            make_row().into_typed::<WrongTypeChange>().unwrap_err(),
+            FromCDCRowError::BadCqlVal {
+                err: FromCqlValError::BadCqlType,
+                column: "v".to_string()
+            }
+        );
+
+        let mut row = make_row();
+        row.take_value("pk");
+        assert_eq!(
+            row.into_typed::<SingleValueChange>().unwrap_err(),
+            FromCDCRowError::BadCqlVal {
+                err: FromCqlValError::ValIsNull,
+                column: "pk".to_string()
+            }
+        );
+    }
 }
@@ -11,15 +11,19 @@ mod tests {
     use async_trait::async_trait;
     use futures::future::RemoteHandle;
     use itertools::{repeat_n, Itertools};
-    use scylla::frame::response::result::CqlValue;
+    use scylla::frame::response::result::{ColumnType, CqlValue};
     use scylla::frame::value::{Value, ValueTooBig};
     use scylla::prepared_statement::PreparedStatement;
     use scylla::Session;
     use scylla_cdc_test_utils::{now, prepare_db};
     use tokio::sync::Mutex;
 
-    use crate::checkpoints::TableBackedCheckpointSaver;
+    use crate::cdc_types::{GenerationTimestamp, RowPosition, StreamID};
+    use crate::checkpoints::{
+        CDCCheckpointSaver, Checkpoint, CheckpointNamespace, TableBackedCheckpointSaver,
+    };
     use crate::consumer::*;
+    use crate::in_memory_log_source::{InMemoryLogRow, InMemoryLogSource};
 
     const SECOND_IN_MILLIS: u64 = 1_000;
     const SLEEP_INTERVAL: u64 = SECOND_IN_MILLIS / 10;
@@ -204,7 +208,7 @@ mod tests {
         fn push_back(&mut self, pk: Vec<PrimaryKeyValue>, operation: Operation) {
             self.performed_operations
                 .entry(pk)
-                .or_insert_with(VecDeque::new)
+                .or_default()
                 .push_back(operation);
         }
 
@@ -371,7 +375,7 @@ mod tests {
         let mut test = Test::new("int_string_test", vec!["int", "text"])
             .await
             .unwrap();
-        let strings = vec!["blep".to_string(), "nghu".to_string(), "pkeee".to_string()];
+        let strings = ["blep".to_string(), "nghu".to_string(), "pkeee".to_string()];
         let start = now();
 
         for i in 0..100 {
@@ -419,6 +423,7 @@ mod tests {
                 &test.keyspace,
                 &format!("{}_checkpoints", test.table_name),
                 300,
+                CheckpointNamespace::new("e2e_tests", &test.keyspace, &test.table_name),
             )
             .await
             .unwrap(),
@@ -488,4 +493,717 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn e2e_test_in_memory_source() {
+        let source = Arc::new(InMemoryLogSource::new());
+        let start = now() - chrono::Duration::seconds(5);
+        let next_generation_start = start + chrono::Duration::seconds(1);
+        let stream_ids: Vec<StreamID> = (1..=3).map(|id| StreamID::new(vec![id])).collect();
+
+        source.add_generation(
+            GenerationTimestamp { timestamp: start },
+            vec![vec![stream_ids[0].clone()], vec![stream_ids[1].clone()]],
+        );
+        source.add_generation(
+            GenerationTimestamp {
+                timestamp: next_generation_start,
+            },
+            vec![vec![stream_ids[2].clone()]],
+        );
+        source.add_table(
+            "ks",
+            "t",
+            &[
+                ("pk1", ColumnType::Int),
+                ("ck", ColumnType::Int),
+                ("v", ColumnType::Int),
+            ],
+        );
+
+        let changes = [
+            (0, 100, OperationType::RowInsert, 0, 1),
+            (1, 500, OperationType::RowInsert, 1, 2),
+            (2, 1500, OperationType::RowUpdate, 0, 3),
+            (2, 2000, OperationType::RowUpdate, 1, 4),
+        ];
+        for (stream, millis, operation_type, pk, v) in changes.clone() {
+            let row = InMemoryLogRow::new(operation_type)
+                .value("pk1", CqlValue::Int(pk))
+                .value("ck", CqlValue::Int(0))
+                .value("v", CqlValue::Int(v));
+            source
+                .push_change(
+                    "ks",
+                    "t",
+                    &stream_ids[stream],
+                    start + chrono::Duration::milliseconds(millis),
+                    vec![row],
+                )
+                .unwrap();
+        }
+
+        let results = Arc::new(Mutex::new(HashMap::new()));
+        let factory = Arc::new(TestConsumerFactory::new(Arc::clone(&results)));
+
+        let (_tester, handle) = CDCLogReaderBuilder::new()
+            .log_source(source)
+            .keyspace("ks")
+            .table_name("t")
+            .start_timestamp(start)
+            .end_timestamp(start + chrono::Duration::seconds(3))
+            .window_size(time::Duration::from_millis(WINDOW_SIZE))
+            .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+            .sleep_interval(time::Duration::from_millis(SLEEP_INTERVAL))
+            .consumer_factory(factory)
+            .build()
+            .await
+            .expect("Creating cdc log printer failed!");
+
+        handle.await.unwrap();
+
+        let mut expected = HashMap::new();
+        for (_, _, operation_type, pk, v) in changes {
+            expected
+                .entry(vec![PrimaryKeyValue::Int(pk)])
+                .or_insert_with(VecDeque::new)
+                .push_back(Operation::new(operation_type, Some(0), Some(v)));
+        }
+        assert_eq!(*results.lock().await, expected);
+    }
+
+    struct TableRecordingConsumer {
+        read_values: Arc<Mutex<Vec<(String, i32)>>>,
+    }
+
+    #[async_trait]
+    impl Consumer for TableRecordingConsumer {
+        async fn consume_cdc(&mut self, mut data: CDCRow<'_>) -> Result<()> {
+            let value = data.take_value("v").unwrap().as_int().unwrap();
+            self.read_values
+                .lock()
+                .await
+                .push((data.table_name().to_string(), value));
+            Ok(())
+        }
+    }
+
+    struct TableRecordingConsumerFactory {
+        read_values: Arc<Mutex<Vec<(String, i32)>>>,
+    }
+
+    #[async_trait]
+    impl ConsumerFactory for TableRecordingConsumerFactory {
+        async fn new_consumer(&self) -> Box<dyn Consumer> {
+            Box::new(TableRecordingConsumer {
+                read_values: Arc::clone(&self.read_values),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn e2e_test_in_memory_source_many_tables() {
+        let source = Arc::new(InMemoryLogSource::new());
+        let start = now() - chrono::Duration::seconds(5);
+        let stream_id = StreamID::new(vec![1]);
+        let table_names = ["t1", "t2", "t3"];
+
+        source.add_generation(
+            GenerationTimestamp { timestamp: start },
+            vec![vec![stream_id.clone()]],
+        );
+        for (i, table_name) in table_names.iter().enumerate() {
+            source.add_table(
+                "ks",
+                table_name,
+                &[("pk", ColumnType::Int), ("v", ColumnType::Int)],
+            );
+            let row = InMemoryLogRow::new(OperationType::RowInsert)
+                .value("pk", CqlValue::Int(0))
+                .value("v", CqlValue::Int(i as i32));
+            source
+                .push_change(
+                    "ks",
+                    table_name,
+                    &stream_id,
+                    start + chrono::Duration::milliseconds(100 * i as i64),
+                    vec![row],
+                )
+                .unwrap();
+        }
+
+        for (read_all_tables, expected_tables) in
+            [(false, &table_names[..2]), (true, &table_names[..])]
+        {
+            let read_values = Arc::new(Mutex::new(vec![]));
+            let factory = Arc::new(TableRecordingConsumerFactory {
+                read_values: Arc::clone(&read_values),
+            });
+
+            let builder = CDCLogReaderBuilder::new()
+                .log_source(source.clone())
+                .keyspace("ks")
+                .start_timestamp(start)
+                .end_timestamp(start + chrono::Duration::seconds(1))
+                .window_size(time::Duration::from_millis(WINDOW_SIZE))
+                .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+                .sleep_interval(time::Duration::from_millis(SLEEP_INTERVAL))
+                .consumer_factory(factory);
+            let builder = if read_all_tables {
+                builder.all_tables()
+            } else {
+                builder.table_names(expected_tables)
+            };
+            let (_tester, handle) = builder
+                .build()
+                .await
+                .expect("Creating cdc log printer failed!");
+
+            handle.await.unwrap();
+
+            let mut read_values = read_values.lock().await.clone();
+            read_values.sort();
+            let expected_values: Vec<(String, i32)> = expected_tables
+                .iter()
+                .enumerate()
+                .map(|(i, table_name)| (table_name.to_string(), i as i32))
+                .collect();
+            assert_eq!(read_values, expected_values);
+        }
+    }
+
+    #[derive(Default)]
+    struct InMemoryCheckpointSaver {
+        generation: std::sync::Mutex<Option<GenerationTimestamp>>,
+        checkpoints: std::sync::Mutex<HashMap<StreamID, chrono::Duration>>,
+    }
+
+    #[async_trait]
+    impl CDCCheckpointSaver for InMemoryCheckpointSaver {
+        async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> Result<()> {
+            self.checkpoints.lock().unwrap().insert(
+                checkpoint.stream_id.clone(),
+                chrono::Duration::from_std(checkpoint.timestamp)?,
+            );
+            Ok(())
+        }
+
+        async fn save_new_generation(&self, generation: &GenerationTimestamp) -> Result<()> {
+            *self.generation.lock().unwrap() = Some(generation.clone());
+            Ok(())
+        }
+
+        async fn load_last_generation(&self) -> Result<Option<GenerationTimestamp>> {
+            Ok(self.generation.lock().unwrap().clone())
+        }
+
+        async fn load_last_checkpoint(
+            &self,
+            stream_id: &StreamID,
+        ) -> Result<Option<chrono::Duration>> {
+            Ok(self.checkpoints.lock().unwrap().get(stream_id).cloned())
+        }
+    }
+
+    // Commits consumed values to the durable storage in groups of `commit_every` rows
+    // and fails after consuming `crash_after` rows, losing the values that weren't committed.
+    struct CommittingConsumer {
+        durable_values: Arc<std::sync::Mutex<Vec<i32>>>,
+        uncommitted: Vec<(RowPosition, i32)>,
+        acknowledged_position: Option<RowPosition>,
+        consumed_count: usize,
+        commit_every: usize,
+        crash_after: Option<usize>,
+    }
+
+    #[async_trait]
+    impl Consumer for CommittingConsumer {
+        async fn consume_cdc(&mut self, mut data: CDCRow<'_>) -> Result<()> {
+            if Some(self.consumed_count) == self.crash_after {
+                bail!("Simulated crash");
+            }
+            self.consumed_count += 1;
+
+            let value = data.take_value("v").unwrap().as_int().unwrap();
+            self.uncommitted.push((data.position(), value));
+            if self.uncommitted.len() == self.commit_every {
+                let mut durable_values = self.durable_values.lock().unwrap();
+                for (position, value) in self.uncommitted.drain(..) {
+                    durable_values.push(value);
+                    self.acknowledged_position = Some(position);
+                }
+            }
+            Ok(())
+        }
+
+        fn acknowledged_position(&self) -> Option<RowPosition> {
+            self.acknowledged_position.clone()
+        }
+    }
+
+    struct CommittingConsumerFactory {
+        durable_values: Arc<std::sync::Mutex<Vec<i32>>>,
+        commit_every: usize,
+        crash_after: Option<usize>,
+    }
+
+    #[async_trait]
+    impl ConsumerFactory for CommittingConsumerFactory {
+        async fn new_consumer(&self) -> Box<dyn Consumer> {
+            Box::new(CommittingConsumer {
+                durable_values: Arc::clone(&self.durable_values),
+                uncommitted: vec![],
+                acknowledged_position: None,
+                consumed_count: 0,
+                commit_every: self.commit_every,
+                crash_after: self.crash_after,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn e2e_test_no_rows_lost_with_acknowledged_progress() {
+        // Values of the first generation, followed by the values of the second one.
+        const FIRST_GENERATION_VALUES: i32 = 16;
+        const N: i32 = 30;
+        let source = Arc::new(InMemoryLogSource::new());
+        let start = now() - chrono::Duration::seconds(5);
+        let first_generation = GenerationTimestamp { timestamp: start };
+        let second_generation = GenerationTimestamp {
+            timestamp: start + chrono::Duration::seconds(2),
+        };
+        let stream_ids = [StreamID::new(vec![1]), StreamID::new(vec![2])];
+
+        source.add_generation(first_generation.clone(), vec![vec![stream_ids[0].clone()]]);
+        source.add_generation(second_generation.clone(), vec![vec![stream_ids[1].clone()]]);
+        source.add_table(
+            "ks",
+            "t",
+            &[("pk", ColumnType::Int), ("v", ColumnType::Int)],
+        );
+        for i in 0..N {
+            let (stream_id, generation, offset) = if i < FIRST_GENERATION_VALUES {
+                (&stream_ids[0], &first_generation, i)
+            } else {
+                (
+                    &stream_ids[1],
+                    &second_generation,
+                    i - FIRST_GENERATION_VALUES,
+                )
+            };
+            let row = InMemoryLogRow::new(OperationType::RowInsert)
+                .value("pk", CqlValue::Int(0))
+                .value("v", CqlValue::Int(i));
+            source
+                .push_change(
+                    "ks",
+                    "t",
+                    stream_id,
+                    generation.timestamp + chrono::Duration::milliseconds(100 * offset as i64),
+                    vec![row],
+                )
+                .unwrap();
+        }
+
+        let checkpoint_saver = Arc::new(InMemoryCheckpointSaver::default());
+        let durable_values = Arc::new(std::sync::Mutex::new(vec![]));
+
+        // The first run crashes in the middle of the first generation.
+        // The second one reads the rest of the log, but leaves the last rows of both generations
+        // uncommitted, so the third one has to read them again.
+        for (run, crash_after, commit_every) in [(0, Some(7), 4), (1, None, 4), (2, None, 1)] {
+            let factory = Arc::new(CommittingConsumerFactory {
+                durable_values: Arc::clone(&durable_values),
+                commit_every,
+                crash_after,
+            });
+
+            let (_tester, handle) = CDCLogReaderBuilder::new()
+                .log_source(source.clone())
+                .keyspace("ks")
+                .table_name("t")
+                .start_timestamp(start)
+                .end_timestamp(start + chrono::Duration::seconds(4))
+                .window_size(time::Duration::from_millis(WINDOW_SIZE))
+                .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+                .sleep_interval(time::Duration::from_millis(10))
+                .should_save_progress(true)
+                .should_load_progress(true)
+                .save_only_acknowledged_progress(true)
+                .pause_between_saves(time::Duration::from_millis(1))
+                .checkpoint_saver(checkpoint_saver.clone())
+                .consumer_factory(factory)
+                .build()
+                .await
+                .expect("Creating cdc log printer failed!");
+
+            let result = handle.await;
+            assert_eq!(result.is_err(), crash_after.is_some());
+
+            // The last value of the first generation wasn't committed in the second run,
+            // so the second generation isn't saved until the third one.
+            let expected_generation = if run < 2 {
+                &first_generation
+            } else {
+                &second_generation
+            };
+            assert_eq!(
+                checkpoint_saver.load_last_generation().await.unwrap(),
+                Some(expected_generation.clone())
+            );
+        }
+
+        let mut durable_values = durable_values.lock().unwrap().clone();
+        durable_values.sort_unstable();
+        durable_values.dedup();
+        assert_eq!(durable_values, (0..N).collect::<Vec<_>>());
+    }
+
+    // Creates a source with a single vnode containing two streams.
+    // Every 100 milliseconds, both streams receive a row with `pk1` equal to the index of the stream.
+    fn prepare_two_stream_source(
+        start: chrono::Duration,
+    ) -> (Arc<InMemoryLogSource>, [StreamID; 2]) {
+        let source = Arc::new(InMemoryLogSource::new());
+        let stream_ids = [StreamID::new(vec![1]), StreamID::new(vec![2])];
+
+        source.add_generation(
+            GenerationTimestamp { timestamp: start },
+            vec![stream_ids.to_vec()],
+        );
+        source.add_table(
+            "ks",
+            "t",
+            &[("pk1", ColumnType::Int), ("v", ColumnType::Int)],
+        );
+        for i in 0..10 {
+            for (pk, stream_id) in stream_ids.iter().enumerate() {
+                let row = InMemoryLogRow::new(OperationType::RowInsert)
+                    .value("pk1", CqlValue::Int(pk as i32))
+                    .value("v", CqlValue::Int(i));
+                source
+                    .push_change(
+                        "ks",
+                        "t",
+                        stream_id,
+                        start + chrono::Duration::milliseconds(100 * i as i64),
+                        vec![row],
+                    )
+                    .unwrap();
+            }
+        }
+
+        (source, stream_ids)
+    }
+
+    #[tokio::test]
+    async fn e2e_test_streams_resume_from_their_own_checkpoints() {
+        let start = now() - chrono::Duration::seconds(5);
+        let millis = chrono::Duration::milliseconds;
+        let (source, stream_ids) = prepare_two_stream_source(start);
+
+        // The first stream was read further than the second one.
+        let checkpoint_saver = Arc::new(InMemoryCheckpointSaver::default());
+        for (stream_id, position) in stream_ids.iter().zip([700, 200]) {
+            checkpoint_saver
+                .save_checkpoint(&Checkpoint {
+                    timestamp: (start + millis(position)).to_std().unwrap(),
+                    stream_id: stream_id.clone(),
+                    generation: GenerationTimestamp { timestamp: start },
+                })
+                .await
+                .unwrap();
+        }
+        checkpoint_saver
+            .save_new_generation(&GenerationTimestamp { timestamp: start })
+            .await
+            .unwrap();
+
+        let results = Arc::new(Mutex::new(HashMap::new()));
+        let factory = Arc::new(TestConsumerFactory::new(Arc::clone(&results)));
+
+        let (_tester, handle) = CDCLogReaderBuilder::new()
+            .log_source(source)
+            .keyspace("ks")
+            .table_name("t")
+            .start_timestamp(start)
+            .end_timestamp(start + chrono::Duration::seconds(2))
+            .window_size(time::Duration::from_millis(WINDOW_SIZE))
+            .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+            .sleep_interval(time::Duration::from_millis(10))
+            .should_load_progress(true)
+            .should_save_progress(true)
+            .checkpoint_saver(checkpoint_saver.clone())
+            .consumer_factory(factory)
+            .build()
+            .await
+            .expect("Creating cdc log printer failed!");
+
+        handle.await.unwrap();
+
+        let mut expected = HashMap::new();
+        for (pk, first_value) in [(0, 7), (1, 2)] {
+            expected.insert(
+                vec![PrimaryKeyValue::Int(pk)],
+                (first_value..10)
+                    .map(|v| Operation::new(OperationType::RowInsert, None, Some(v)))
+                    .collect::<VecDeque<_>>(),
+            );
+        }
+        assert_eq!(*results.lock().await, expected);
+    }
+
+    // Fails on the first row with `pk1` equal to `failing_pk`.
+    struct FailingConsumer {
+        failing_pk: i32,
+    }
+
+    #[async_trait]
+    impl Consumer for FailingConsumer {
+        async fn consume_cdc(&mut self, data: CDCRow<'_>) -> Result<()> {
+            if data.get_value("pk1").as_ref().unwrap().as_int() == Some(self.failing_pk) {
+                bail!("Simulated crash");
+            }
+            Ok(())
+        }
+    }
+
+    struct FailingConsumerFactory {
+        failing_pk: i32,
+    }
+
+    #[async_trait]
+    impl ConsumerFactory for FailingConsumerFactory {
+        async fn new_consumer(&self) -> Box<dyn Consumer> {
+            Box::new(FailingConsumer {
+                failing_pk: self.failing_pk,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn e2e_test_checkpoints_track_streams_separately() {
+        let start = now() - chrono::Duration::seconds(5);
+        let (source, stream_ids) = prepare_two_stream_source(start);
+        let checkpoint_saver = Arc::new(InMemoryCheckpointSaver::default());
+
+        let (_tester, handle) = CDCLogReaderBuilder::new()
+            .log_source(source)
+            .keyspace("ks")
+            .table_name("t")
+            .start_timestamp(start)
+            .end_timestamp(start + chrono::Duration::seconds(2))
+            .window_size(time::Duration::from_millis(WINDOW_SIZE))
+            .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+            .sleep_interval(time::Duration::from_millis(10))
+            .should_save_progress(true)
+            .checkpoint_saver(checkpoint_saver.clone())
+            .consumer_factory(Arc::new(FailingConsumerFactory { failing_pk: 1 }))
+            .build()
+            .await
+            .expect("Creating cdc log printer failed!");
+
+        assert!(handle.await.is_err());
+
+        // The first stream is read before the second one in the first window,
+        // so its rows from that window were consumed before the crash.
+        let first_stream_position = checkpoint_saver
+            .load_last_checkpoint(&stream_ids[0])
+            .await
+            .unwrap()
+            .unwrap();
+        let second_stream_position = checkpoint_saver
+            .load_last_checkpoint(&stream_ids[1])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            first_stream_position,
+            start + chrono::Duration::milliseconds(200)
+        );
+        assert_eq!(second_stream_position, start);
+    }
+
+    // Records the values and the highest number of rows consumed at the same time.
+    struct ConcurrencyRecordingConsumer {
+        read_values: Arc<Mutex<Vec<i32>>>,
+        active: Arc<std::sync::atomic::AtomicUsize>,
+        max_active: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Consumer for ConcurrencyRecordingConsumer {
+        async fn consume_cdc(&mut self, mut data: CDCRow<'_>) -> Result<()> {
+            use std::sync::atomic::Ordering;
+
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(time::Duration::from_millis(5)).await;
+            let value = data.take_value("v").unwrap().as_int().unwrap();
+            self.read_values.lock().await.push(value);
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct ConcurrencyRecordingConsumerFactory {
+        read_values: Arc<Mutex<Vec<i32>>>,
+        active: Arc<std::sync::atomic::AtomicUsize>,
+        max_active: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl ConsumerFactory for ConcurrencyRecordingConsumerFactory {
+        async fn new_consumer(&self) -> Box<dyn Consumer> {
+            Box::new(ConcurrencyRecordingConsumer {
+                read_values: Arc::clone(&self.read_values),
+                active: Arc::clone(&self.active),
+                max_active: Arc::clone(&self.max_active),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn e2e_test_limited_concurrent_readers() {
+        const STREAMS: u8 = 6;
+        let source = Arc::new(InMemoryLogSource::new());
+        let start = now() - chrono::Duration::seconds(5);
+        let stream_ids: Vec<StreamID> = (0..STREAMS).map(|id| StreamID::new(vec![id])).collect();
+
+        // Two vnodes, which are split into groups of two streams.
+        source.add_generation(
+            GenerationTimestamp { timestamp: start },
+            vec![stream_ids[..4].to_vec(), stream_ids[4..].to_vec()],
+        );
+        source.add_table(
+            "ks",
+            "t",
+            &[("pk", ColumnType::Int), ("v", ColumnType::Int)],
+        );
+        for (i, stream_id) in stream_ids.iter().enumerate() {
+            let row = InMemoryLogRow::new(OperationType::RowInsert)
+                .value("pk", CqlValue::Int(i as i32))
+                .value("v", CqlValue::Int(i as i32));
+            source
+                .push_change(
+                    "ks",
+                    "t",
+                    stream_id,
+                    start + chrono::Duration::milliseconds(100),
+                    vec![row],
+                )
+                .unwrap();
+        }
+
+        let read_values = Arc::new(Mutex::new(vec![]));
+        let max_active = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let factory = Arc::new(ConcurrencyRecordingConsumerFactory {
+            read_values: Arc::clone(&read_values),
+            active: Default::default(),
+            max_active: Arc::clone(&max_active),
+        });
+
+        let (reader, handle) = CDCLogReaderBuilder::new()
+            .log_source(source)
+            .keyspace("ks")
+            .table_name("t")
+            .start_timestamp(start)
+            .end_timestamp(start + chrono::Duration::seconds(1))
+            .window_size(time::Duration::from_millis(WINDOW_SIZE))
+            .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+            .sleep_interval(time::Duration::from_millis(10))
+            .max_concurrent_readers(1)
+            .stream_group_size(2)
+            .consumer_factory(factory)
+            .build()
+            .await
+            .expect("Creating cdc log printer failed!");
+
+        handle.await.unwrap();
+
+        let lags = reader.stream_group_lags();
+        assert_eq!(
+            lags.iter()
+                .map(|lag| lag.stream_ids.clone())
+                .collect::<Vec<_>>(),
+            stream_ids
+                .chunks(2)
+                .map(|ids| ids.to_vec())
+                .collect::<Vec<_>>()
+        );
+        for lag in lags {
+            assert!(lag.read_timestamp >= start + chrono::Duration::seconds(1));
+        }
+
+        let mut read_values = read_values.lock().await.clone();
+        read_values.sort_unstable();
+        assert_eq!(read_values, (0..STREAMS as i32).collect::<Vec<_>>());
+        assert_eq!(max_active.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn e2e_test_progress() {
+        let source = Arc::new(InMemoryLogSource::new());
+        let start = now() - chrono::Duration::seconds(5);
+        let end = start + chrono::Duration::seconds(1);
+        let stream_ids: Vec<StreamID> = (0..4u8).map(|id| StreamID::new(vec![id])).collect();
+        let generation = GenerationTimestamp { timestamp: start };
+
+        source.add_generation(
+            generation.clone(),
+            vec![stream_ids[..2].to_vec(), stream_ids[2..].to_vec()],
+        );
+        source.add_table(
+            "ks",
+            "t",
+            &[("pk", ColumnType::Int), ("v", ColumnType::Int)],
+        );
+
+        let factory = Arc::new(ConcurrencyRecordingConsumerFactory {
+            read_values: Default::default(),
+            active: Default::default(),
+            max_active: Default::default(),
+        });
+        let (reader, handle) = CDCLogReaderBuilder::new()
+            .log_source(source)
+            .keyspace("ks")
+            .table_name("t")
+            .start_timestamp(start)
+            .end_timestamp(end)
+            .window_size(time::Duration::from_millis(WINDOW_SIZE))
+            .safety_interval(time::Duration::from_millis(SAFETY_INTERVAL))
+            .sleep_interval(time::Duration::from_millis(10))
+            .consumer_factory(factory)
+            .build()
+            .await
+            .expect("Creating cdc log printer failed!");
+
+        handle.await.unwrap();
+
+        let progress = reader.progress();
+        assert_eq!(progress.generation, Some(generation));
+        assert_eq!(progress.end_timestamp, Some(end));
+        assert_eq!(
+            progress
+                .stream_groups
+                .iter()
+                .map(|group| group.stream_ids.clone())
+                .collect::<Vec<_>>(),
+            vec![stream_ids[..2].to_vec(), stream_ids[2..].to_vec()]
+        );
+        for group in &progress.stream_groups {
+            assert!(group.window_begin >= end);
+            assert!(group.window_end >= Some(group.window_begin));
+        }
+        assert_eq!(
+            progress.watermark,
+            progress
+                .stream_groups
+                .iter()
+                .map(|group| group.window_begin)
+                .min()
+        );
+        assert!(progress.is_caught_up(end));
+        assert!(!progress.is_caught_up(now() + chrono::Duration::seconds(60)));
+    }
 }
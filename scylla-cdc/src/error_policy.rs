@@ -0,0 +1,168 @@
+//! A module containing the policy of handling errors returned by the consumers.
+//!
+//! By default, an error returned by [`Consumer::consume_cdc`](crate::consumer::Consumer::consume_cdc)
+//! stops the [`CDCLogReader`](crate::log_reader::CDCLogReader).
+//! With [`ErrorPolicy`], consuming the failed row can be retried,
+//! and a row that still can't be consumed can be skipped or passed to a [`DeadLetterSink`].
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tracing::warn;
+
+use crate::consumer::CDCRow;
+
+/// Customizable trait receiving rows that the consumer failed to consume.
+#[async_trait]
+pub trait DeadLetterSink: Send + Sync {
+    /// Stores the row together with the error returned by the consumer.
+    /// If it fails, the reader stops.
+    async fn send_dead_letter(&self, row: CDCRow<'_>, error: &anyhow::Error) -> anyhow::Result<()>;
+}
+
+// What happens to a row that the consumer failed to consume, after all the retries.
+#[derive(Clone)]
+pub(crate) enum ErrorAction {
+    Halt,
+    Skip,
+    DeadLetter(Arc<dyn DeadLetterSink>),
+}
+
+/// Policy of handling errors returned by the consumer.
+/// # Example
+///
+/// ```
+/// # use scylla_cdc::error_policy::ErrorPolicy;
+/// # use std::time::Duration;
+/// // Try to consume a failed row 3 more times, then skip it.
+/// let policy = ErrorPolicy::skip().with_retries(3, Duration::from_millis(100));
+/// ```
+#[derive(Clone)]
+pub struct ErrorPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) retry_backoff: Duration,
+    pub(crate) action: ErrorAction,
+}
+
+impl ErrorPolicy {
+    /// Stops the reader when the consumer fails to consume a row.
+    pub fn halt() -> ErrorPolicy {
+        ErrorPolicy::new(ErrorAction::Halt)
+    }
+
+    /// Logs a warning and skips the row that the consumer failed to consume.
+    pub fn skip() -> ErrorPolicy {
+        ErrorPolicy::new(ErrorAction::Skip)
+    }
+
+    /// Passes the row that the consumer failed to consume to the given sink.
+    pub fn dead_letter(sink: Arc<dyn DeadLetterSink>) -> ErrorPolicy {
+        ErrorPolicy::new(ErrorAction::DeadLetter(sink))
+    }
+
+    /// Set the number of times a failed row is passed to the consumer again,
+    /// waiting for `backoff` before every retry.
+    /// The policy is applied only after the last retry fails.
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    fn new(action: ErrorAction) -> ErrorPolicy {
+        ErrorPolicy {
+            max_retries: 0,
+            retry_backoff: Duration::ZERO,
+            action,
+        }
+    }
+
+    // Whether a copy of every row has to be kept in case the consumer fails.
+    fn needs_row_copy(&self) -> bool {
+        self.max_retries > 0 || matches!(self.action, ErrorAction::DeadLetter(_))
+    }
+
+    // Passes `item` to the consumer and handles its errors according to the policy.
+    // `time` is the `cdc$time` of the item, used in the logs.
+    pub(crate) async fn run<T, C: PolicyConsumer<T>>(
+        &self,
+        consumer: &mut C,
+        time: uuid::Uuid,
+        mut item: T,
+    ) -> anyhow::Result<()> {
+        let mut retry = 0;
+
+        let (error, item) = loop {
+            let item_copy = self.needs_row_copy().then(|| C::duplicate(&item));
+            let error = match consumer.consume(item).await {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+            if retry == self.max_retries {
+                break (error, item_copy);
+            }
+
+            retry += 1;
+            warn!(
+                "Consuming the {} with cdc$time {} failed, retrying ({}/{}): {}",
+                C::KIND,
+                time,
+                retry,
+                self.max_retries,
+                error
+            );
+            tokio::time::sleep(self.retry_backoff).await;
+            // The copy is always kept when retries are allowed.
+            item = item_copy.unwrap();
+        };
+
+        match &self.action {
+            ErrorAction::Halt => Err(error),
+            ErrorAction::Skip => {
+                warn!(
+                    "Skipping the {} with cdc$time {}, which the consumer failed to consume: {}",
+                    C::KIND,
+                    time,
+                    error
+                );
+                Ok(())
+            }
+            // The copy is always kept for the dead letter sink.
+            ErrorAction::DeadLetter(sink) => {
+                consumer
+                    .send_dead_letter(sink.as_ref(), item.unwrap(), &error)
+                    .await
+            }
+        }
+    }
+}
+
+// Consumer of the items the error policy is applied to: single rows or whole changes.
+pub(crate) trait PolicyConsumer<T>: Send {
+    // Name of the items used in the logs.
+    const KIND: &'static str;
+
+    fn consume<'a>(&'a mut self, item: T) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        T: 'a;
+
+    // Copies the item, so it can be consumed again or passed to the dead letter sink.
+    fn duplicate(item: &T) -> T;
+
+    fn send_dead_letter<'a>(
+        &'a mut self,
+        sink: &'a dyn DeadLetterSink,
+        item: T,
+        error: &'a anyhow::Error,
+    ) -> BoxFuture<'a, anyhow::Result<()>>
+    where
+        T: 'a;
+}
+
+/// Create an [`ErrorPolicy`] stopping the reader on the first error, same as [`ErrorPolicy::halt()`]
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self::halt()
+    }
+}
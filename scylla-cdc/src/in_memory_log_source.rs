@@ -0,0 +1,655 @@
+//! A module containing an in-memory implementation of [`CDCLogSource`].
+//!
+//! [`InMemoryLogSource`] makes it possible to test consumers and the reading logic
+//! without a running Scylla cluster. Generations, streams and rows of the CDC log
+//! are added by the test code and then read by [`CDCLogReader`](crate::log_reader::CDCLogReader)
+//! the same way as the data stored in a real CDC log table.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures::stream;
+use futures::StreamExt;
+use scylla::frame::response::result::{ColumnSpec, ColumnType, CqlValue, Row, TableSpec};
+use uuid::v1::{Context, Timestamp};
+use uuid::Uuid;
+
+use crate::cdc_types::{GenerationTimestamp, RowPosition, StreamID};
+use crate::consumer::OperationType;
+use crate::log_source::{CDCLogRows, CDCLogSource};
+
+const STREAM_ID_NAME: &str = "cdc$stream_id";
+const TIME_NAME: &str = "cdc$time";
+const BATCH_SEQ_NO_NAME: &str = "cdc$batch_seq_no";
+const END_OF_BATCH_NAME: &str = "cdc$end_of_batch";
+const OPERATION_NAME: &str = "cdc$operation";
+const TTL_NAME: &str = "cdc$ttl";
+const IS_DELETED_PREFIX: &str = "cdc$deleted_";
+const ARE_ELEMENTS_DELETED_PREFIX: &str = "cdc$deleted_elements_";
+
+// Node identifier used in the generated timeuuids.
+const NODE_ID: [u8; 6] = [0, 0, 0, 0, 0, 1];
+
+/// A single row of a change added to [`InMemoryLogSource`].
+/// Metadata columns (`cdc$stream_id`, `cdc$time`, `cdc$batch_seq_no`, `cdc$end_of_batch`)
+/// are filled by [`InMemoryLogSource::push_change`].
+pub struct InMemoryLogRow {
+    operation: OperationType,
+    ttl: Option<i64>,
+    values: HashMap<String, CqlValue>,
+    deleted: HashSet<String>,
+    deleted_elements: HashMap<String, Vec<CqlValue>>,
+}
+
+impl InMemoryLogRow {
+    pub fn new(operation: OperationType) -> InMemoryLogRow {
+        InMemoryLogRow {
+            operation,
+            ttl: None,
+            values: HashMap::new(),
+            deleted: HashSet::new(),
+            deleted_elements: HashMap::new(),
+        }
+    }
+
+    /// Sets the value of the `cdc$ttl` column.
+    pub fn ttl(mut self, ttl: i64) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the value of the given column of the logged table.
+    pub fn value(mut self, column: &str, value: CqlValue) -> Self {
+        self.values.insert(column.to_string(), value);
+        self
+    }
+
+    /// Marks the value of the given column as deleted.
+    pub fn deleted(mut self, column: &str) -> Self {
+        self.deleted.insert(column.to_string());
+        self
+    }
+
+    /// Sets the elements deleted from the given collection column.
+    pub fn deleted_elements(mut self, column: &str, elements: Vec<CqlValue>) -> Self {
+        self.deleted_elements.insert(column.to_string(), elements);
+        self
+    }
+}
+
+struct InMemoryLogEntry {
+    position: RowPosition,
+    // Timestamp encoded in `cdc$time`.
+    time: chrono::Duration,
+    row: Row,
+}
+
+struct InMemoryLogTable {
+    column_specs: Vec<ColumnSpec>,
+    entries: Vec<InMemoryLogEntry>,
+}
+
+#[derive(Default)]
+struct InMemoryLogSourceState {
+    generations: BTreeMap<GenerationTimestamp, Vec<Vec<StreamID>>>,
+    // Maps `keyspace.table_name` to the contents of its CDC log.
+    tables: HashMap<String, InMemoryLogTable>,
+}
+
+/// Implementation of [`CDCLogSource`] trait keeping the generations
+/// and the CDC log in memory. Intended for testing purposes.
+///
+/// # Example
+///
+/// ```
+/// # use scylla::frame::response::result::{ColumnType, CqlValue};
+/// # use scylla_cdc::cdc_types::{GenerationTimestamp, StreamID};
+/// # use scylla_cdc::consumer::OperationType;
+/// # use scylla_cdc::in_memory_log_source::{InMemoryLogRow, InMemoryLogSource};
+/// # fn example() -> anyhow::Result<()> {
+/// let source = InMemoryLogSource::new();
+/// let stream_id = StreamID::new(vec![0x01]);
+/// source.add_generation(
+///     GenerationTimestamp {
+///         timestamp: chrono::Duration::zero(),
+///     },
+///     vec![vec![stream_id.clone()]],
+/// );
+/// source.add_table("ks", "t", &[("pk", ColumnType::Int), ("v", ColumnType::Text)]);
+/// source.push_change(
+///     "ks",
+///     "t",
+///     &stream_id,
+///     chrono::Duration::seconds(1),
+///     vec![InMemoryLogRow::new(OperationType::RowInsert)
+///         .value("pk", CqlValue::Int(1))
+///         .value("v", CqlValue::Text("foo".to_string()))],
+/// )?;
+/// # Ok(())
+/// # }
+/// # example().unwrap();
+/// ```
+pub struct InMemoryLogSource {
+    state: Mutex<InMemoryLogSourceState>,
+    context: Context,
+}
+
+impl InMemoryLogSource {
+    pub fn new() -> InMemoryLogSource {
+        InMemoryLogSource {
+            state: Mutex::new(Default::default()),
+            context: Context::new(0),
+        }
+    }
+
+    /// Adds a generation with the given streams, grouped by vnodes.
+    pub fn add_generation(&self, generation: GenerationTimestamp, stream_ids: Vec<Vec<StreamID>>) {
+        let mut state = self.state.lock().unwrap();
+        state.generations.insert(generation, stream_ids);
+    }
+
+    /// Creates an empty CDC log of the table `keyspace.table_name` with the given columns.
+    /// The metadata columns and the `cdc$deleted_` columns are added automatically,
+    /// as well as the `cdc$deleted_elements_` columns for the collection columns.
+    pub fn add_table(&self, keyspace: &str, table_name: &str, columns: &[(&str, ColumnType)]) {
+        let log_table_name = format!("{}_scylla_cdc_log", table_name);
+        let make_spec = |name: &str, typ: ColumnType| ColumnSpec {
+            table_spec: TableSpec {
+                ks_name: keyspace.to_string(),
+                table_name: log_table_name.clone(),
+            },
+            name: name.to_string(),
+            typ,
+        };
+
+        let mut column_specs = vec![
+            make_spec(STREAM_ID_NAME, ColumnType::Blob),
+            make_spec(TIME_NAME, ColumnType::Timeuuid),
+            make_spec(BATCH_SEQ_NO_NAME, ColumnType::Int),
+            make_spec(END_OF_BATCH_NAME, ColumnType::Boolean),
+            make_spec(OPERATION_NAME, ColumnType::TinyInt),
+            make_spec(TTL_NAME, ColumnType::BigInt),
+        ];
+        for (name, typ) in columns {
+            column_specs.push(make_spec(name, typ.clone()));
+            column_specs.push(make_spec(
+                &format!("{}{}", IS_DELETED_PREFIX, name),
+                ColumnType::Boolean,
+            ));
+            if let Some(element_type) = deleted_elements_type(typ) {
+                column_specs.push(make_spec(
+                    &format!("{}{}", ARE_ELEMENTS_DELETED_PREFIX, name),
+                    ColumnType::Set(Box::new(element_type)),
+                ));
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.tables.insert(
+            format!("{}.{}", keyspace, table_name),
+            InMemoryLogTable {
+                column_specs,
+                entries: vec![],
+            },
+        );
+    }
+
+    /// Appends rows of one change to the CDC log of the table `keyspace.table_name`.
+    /// All the rows get the same `cdc$time`, generated from the given timestamp,
+    /// consecutive `cdc$batch_seq_no` values and `cdc$end_of_batch` set in the last one.
+    /// Returns the generated `cdc$time`.
+    pub fn push_change(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+        stream_id: &StreamID,
+        timestamp: chrono::Duration,
+        rows: Vec<InMemoryLogRow>,
+    ) -> anyhow::Result<Uuid> {
+        let time = self.new_timeuuid(timestamp)?;
+        let mut state = self.state.lock().unwrap();
+        let keyspace_table_name = format!("{}.{}", keyspace, table_name);
+        let table = state
+            .tables
+            .get_mut(&keyspace_table_name)
+            .ok_or_else(|| anyhow::anyhow!("table {} does not exist", keyspace_table_name))?;
+
+        let rows_count = rows.len();
+        let mut entries = Vec::with_capacity(rows_count);
+        for (i, mut log_row) in rows.into_iter().enumerate() {
+            let batch_seq_no = i as i32;
+            let mut columns = Vec::with_capacity(table.column_specs.len());
+            for spec in table.column_specs.iter() {
+                let name = spec.name.as_str();
+                let column = match name {
+                    STREAM_ID_NAME => Some(CqlValue::Blob(stream_id.id.clone())),
+                    TIME_NAME => Some(CqlValue::Timeuuid(time)),
+                    BATCH_SEQ_NO_NAME => Some(CqlValue::Int(batch_seq_no)),
+                    END_OF_BATCH_NAME => (i + 1 == rows_count).then_some(CqlValue::Boolean(true)),
+                    OPERATION_NAME => Some(CqlValue::TinyInt(log_row.operation.clone() as i8)),
+                    TTL_NAME => log_row.ttl.map(CqlValue::BigInt),
+                    _ => {
+                        if let Some(stripped) = name.strip_prefix(ARE_ELEMENTS_DELETED_PREFIX) {
+                            log_row.deleted_elements.remove(stripped).map(CqlValue::Set)
+                        } else if let Some(stripped) = name.strip_prefix(IS_DELETED_PREFIX) {
+                            log_row
+                                .deleted
+                                .remove(stripped)
+                                .then_some(CqlValue::Boolean(true))
+                        } else {
+                            log_row.values.remove(name)
+                        }
+                    }
+                };
+                columns.push(column);
+            }
+
+            let unknown_column = log_row
+                .values
+                .keys()
+                .chain(log_row.deleted.iter())
+                .chain(log_row.deleted_elements.keys())
+                .next();
+            if let Some(column) = unknown_column {
+                return Err(anyhow::anyhow!(
+                    "column {} does not exist in the CDC log of {}",
+                    column,
+                    keyspace_table_name
+                ));
+            }
+
+            entries.push(InMemoryLogEntry {
+                position: RowPosition {
+                    stream_id: stream_id.clone(),
+                    time,
+                    batch_seq_no,
+                },
+                time: timestamp,
+                row: Row { columns },
+            });
+        }
+        table.entries.extend(entries);
+
+        Ok(time)
+    }
+
+    fn new_timeuuid(&self, timestamp: chrono::Duration) -> anyhow::Result<Uuid> {
+        let secs = timestamp.num_seconds();
+        let nanos = (timestamp - chrono::Duration::seconds(secs))
+            .num_nanoseconds()
+            .unwrap_or_default();
+        if secs < 0 || nanos < 0 {
+            return Err(anyhow::anyhow!(
+                "timestamp of a change cannot be earlier than the Unix epoch"
+            ));
+        }
+
+        let timestamp = Timestamp::from_unix(&self.context, secs as u64, nanos as u32);
+        Ok(Uuid::new_v1(timestamp, &NODE_ID))
+    }
+}
+
+impl Default for InMemoryLogSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns the type of elements of the `cdc$deleted_elements_` column
+// or `None` if the column of the given type does not have one.
+fn deleted_elements_type(typ: &ColumnType) -> Option<ColumnType> {
+    match typ {
+        ColumnType::Set(element_type) => Some(element_type.as_ref().clone()),
+        ColumnType::Map(key_type, _) => Some(key_type.as_ref().clone()),
+        ColumnType::List(_) => Some(ColumnType::Timeuuid),
+        ColumnType::UserDefinedType { .. } => Some(ColumnType::SmallInt),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl CDCLogSource for InMemoryLogSource {
+    async fn fetch_all_generations(&self) -> anyhow::Result<Vec<GenerationTimestamp>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.generations.keys().rev().cloned().collect())
+    }
+
+    async fn fetch_generation_by_timestamp(
+        &self,
+        time: &chrono::Duration,
+    ) -> anyhow::Result<Option<GenerationTimestamp>> {
+        let state = self.state.lock().unwrap();
+        let generation = state
+            .generations
+            .keys()
+            .rev()
+            .find(|generation| generation.timestamp <= *time)
+            .cloned();
+        Ok(generation)
+    }
+
+    async fn fetch_next_generation(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Option<GenerationTimestamp>> {
+        let state = self.state.lock().unwrap();
+        let next_generation = state
+            .generations
+            .keys()
+            .find(|next| next.timestamp > generation.timestamp)
+            .cloned();
+        Ok(next_generation)
+    }
+
+    async fn fetch_stream_ids(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Vec<Vec<StreamID>>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .generations
+            .get(generation)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn fetch_cdc_table_names(&self, keyspace: &str) -> anyhow::Result<Vec<String>> {
+        let state = self.state.lock().unwrap();
+        let prefix = format!("{}.", keyspace);
+        let mut table_names: Vec<String> = state
+            .tables
+            .keys()
+            .filter_map(|name| name.strip_prefix(&prefix))
+            .map(|name| name.to_string())
+            .collect();
+        table_names.sort();
+        Ok(table_names)
+    }
+
+    async fn fetch_rows(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+        stream_ids: &[StreamID],
+        window_begin: chrono::Duration,
+        window_end: chrono::Duration,
+    ) -> anyhow::Result<CDCLogRows> {
+        let state = self.state.lock().unwrap();
+        let keyspace_table_name = format!("{}.{}", keyspace, table_name);
+        let table = state
+            .tables
+            .get(&keyspace_table_name)
+            .ok_or_else(|| anyhow::anyhow!("table {} does not exist", keyspace_table_name))?;
+
+        let mut entries: Vec<&InMemoryLogEntry> = table
+            .entries
+            .iter()
+            .filter(|entry| {
+                stream_ids.contains(&entry.position.stream_id)
+                    && window_begin <= entry.time
+                    && entry.time < window_end
+            })
+            .collect();
+        entries.sort_by(|a, b| a.position.cmp(&b.position));
+        let rows: Vec<anyhow::Result<Row>> = entries
+            .into_iter()
+            .map(|entry| {
+                Ok(Row {
+                    columns: entry.row.columns.clone(),
+                })
+            })
+            .collect();
+
+        Ok(CDCLogRows {
+            column_specs: table.column_specs.clone(),
+            rows: stream::iter(rows).boxed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::consumer::{CDCRow, CDCRowSchema};
+
+    const TEST_KEYSPACE: &str = "ks";
+    const TEST_TABLE: &str = "t";
+
+    fn make_generation(millis: i64) -> GenerationTimestamp {
+        GenerationTimestamp {
+            timestamp: chrono::Duration::milliseconds(millis),
+        }
+    }
+
+    fn make_source() -> InMemoryLogSource {
+        let source = InMemoryLogSource::new();
+        source.add_generation(
+            make_generation(1000),
+            vec![vec![StreamID::new(vec![1]), StreamID::new(vec![2])]],
+        );
+        source.add_generation(make_generation(3000), vec![vec![StreamID::new(vec![3])]]);
+        source.add_table(
+            TEST_KEYSPACE,
+            TEST_TABLE,
+            &[
+                ("pk", ColumnType::Int),
+                ("v", ColumnType::Int),
+                ("s", ColumnType::Set(Box::new(ColumnType::Int))),
+            ],
+        );
+        source
+    }
+
+    async fn fetch_values(
+        source: &InMemoryLogSource,
+        stream_ids: &[StreamID],
+        window_begin: i64,
+        window_end: i64,
+    ) -> Vec<(i32, i32, bool)> {
+        let rows = source
+            .fetch_rows(
+                TEST_KEYSPACE,
+                TEST_TABLE,
+                stream_ids,
+                chrono::Duration::milliseconds(window_begin),
+                chrono::Duration::milliseconds(window_end),
+            )
+            .await
+            .unwrap();
+        let schema = CDCRowSchema::new(&rows.column_specs);
+
+        rows.rows
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| {
+                let row = CDCRow::from_row(row, &schema);
+                (
+                    row.batch_seq_no,
+                    row.get_value("v").as_ref().unwrap().as_int().unwrap(),
+                    row.end_of_batch,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_generations() {
+        let source = make_source();
+
+        assert_eq!(
+            source.fetch_all_generations().await.unwrap(),
+            vec![make_generation(3000), make_generation(1000)]
+        );
+        assert_eq!(
+            source
+                .fetch_generation_by_timestamp(&chrono::Duration::milliseconds(999))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            source
+                .fetch_generation_by_timestamp(&chrono::Duration::milliseconds(2999))
+                .await
+                .unwrap(),
+            Some(make_generation(1000))
+        );
+        assert_eq!(
+            source
+                .fetch_next_generation(&make_generation(1000))
+                .await
+                .unwrap(),
+            Some(make_generation(3000))
+        );
+        assert_eq!(
+            source
+                .fetch_next_generation(&make_generation(3000))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            source
+                .fetch_stream_ids(&make_generation(3000))
+                .await
+                .unwrap(),
+            vec![vec![StreamID::new(vec![3])]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cdc_table_names() {
+        let source = make_source();
+        source.add_table(TEST_KEYSPACE, "a", &[("pk", ColumnType::Int)]);
+        source.add_table("other_ks", "b", &[("pk", ColumnType::Int)]);
+
+        assert_eq!(
+            source.fetch_cdc_table_names(TEST_KEYSPACE).await.unwrap(),
+            vec!["a".to_string(), TEST_TABLE.to_string()]
+        );
+        assert!(source
+            .fetch_cdc_table_names("missing_ks")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_rows() {
+        let source = make_source();
+        let stream_1 = StreamID::new(vec![1]);
+        let stream_2 = StreamID::new(vec![2]);
+        let make_row = |v: i32| {
+            InMemoryLogRow::new(OperationType::RowUpdate)
+                .value("pk", CqlValue::Int(0))
+                .value("v", CqlValue::Int(v))
+        };
+
+        for (stream_id, timestamp, values) in [
+            (&stream_2, 1500, vec![1]),
+            (&stream_1, 1200, vec![2, 3]),
+            (&stream_1, 1100, vec![4]),
+            (&stream_1, 2000, vec![5]),
+        ] {
+            source
+                .push_change(
+                    TEST_KEYSPACE,
+                    TEST_TABLE,
+                    stream_id,
+                    chrono::Duration::milliseconds(timestamp),
+                    values.into_iter().map(make_row).collect(),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            fetch_values(&source, &[stream_1.clone(), stream_2], 1000, 2000).await,
+            vec![(0, 4, true), (0, 2, false), (1, 3, true), (0, 1, true)]
+        );
+        assert_eq!(
+            fetch_values(&source, &[stream_1], 1200, 3000).await,
+            vec![(0, 2, false), (1, 3, true), (0, 5, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_change_columns() {
+        let source = make_source();
+        let stream_id = StreamID::new(vec![1]);
+        let timestamp = chrono::Duration::milliseconds(1500);
+
+        let time = source
+            .push_change(
+                TEST_KEYSPACE,
+                TEST_TABLE,
+                &stream_id,
+                timestamp,
+                vec![InMemoryLogRow::new(OperationType::RowUpdate)
+                    .ttl(100)
+                    .value("pk", CqlValue::Int(0))
+                    .deleted("v")
+                    .deleted_elements("s", vec![CqlValue::Int(7)])],
+            )
+            .unwrap();
+        assert_eq!(time.get_timestamp().unwrap().to_unix(), (1, 500_000_000));
+
+        let rows = source
+            .fetch_rows(
+                TEST_KEYSPACE,
+                TEST_TABLE,
+                std::slice::from_ref(&stream_id),
+                timestamp,
+                timestamp + chrono::Duration::milliseconds(1),
+            )
+            .await
+            .unwrap();
+        let schema = CDCRowSchema::new(&rows.column_specs);
+        let mut rows = rows.rows.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(rows.len(), 1);
+        let mut row = CDCRow::from_row(rows.remove(0), &schema);
+
+        assert_eq!(row.stream_id, stream_id);
+        assert_eq!(row.time, time);
+        assert_eq!(row.operation, OperationType::RowUpdate);
+        assert_eq!(row.ttl, Some(100));
+        assert_eq!(row.take_value("pk"), Some(CqlValue::Int(0)));
+        assert_eq!(row.take_value("v"), None);
+        assert!(row.is_value_deleted("v"));
+        assert!(!row.is_value_deleted("pk"));
+        assert_eq!(row.take_deleted_elements("s"), vec![CqlValue::Int(7)]);
+    }
+
+    #[test]
+    fn test_push_change_errors() {
+        let source = make_source();
+        let stream_id = StreamID::new(vec![1]);
+        let timestamp = chrono::Duration::milliseconds(1500);
+
+        assert!(source
+            .push_change(TEST_KEYSPACE, "missing", &stream_id, timestamp, vec![])
+            .is_err());
+        assert!(source
+            .push_change(
+                TEST_KEYSPACE,
+                TEST_TABLE,
+                &stream_id,
+                timestamp,
+                vec![InMemoryLogRow::new(OperationType::RowInsert)
+                    .value("missing", CqlValue::Int(0))],
+            )
+            .is_err());
+        assert!(source
+            .push_change(
+                TEST_KEYSPACE,
+                TEST_TABLE,
+                &stream_id,
+                chrono::Duration::milliseconds(-1),
+                vec![],
+            )
+            .is_err());
+    }
+}
@@ -64,10 +64,29 @@
 //! }
 //! ```
 
+// Allows to use the derive macros, which refer to `scylla_cdc`, inside this crate.
+extern crate self as scylla_cdc;
+
 pub mod cdc_types;
 pub mod checkpoints;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod consumer;
 mod e2e_tests;
+pub mod error_policy;
+pub mod in_memory_log_source;
 pub mod log_reader;
+pub mod log_source;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod retry_policy;
 mod stream_generations;
 mod stream_reader;
+
+// Items used by the code generated by the derive macros.
+#[doc(hidden)]
+pub mod _macro_internal {
+    pub use crate::consumer::{CDCRow, FromCDCRow, FromCDCRowError};
+    pub use scylla::cql_to_rust::FromCqlVal;
+    pub use scylla::frame::response::result::CqlValue;
+}
@@ -13,7 +13,7 @@
 //! please refer to the [Scylla CDC documentation](https://docs.scylladb.com/using-scylla/cdc/cdc-stream-generations/).
 
 use std::cmp::max;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time;
 use std::time::SystemTime;
 
@@ -22,12 +22,16 @@ use futures::future::RemoteHandle;
 use futures::stream::{FusedStream, FuturesUnordered, StreamExt};
 use futures::FutureExt;
 use scylla::Session;
+use tokio::sync::Semaphore;
 use tracing::warn;
 
-use crate::cdc_types::GenerationTimestamp;
+use crate::cdc_types::{GenerationTimestamp, StreamID};
 use crate::checkpoints::CDCCheckpointSaver;
-use crate::consumer::ConsumerFactory;
-use crate::stream_generations::GenerationFetcher;
+use crate::consumer::{ChangeConsumerFactory, ChangeConsumerFactoryAdapter, ConsumerFactory};
+use crate::error_policy::ErrorPolicy;
+use crate::log_source::{CDCLogSource, ScyllaLogSource};
+use crate::retry_policy::RetryPolicy;
+use crate::stream_generations::fetch_generations_continuously;
 use crate::stream_reader::{CDCReaderConfig, StreamReader};
 
 const SECOND_IN_MILLIS: i64 = 1_000;
@@ -35,6 +39,7 @@ const DEFAULT_SLEEP_INTERVAL: i64 = SECOND_IN_MILLIS * 10;
 const DEFAULT_WINDOW_SIZE: i64 = SECOND_IN_MILLIS * 60;
 const DEFAULT_SAFETY_INTERVAL: i64 = SECOND_IN_MILLIS * 30;
 const DEFAULT_PAUSE: u64 = 10;
+const DEFAULT_MAX_UNACKNOWLEDGED_WINDOWS: usize = 1000;
 
 /// To create a new CDCLogReader instance please see documentation for [`CDCLogReaderBuilder`]
 pub struct CDCLogReader {
@@ -42,11 +47,114 @@ pub struct CDCLogReader {
     // Usage of the "watch" channel will make it possible to change the the timestamp later,
     // for example if somebody loses patience and wants to stop now not later
     end_timestamp: tokio::sync::watch::Sender<chrono::Duration>,
+    // Readers of the generation that is currently read, shared with the worker.
+    readers: Arc<Mutex<Vec<Arc<StreamReader>>>>,
+    // Generation that is currently read, shared with the worker.
+    generation: Arc<Mutex<Option<GenerationTimestamp>>>,
+}
+
+/// Lag of a group of streams read together by one reader.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamGroupLag {
+    pub stream_ids: Vec<StreamID>,
+    /// All the rows of the streams with earlier `cdc$time` were read.
+    pub read_timestamp: chrono::Duration,
+    /// Time elapsed since `read_timestamp`.
+    pub lag: chrono::Duration,
+}
+
+/// Progress of reading the CDC log, returned by [`CDCLogReader::progress`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReaderProgress {
+    /// Generation that is currently read, `None` until the first generation is fetched.
+    pub generation: Option<GenerationTimestamp>,
+    /// Progress of every group of streams of the generation.
+    pub stream_groups: Vec<StreamGroupProgress>,
+    /// All the rows of all the streams of the generation with earlier `cdc$time` were read.
+    /// The minimum of the window begins of the stream groups, `None` if there are no stream groups.
+    pub watermark: Option<chrono::Duration>,
+    /// Timestamp at which the reader stops, `None` if it reads until stopped.
+    pub end_timestamp: Option<chrono::Duration>,
+}
+
+/// Progress of a group of streams read together by one reader.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamGroupProgress {
+    pub stream_ids: Vec<StreamID>,
+    /// Begin of the window that is currently read.
+    /// All the rows of the streams with earlier `cdc$time` were read.
+    pub window_begin: chrono::Duration,
+    /// End of the last window fetched from the CDC log, `None` if no window was fetched yet.
+    /// With prefetching, the rows of several windows may be fetched ahead of the consumer.
+    pub window_end: Option<chrono::Duration>,
+}
+
+impl ReaderProgress {
+    /// Checks if all the rows with `cdc$time` before `timestamp` were read,
+    /// e.g. to find out if a replay of the log caught up with the time it started.
+    pub fn is_caught_up(&self, timestamp: chrono::Duration) -> bool {
+        self.watermark
+            .is_some_and(|watermark| watermark >= timestamp)
+    }
 }
 
 impl CDCLogReader {
-    fn new(end_timestamp: tokio::sync::watch::Sender<chrono::Duration>) -> Self {
-        CDCLogReader { end_timestamp }
+    fn new(
+        end_timestamp: tokio::sync::watch::Sender<chrono::Duration>,
+        readers: Arc<Mutex<Vec<Arc<StreamReader>>>>,
+        generation: Arc<Mutex<Option<GenerationTimestamp>>>,
+    ) -> Self {
+        CDCLogReader {
+            end_timestamp,
+            readers,
+            generation,
+        }
+    }
+
+    /// Returns the generation that is currently read and the windows read by its stream groups.
+    pub fn progress(&self) -> ReaderProgress {
+        let stream_groups: Vec<StreamGroupProgress> = self
+            .readers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|reader| StreamGroupProgress {
+                stream_ids: reader.stream_ids().to_vec(),
+                window_begin: reader.read_timestamp(),
+                window_end: reader.window_end(),
+            })
+            .collect();
+        let watermark = stream_groups.iter().map(|group| group.window_begin).min();
+        let end_timestamp = Some(*self.end_timestamp.borrow())
+            .filter(|timestamp| *timestamp != chrono::Duration::max_value());
+
+        ReaderProgress {
+            generation: self.generation.lock().unwrap().clone(),
+            stream_groups,
+            watermark,
+            end_timestamp,
+        }
+    }
+
+    /// Returns the lag of every group of streams of the generation that is currently read.
+    pub fn stream_group_lags(&self) -> Vec<StreamGroupLag> {
+        let now = chrono::Duration::milliseconds(chrono::Local::now().timestamp_millis());
+        self.readers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|reader| {
+                let read_timestamp = reader.read_timestamp();
+                StreamGroupLag {
+                    stream_ids: reader.stream_ids().to_vec(),
+                    read_timestamp,
+                    lag: max(now - read_timestamp, chrono::Duration::zero()),
+                }
+            })
+            .collect()
     }
 
     // Tell the worker to set the end timestamp and then stop
@@ -61,11 +169,13 @@ impl CDCLogReader {
 }
 
 struct CDCReaderWorker {
-    session: Arc<Session>,
+    source: Arc<dyn CDCLogSource>,
     keyspace: String,
-    table_name: String,
+    table_names: Vec<String>,
     end_timestamp: chrono::Duration,
-    readers: Vec<Arc<StreamReader>>,
+    readers: Arc<Mutex<Vec<Arc<StreamReader>>>>,
+    generation: Arc<Mutex<Option<GenerationTimestamp>>>,
+    stream_group_size: Option<usize>,
     end_timestamp_receiver: tokio::sync::watch::Receiver<chrono::Duration>,
     consumer_factory: Arc<dyn ConsumerFactory>,
     config: CDCReaderConfig,
@@ -73,17 +183,21 @@ struct CDCReaderWorker {
 
 impl CDCReaderWorker {
     pub async fn run(&mut self) -> anyhow::Result<()> {
-        let fetcher = Arc::new(GenerationFetcher::new(&self.session));
-        let (mut generation_receiver, _future_handle) = fetcher
-            .clone()
-            .fetch_generations_continuously(self.config.lower_timestamp, self.config.sleep_interval)
-            .await?;
+        let (mut generation_receiver, _future_handle) = fetch_generations_continuously(
+            Arc::clone(&self.source),
+            self.config.lower_timestamp,
+            self.config.sleep_interval,
+        )
+        .await?;
 
         let mut stream_reader_tasks = FuturesUnordered::new();
 
         let mut next_generation: Option<GenerationTimestamp> = None;
         let mut current_generation: Option<GenerationTimestamp> = None;
         let mut err: Option<anyhow::Error> = None;
+        // Set once a generation finishes with rows not acknowledged by the consumers.
+        // Its rows are read again after a restart only if it stays the saved generation.
+        let mut keep_saved_generation = false;
 
         let mut reader_config = self.config.clone();
 
@@ -124,7 +238,13 @@ impl CDCReaderWorker {
                         return Ok(());
                     }
 
-                    if self.config.should_save_progress {
+                    keep_saved_generation |= self
+                        .readers
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .any(|reader| reader.has_unacknowledged_rows());
+                    if self.config.should_save_progress && !keep_saved_generation {
                         self.config
                             .checkpoint_saver
                             .as_ref()
@@ -136,37 +256,52 @@ impl CDCReaderWorker {
                     reader_config.lower_timestamp =
                         max(self.config.lower_timestamp, generation.timestamp);
 
-                    self.readers = fetcher
-                        .fetch_stream_ids(&generation)
-                        .await?
+                    let mut stream_id_groups = self.source.fetch_stream_ids(&generation).await?;
+                    if let Some(group_size) = self.stream_group_size {
+                        stream_id_groups = regroup_streams(stream_id_groups, group_size);
+                    }
+                    let readers: Vec<Arc<StreamReader>> = stream_id_groups
                         .into_iter()
                         .map(|stream_ids| {
                             Arc::new(StreamReader::new(
-                                &self.session,
+                                &self.source,
                                 stream_ids,
                                 reader_config.clone(),
                             ))
                         })
                         .collect();
+                    #[cfg(feature = "metrics")]
+                    {
+                        crate::metrics::generation_switched(&self.keyspace);
+                        for reader in self.readers.lock().unwrap().iter() {
+                            crate::metrics::remove_stream_group_lag(
+                                &self.keyspace,
+                                &self.table_names,
+                                reader.stream_ids(),
+                            );
+                        }
+                    }
+                    *self.readers.lock().unwrap() = readers.clone();
+                    *self.generation.lock().unwrap() = Some(generation.clone());
 
                     self.set_upper_timestamp(self.end_timestamp).await;
 
-                    stream_reader_tasks = self
-                        .readers
+                    stream_reader_tasks = readers
                         .iter()
                         .map(|reader| {
                             let reader = Arc::clone(reader);
                             let keyspace = self.keyspace.clone();
-                            let table_name = self.table_name.clone();
+                            let table_names = self.table_names.clone();
                             let factory = Arc::clone(&self.consumer_factory);
                             tokio::spawn(async move {
                                 let consumer = factory.new_consumer().await;
-                                reader.fetch_cdc(keyspace, table_name, consumer).await
+                                reader.fetch_cdc(keyspace, table_names, consumer).await
                             })
                         })
                         .collect();
                 } else if let Some(current) = current_generation.take() {
-                    if let Ok(Some(generation)) = fetcher.fetch_next_generation(&current).await {
+                    if let Ok(Some(generation)) = self.source.fetch_next_generation(&current).await
+                    {
                         if generation.timestamp <= self.end_timestamp {
                             // Fetched next generation with lower or equal timestamp than end_timestamp
                             next_generation = Some(generation.clone());
@@ -190,7 +325,8 @@ impl CDCReaderWorker {
     }
 
     async fn set_upper_timestamp(&self, new_upper_timestamp: chrono::Duration) {
-        for reader in self.readers.iter() {
+        let readers = self.readers.lock().unwrap().clone();
+        for reader in readers.iter() {
             reader.set_upper_timestamp(new_upper_timestamp).await;
         }
     }
@@ -201,6 +337,16 @@ impl CDCReaderWorker {
     }
 }
 
+// Splits the streams into groups of the given size.
+// Streams of the consecutive vnodes are merged, while the bigger vnodes are split.
+fn regroup_streams(stream_id_groups: Vec<Vec<StreamID>>, group_size: usize) -> Vec<Vec<StreamID>> {
+    let stream_ids: Vec<StreamID> = stream_id_groups.into_iter().flatten().collect();
+    stream_ids
+        .chunks(group_size)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
 /// CDCLogReaderBuilder is used to create new [`CDCLogReader`] instances.
 /// # Example
 ///
@@ -244,18 +390,28 @@ impl CDCReaderWorker {
 /// ```
 pub struct CDCLogReaderBuilder {
     session: Option<Arc<Session>>,
+    log_source: Option<Arc<dyn CDCLogSource>>,
     keyspace: Option<String>,
-    table_name: Option<String>,
+    table_names: Vec<String>,
+    all_tables: bool,
     start_timestamp: chrono::Duration,
     end_timestamp: chrono::Duration,
     window_size: time::Duration,
     safety_interval: time::Duration,
     sleep_interval: time::Duration,
     consumer_factory: Option<Arc<dyn ConsumerFactory>>,
+    change_consumer_factory: Option<Arc<dyn ChangeConsumerFactory>>,
     should_load_progress: bool,
     should_save_progress: bool,
     checkpoint_saver: Option<Arc<dyn CDCCheckpointSaver>>,
     pause_between_saves: time::Duration,
+    save_only_acknowledged_progress: bool,
+    max_unacknowledged_windows: usize,
+    max_concurrent_readers: Option<usize>,
+    stream_group_size: Option<usize>,
+    prefetch_depth: Option<usize>,
+    retry_policy: Option<RetryPolicy>,
+    error_policy: ErrorPolicy,
 }
 
 impl CDCLogReaderBuilder {
@@ -270,11 +426,20 @@ impl CDCLogReaderBuilder {
     /// * should_load_progress: false
     /// * should_save_progress: false,
     /// * pause_between_saves: 10 seconds
+    /// * save_only_acknowledged_progress: false
+    /// * max_unacknowledged_windows: 1000
+    /// * max_concurrent_readers: unlimited
+    /// * stream_group_size: streams are grouped by vnodes
+    /// * prefetch_depth: rows are fetched only when the consumer asks for them
+    /// * retry_policy: failed reads are not retried
+    /// * error_policy: halt on the first error returned by a consumer
     pub fn new() -> CDCLogReaderBuilder {
         let end_timestamp = chrono::Duration::max_value();
         let session = None;
+        let log_source = None;
         let keyspace = None;
-        let table_name = None;
+        let table_names = vec![];
+        let all_tables = false;
         let start_timestamp = chrono::Duration::from_std(
             SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -285,29 +450,48 @@ impl CDCLogReaderBuilder {
         let safety_interval = time::Duration::from_millis(DEFAULT_SAFETY_INTERVAL as u64);
         let sleep_interval = time::Duration::from_millis(DEFAULT_SLEEP_INTERVAL as u64);
         let consumer_factory = None;
+        let change_consumer_factory = None;
         let should_load_progress = false;
         let should_save_progress = false;
         let checkpoint_saver = None;
         let pause_between_saves = time::Duration::from_secs(DEFAULT_PAUSE);
+        let save_only_acknowledged_progress = false;
+        let max_unacknowledged_windows = DEFAULT_MAX_UNACKNOWLEDGED_WINDOWS;
+        let max_concurrent_readers = None;
+        let stream_group_size = None;
+        let prefetch_depth = None;
+        let retry_policy = None;
+        let error_policy = ErrorPolicy::halt();
         CDCLogReaderBuilder {
             session,
+            log_source,
             keyspace,
-            table_name,
+            table_names,
+            all_tables,
             start_timestamp,
             end_timestamp,
             window_size,
             safety_interval,
             sleep_interval,
             consumer_factory,
+            change_consumer_factory,
             should_load_progress,
             should_save_progress,
             checkpoint_saver,
             pause_between_saves,
+            save_only_acknowledged_progress,
+            max_unacknowledged_windows,
+            max_concurrent_readers,
+            stream_group_size,
+            prefetch_depth,
+            retry_policy,
+            error_policy,
         }
     }
 
     /// Set session to enable [`CDCLogReader`] perform queries.
-    /// This is a required field for [`CDCLogReaderBuilder::build()`]
+    /// This is a required field for [`CDCLogReaderBuilder::build()`],
+    /// unless the log source is set with [`CDCLogReaderBuilder::log_source()`].
     /// In case it is not set [`CDCLogReaderBuilder::build()`] will return error message:
     /// `failed to create the cdc reader: missing session`
     pub fn session(mut self, session: Arc<Session>) -> Self {
@@ -315,6 +499,13 @@ impl CDCLogReaderBuilder {
         self
     }
 
+    /// Set instance of [`CDCLogSource`] trait object to read generations and rows of the CDC log from.
+    /// If it is not set, [`ScyllaLogSource`] using the session is created.
+    pub fn log_source(mut self, log_source: Arc<dyn CDCLogSource>) -> Self {
+        self.log_source = Some(log_source);
+        self
+    }
+
     /// Set keyspace of the CDC log table that [`CDCLogReader`] instance will read data from.
     /// This is a required field for [`CDCLogReaderBuilder::build()`]
     /// In case it is not set [`CDCLogReaderBuilder::build()`] will return error message:
@@ -325,11 +516,29 @@ impl CDCLogReaderBuilder {
     }
 
     /// Set table name of the CDC log table that [`CDCLogReader`] instance will read data from.
-    /// This is a required field for [`CDCLogReaderBuilder::build()`]
+    /// This is a required field for [`CDCLogReaderBuilder::build()`],
+    /// unless the tables are set with [`CDCLogReaderBuilder::table_names()`]
+    /// or [`CDCLogReaderBuilder::all_tables()`].
     /// In case it is not set [`CDCLogReaderBuilder::build()`] will return error message:
     /// `failed to create the cdc reader: missing table name`
     pub fn table_name(mut self, table_name: &str) -> Self {
-        self.table_name = Some(table_name.to_string());
+        self.table_names = vec![table_name.to_string()];
+        self
+    }
+
+    /// Set names of the tables from the keyspace that [`CDCLogReader`] instance will read data from.
+    /// The CDC logs of all the tables are read by the same stream readers,
+    /// so every consumer receives rows of all the tables.
+    /// Use [`CDCRow::table_name()`](crate::consumer::CDCRow::table_name) to tell them apart.
+    pub fn table_names(mut self, table_names: &[&str]) -> Self {
+        self.table_names = table_names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Make [`CDCLogReader`] instance read data from all the tables in the keyspace
+    /// that have CDC enabled. The tables are listed once, when the reader is built.
+    pub fn all_tables(mut self) -> Self {
+        self.all_tables = true;
         self
     }
 
@@ -374,8 +583,22 @@ impl CDCLogReaderBuilder {
 
     /// Set consumer factory which will be used in the [`CDCLogReader`] instance to create
     /// consumers and feed them with data fetched from the CDC log.
+    /// Replaces the factory set with [`CDCLogReaderBuilder::change_consumer_factory()`].
     pub fn consumer_factory(mut self, consumer_factory: Arc<dyn ConsumerFactory>) -> Self {
         self.consumer_factory = Some(consumer_factory);
+        self.change_consumer_factory = None;
+        self
+    }
+
+    /// Set change consumer factory which will be used in the [`CDCLogReader`] instance to create
+    /// consumers and feed them with whole changes fetched from the CDC log.
+    /// Replaces the factory set with [`CDCLogReaderBuilder::consumer_factory()`].
+    pub fn change_consumer_factory(
+        mut self,
+        change_consumer_factory: Arc<dyn ChangeConsumerFactory>,
+    ) -> Self {
+        self.change_consumer_factory = Some(change_consumer_factory);
+        self.consumer_factory = None;
         self
     }
 
@@ -402,6 +625,82 @@ impl CDCLogReaderBuilder {
         self
     }
 
+    /// Set flag indicating whether the [`CDCLogReader`] instance should save only
+    /// the progress acknowledged by the consumers with [`Consumer::acknowledged_position`](crate::consumer::Consumer::acknowledged_position).
+    /// Otherwise, the progress is saved as soon as the rows are passed to the consumers,
+    /// so the rows not yet durably processed by them are lost in case of a crash.
+    ///
+    /// The checkpoint is moved to the end of a window once all its rows are acknowledged,
+    /// so after a restart some acknowledged rows may be read again.
+    /// If rows are still not acknowledged when the reading of their generation finishes,
+    /// the later generations are not saved, so after a restart the reading resumes
+    /// from that generation and its unacknowledged rows are read again.
+    /// Default value is `false`.
+    pub fn save_only_acknowledged_progress(mut self, value: bool) -> Self {
+        self.save_only_acknowledged_progress = value;
+        self
+    }
+
+    /// Set the maximum number of windows with rows not acknowledged by a consumer,
+    /// when saving only the acknowledged progress.
+    /// A stream reader whose consumer falls further behind with acknowledging its rows stops with an error,
+    /// instead of keeping track of the positions of all the unacknowledged rows.
+    /// Windows without rows don't count towards the limit.
+    /// Default value is 1000.
+    pub fn max_unacknowledged_windows(mut self, max_windows: usize) -> Self {
+        self.max_unacknowledged_windows = max_windows;
+        self
+    }
+
+    /// Set the maximum number of stream readers reading their windows at the same time.
+    /// The readers that wait for their turn are served in the order they finished their previous windows,
+    /// so the groups of streams are read round-robin, one window at a time.
+    /// By default, all the readers work at the same time.
+    pub fn max_concurrent_readers(mut self, max_concurrent_readers: usize) -> Self {
+        self.max_concurrent_readers = Some(max_concurrent_readers);
+        self
+    }
+
+    /// Set the number of streams read together by a single stream reader with one query.
+    /// Streams of the consecutive vnodes are merged into one group, and bigger vnodes are split.
+    /// By default, there is one stream reader per vnode.
+    pub fn stream_group_size(mut self, stream_group_size: usize) -> Self {
+        self.stream_group_size = Some(stream_group_size);
+        self
+    }
+
+    /// Make every stream reader fetch rows concurrently with consuming them,
+    /// at most `prefetch_depth` rows ahead of its consumer, so the fetching of the next rows
+    /// and windows doesn't wait for the consumer to process the previous ones.
+    /// The consumer still receives rows of every stream in order.
+    /// A reader waiting for its consumer doesn't count towards `max_concurrent_readers`.
+    /// By default, the rows are fetched only when the consumer is ready to consume them.
+    pub fn prefetch_depth(mut self, prefetch_depth: usize) -> Self {
+        self.prefetch_depth = Some(prefetch_depth);
+        self
+    }
+
+    /// Set the policy of retrying reads of the CDC log that failed because of transient errors.
+    /// A window whose reading failed is read again from its beginning,
+    /// but the rows already passed to the consumer are not passed again.
+    /// Errors returned by the consumer are not retried.
+    /// By default, any failed read stops the [`CDCLogReader`] instance.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Set the policy of handling errors returned by the consumers.
+    /// It decides whether a row that a consumer failed to consume is passed to it again,
+    /// skipped, passed to a [`DeadLetterSink`](crate::error_policy::DeadLetterSink)
+    /// or whether the [`CDCLogReader`] instance stops.
+    /// With [`CDCLogReaderBuilder::change_consumer_factory()`], the policy is applied to whole changes.
+    /// By default, the first error stops the [`CDCLogReader`] instance.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
     /// Set instance of [`CDCCheckpointSaver`] trait object to load/save checkpoints.
     pub fn checkpoint_saver(mut self, cp_saver: Arc<dyn CDCCheckpointSaver>) -> Self {
         self.checkpoint_saver = Some(cp_saver);
@@ -411,19 +710,57 @@ impl CDCLogReaderBuilder {
     /// Build the CDCLogReader after setting all the options
     /// It will fail with an error message if all the required fields are not set.
     /// Currently required fields are the following:
-    /// `session`, `keyspace`, `table_name`, `consumer_factory`
+    /// `session` (or `log_source`), `keyspace`, `table_name` (or `table_names`, `all_tables`), `consumer_factory`
     pub async fn build(self) -> anyhow::Result<(CDCLogReader, RemoteHandle<anyhow::Result<()>>)> {
         let should_use_checkpoint_saver = self.should_save_progress || self.should_load_progress;
 
-        let table_name = self.table_name.ok_or_else(|| {
-            anyhow::anyhow!("failed to create the cdc reader: missing table name")
-        })?;
+        if self.table_names.is_empty() && !self.all_tables {
+            return Err(anyhow::anyhow!(
+                "failed to create the cdc reader: missing table name"
+            ));
+        }
         let keyspace = self.keyspace.ok_or_else(|| {
             anyhow::anyhow!("failed to create the cdc reader: missing keyspace name")
         })?;
-        let session = self
-            .session
-            .ok_or_else(|| anyhow::anyhow!("failed to create the cdc reader: missing session"))?;
+        let source = match (self.log_source, self.session) {
+            (Some(log_source), _) => log_source,
+            (None, Some(session)) => Arc::new(ScyllaLogSource::new(session)),
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "failed to create the cdc reader: missing session"
+                ))
+            }
+        };
+
+        let table_names = if self.all_tables {
+            let table_names = source.fetch_cdc_table_names(&keyspace).await?;
+            if table_names.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "failed to create the cdc reader: no tables with CDC enabled in keyspace {}",
+                    keyspace
+                ));
+            }
+            table_names
+        } else {
+            self.table_names
+        };
+
+        if self.max_concurrent_readers == Some(0) {
+            return Err(anyhow::anyhow!(
+                "failed to create the cdc reader: max_concurrent_readers must be positive"
+            ));
+        }
+        if self.stream_group_size == Some(0) {
+            return Err(anyhow::anyhow!(
+                "failed to create the cdc reader: stream_group_size must be positive"
+            ));
+        }
+
+        if self.prefetch_depth == Some(0) {
+            return Err(anyhow::anyhow!(
+                "failed to create the cdc reader: prefetch_depth must be positive"
+            ));
+        }
 
         if should_use_checkpoint_saver && self.checkpoint_saver.is_none() {
             return Err(anyhow::anyhow!(
@@ -431,14 +768,29 @@ impl CDCLogReaderBuilder {
             ));
         }
 
-        let consumer_factory = self.consumer_factory.ok_or_else(|| {
-            anyhow::anyhow!("failed to create the cdc reader: missing consumer factory")
-        })?;
+        // Consumers of whole changes are wrapped in adapters applying the error policy,
+        // so a failed change is retried or passed to the dead letter sink with all its rows.
+        let (consumer_factory, error_policy): (Arc<dyn ConsumerFactory>, _) = match self
+            .change_consumer_factory
+        {
+            Some(factory) => (
+                Arc::new(
+                    ChangeConsumerFactoryAdapter::new(factory).with_error_policy(self.error_policy),
+                ),
+                ErrorPolicy::halt(),
+            ),
+            None => (
+                self.consumer_factory.ok_or_else(|| {
+                    anyhow::anyhow!("failed to create the cdc reader: missing consumer factory")
+                })?,
+                self.error_policy,
+            ),
+        };
 
-        let end_timestamp = chrono::Duration::max_value();
         let (end_timestamp_sender, end_timestamp_receiver) =
-            tokio::sync::watch::channel(end_timestamp);
-        let readers = vec![];
+            tokio::sync::watch::channel(self.end_timestamp);
+        let readers = Arc::new(Mutex::new(vec![]));
+        let generation = Arc::new(Mutex::new(None));
 
         let mut start_timestamp = self.start_timestamp;
         if self.should_load_progress {
@@ -464,14 +816,24 @@ impl CDCLogReaderBuilder {
             should_save_progress: self.should_save_progress,
             checkpoint_saver: self.checkpoint_saver.clone(),
             pause_between_saves: self.pause_between_saves,
+            save_only_acknowledged_progress: self.save_only_acknowledged_progress,
+            max_unacknowledged_windows: self.max_unacknowledged_windows,
+            reader_permits: self
+                .max_concurrent_readers
+                .map(|permits| Arc::new(Semaphore::new(permits))),
+            prefetch_depth: self.prefetch_depth,
+            retry_policy: self.retry_policy,
+            error_policy,
         };
 
         let mut cdc_reader_worker = CDCReaderWorker {
-            session,
+            source,
             keyspace,
-            table_name,
+            table_names,
             end_timestamp: self.end_timestamp,
-            readers,
+            readers: Arc::clone(&readers),
+            generation: Arc::clone(&generation),
+            stream_group_size: self.stream_group_size,
             end_timestamp_receiver,
             consumer_factory,
             config,
@@ -479,7 +841,7 @@ impl CDCLogReaderBuilder {
 
         let (fut, handle) = async move { cdc_reader_worker.run().await }.remote_handle();
         tokio::task::spawn(fut);
-        let printer = CDCLogReader::new(end_timestamp_sender);
+        let printer = CDCLogReader::new(end_timestamp_sender, readers, generation);
 
         Ok((printer, handle))
     }
@@ -491,3 +853,35 @@ impl Default for CDCLogReaderBuilder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regroup_streams() {
+        let stream_id = |id: u8| StreamID::new(vec![id]);
+        let groups = vec![
+            vec![stream_id(1)],
+            vec![stream_id(2), stream_id(3), stream_id(4)],
+            vec![stream_id(5)],
+        ];
+
+        assert_eq!(
+            regroup_streams(groups.clone(), 2),
+            vec![
+                vec![stream_id(1), stream_id(2)],
+                vec![stream_id(3), stream_id(4)],
+                vec![stream_id(5)],
+            ]
+        );
+        assert_eq!(
+            regroup_streams(groups.clone(), 1),
+            (1..=5).map(|id| vec![stream_id(id)]).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            regroup_streams(groups, 10),
+            vec![(1..=5).map(stream_id).collect::<Vec<_>>()]
+        );
+    }
+}
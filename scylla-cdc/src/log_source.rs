@@ -0,0 +1,194 @@
+//! A module containing the abstraction over the source of the CDC log.
+//!
+//! [`CDCLogReader`](crate::log_reader::CDCLogReader) reads stream generations
+//! and rows of the CDC log through the [`CDCLogSource`] trait.
+//! By default, the data is read from a Scylla cluster by [`ScyllaLogSource`].
+//! For testing purposes, [`InMemoryLogSource`](crate::in_memory_log_source::InMemoryLogSource)
+//! can be used instead.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use scylla::frame::response::result::{ColumnSpec, Row};
+use scylla::frame::value::Timestamp;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::Session;
+use tokio::sync::Mutex;
+
+use crate::cdc_types::{GenerationTimestamp, StreamID};
+use crate::stream_generations::GenerationFetcher;
+
+const CDC_LOG_TABLE_SUFFIX: &str = "_scylla_cdc_log";
+
+/// Rows of the CDC log returned by [`CDCLogSource::fetch_rows`].
+pub struct CDCLogRows {
+    /// Specification of the columns of the CDC log table, in the order used in the rows.
+    pub column_specs: Vec<ColumnSpec>,
+    /// Rows ordered by `cdc$time` and `cdc$batch_seq_no` within each stream.
+    pub rows: BoxStream<'static, anyhow::Result<Row>>,
+}
+
+/// Customizable trait responsible for reading stream generations and rows of the CDC log.
+#[async_trait]
+pub trait CDCLogSource: Send + Sync {
+    /// Returns all the generations, ordered from the newest to the oldest.
+    async fn fetch_all_generations(&self) -> anyhow::Result<Vec<GenerationTimestamp>>;
+    /// Returns the generation that was operating at the given time or `None` if there is no such generation.
+    async fn fetch_generation_by_timestamp(
+        &self,
+        time: &chrono::Duration,
+    ) -> anyhow::Result<Option<GenerationTimestamp>>;
+    /// Returns the generation following the given one or `None` if the given one is currently operating.
+    async fn fetch_next_generation(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Option<GenerationTimestamp>>;
+    /// Returns identifiers of all streams of the given generation, grouped by vnodes.
+    async fn fetch_stream_ids(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Vec<Vec<StreamID>>>;
+    /// Returns names of the tables in the given keyspace that have CDC enabled.
+    async fn fetch_cdc_table_names(&self, keyspace: &str) -> anyhow::Result<Vec<String>>;
+    /// Returns rows of the CDC log of `keyspace.table_name` from the given streams,
+    /// with `cdc$time` in range [`window_begin`, `window_end`).
+    async fn fetch_rows(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+        stream_ids: &[StreamID],
+        window_begin: chrono::Duration,
+        window_end: chrono::Duration,
+    ) -> anyhow::Result<CDCLogRows>;
+}
+
+/// Default implementation for [`CDCLogSource`] trait.
+/// Reads the generations from `system_distributed` keyspace
+/// and the rows from the CDC log table of a Scylla cluster.
+pub struct ScyllaLogSource {
+    session: Arc<Session>,
+    generation_fetcher: GenerationFetcher,
+    // Maps `keyspace.table_name` to the prepared query reading its CDC log.
+    fetch_rows_stmts: Mutex<HashMap<String, PreparedStatement>>,
+}
+
+impl ScyllaLogSource {
+    pub fn new(session: Arc<Session>) -> ScyllaLogSource {
+        let generation_fetcher = GenerationFetcher::new(&session);
+        ScyllaLogSource::with_generation_fetcher(session, generation_fetcher)
+    }
+
+    pub(crate) fn with_generation_fetcher(
+        session: Arc<Session>,
+        generation_fetcher: GenerationFetcher,
+    ) -> ScyllaLogSource {
+        ScyllaLogSource {
+            session,
+            generation_fetcher,
+            fetch_rows_stmts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn get_fetch_rows_stmt(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+    ) -> anyhow::Result<PreparedStatement> {
+        let keyspace_table_name = format!("{}.{}", keyspace, table_name);
+        let mut stmts = self.fetch_rows_stmts.lock().await;
+
+        if let Some(stmt) = stmts.get(&keyspace_table_name) {
+            return Ok(stmt.clone());
+        }
+
+        let query = format!(
+            "SELECT * FROM {}{} \
+            WHERE \"cdc$stream_id\" in ? \
+            AND \"cdc$time\" >= minTimeuuid(?) \
+            AND \"cdc$time\" < minTimeuuid(?)  BYPASS CACHE",
+            keyspace_table_name, CDC_LOG_TABLE_SUFFIX
+        );
+        let stmt = self.session.prepare(query).await?;
+        stmts.insert(keyspace_table_name, stmt.clone());
+
+        Ok(stmt)
+    }
+}
+
+#[async_trait]
+impl CDCLogSource for ScyllaLogSource {
+    async fn fetch_all_generations(&self) -> anyhow::Result<Vec<GenerationTimestamp>> {
+        self.generation_fetcher.fetch_all_generations().await
+    }
+
+    async fn fetch_generation_by_timestamp(
+        &self,
+        time: &chrono::Duration,
+    ) -> anyhow::Result<Option<GenerationTimestamp>> {
+        self.generation_fetcher
+            .fetch_generation_by_timestamp(time)
+            .await
+    }
+
+    async fn fetch_next_generation(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Option<GenerationTimestamp>> {
+        self.generation_fetcher
+            .fetch_next_generation(generation)
+            .await
+    }
+
+    async fn fetch_stream_ids(
+        &self,
+        generation: &GenerationTimestamp,
+    ) -> anyhow::Result<Vec<Vec<StreamID>>> {
+        self.generation_fetcher.fetch_stream_ids(generation).await
+    }
+
+    async fn fetch_cdc_table_names(&self, keyspace: &str) -> anyhow::Result<Vec<String>> {
+        let mut rows = self
+            .session
+            .query_iter(
+                "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ?",
+                (keyspace,),
+            )
+            .await?
+            .into_typed::<(String,)>();
+
+        let mut table_names = Vec::new();
+        while let Some(row) = rows.next().await {
+            let (log_table_name,) = row?;
+            if let Some(table_name) = log_table_name.strip_suffix(CDC_LOG_TABLE_SUFFIX) {
+                table_names.push(table_name.to_string());
+            }
+        }
+
+        Ok(table_names)
+    }
+
+    async fn fetch_rows(
+        &self,
+        keyspace: &str,
+        table_name: &str,
+        stream_ids: &[StreamID],
+        window_begin: chrono::Duration,
+        window_end: chrono::Duration,
+    ) -> anyhow::Result<CDCLogRows> {
+        let stmt = self.get_fetch_rows_stmt(keyspace, table_name).await?;
+        let rows = self
+            .session
+            .execute_iter(
+                stmt,
+                (stream_ids, Timestamp(window_begin), Timestamp(window_end)),
+            )
+            .await?;
+
+        Ok(CDCLogRows {
+            column_specs: rows.get_column_specs().to_vec(),
+            rows: rows.map_err(anyhow::Error::new).boxed(),
+        })
+    }
+}
@@ -0,0 +1,159 @@
+//! Prometheus metrics of reading the CDC log.
+//!
+//! The metrics are registered in the default registry of the [`prometheus`] crate
+//! when they are first updated, and can be exported with [`encode_text`] or [`prometheus::gather`].
+//!
+//! Available with the `metrics` feature.
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use prometheus::{
+    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    Encoder, GaugeVec, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+
+use crate::cdc_types::StreamID;
+use crate::consumer::OperationType;
+
+static ROWS_CONSUMED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "scylla_cdc_rows_consumed_total",
+        "Rows of the CDC log passed to the consumers, by operation type",
+        &["keyspace", "table", "operation"]
+    )
+    .unwrap()
+});
+
+static WINDOWS_FETCHED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "scylla_cdc_windows_fetched_total",
+        "Windows of the CDC log read by the stream readers",
+        &["keyspace"]
+    )
+    .unwrap()
+});
+
+static QUERY_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "scylla_cdc_query_duration_seconds",
+        "Latency of the queries reading a window of the CDC log, until the first page of rows",
+        &["keyspace", "table"]
+    )
+    .unwrap()
+});
+
+static STREAM_GROUP_LAG: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "scylla_cdc_stream_group_lag_seconds",
+        "Time between now and the end of the last window read by a stream reader. \
+        Stream groups are identified by their first stream ID",
+        &["keyspace", "tables", "stream_group"]
+    )
+    .unwrap()
+});
+
+static CHECKPOINT_SAVE_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "scylla_cdc_checkpoint_save_failures_total",
+        "Failed attempts to save the checkpoints of the streams of a stream reader"
+    )
+    .unwrap()
+});
+
+static GENERATION_SWITCHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "scylla_cdc_generation_switches_total",
+        "Generations of CDC streams started to be read",
+        &["keyspace"]
+    )
+    .unwrap()
+});
+
+/// Returns all the metrics of the default registry in the Prometheus text format.
+pub fn encode_text() -> anyhow::Result<String> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+pub(crate) fn row_consumed(keyspace: &str, table: &str, operation: &OperationType) {
+    ROWS_CONSUMED
+        .with_label_values(&[keyspace, table, &operation.to_string()])
+        .inc();
+}
+
+pub(crate) fn window_fetched(keyspace: &str) {
+    WINDOWS_FETCHED.with_label_values(&[keyspace]).inc();
+}
+
+pub(crate) fn observe_query(keyspace: &str, table: &str, duration: Duration) {
+    QUERY_DURATION
+        .with_label_values(&[keyspace, table])
+        .observe(duration.as_secs_f64());
+}
+
+pub(crate) fn set_stream_group_lag(
+    keyspace: &str,
+    tables: &[String],
+    stream_ids: &[StreamID],
+    lag: chrono::Duration,
+) {
+    STREAM_GROUP_LAG
+        .with_label_values(&[keyspace, &tables.join(","), &stream_group_label(stream_ids)])
+        .set(lag.num_milliseconds() as f64 / 1000.);
+}
+
+// Removes the lag of a stream group that isn't read anymore, after a generation switch.
+pub(crate) fn remove_stream_group_lag(keyspace: &str, tables: &[String], stream_ids: &[StreamID]) {
+    // The lag isn't set if the reader hasn't read any window.
+    let _ = STREAM_GROUP_LAG.remove_label_values(&[
+        keyspace,
+        &tables.join(","),
+        &stream_group_label(stream_ids),
+    ]);
+}
+
+pub(crate) fn checkpoint_save_failed() {
+    CHECKPOINT_SAVE_FAILURES.inc();
+}
+
+pub(crate) fn generation_switched(keyspace: &str) {
+    GENERATION_SWITCHES.with_label_values(&[keyspace]).inc();
+}
+
+fn stream_group_label(stream_ids: &[StreamID]) -> String {
+    stream_ids
+        .first()
+        .map(|stream_id| hex::encode(&stream_id.id))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_text() {
+        let stream_ids = vec![StreamID::new(vec![1, 2]), StreamID::new(vec![3, 4])];
+        let tables = vec!["t".to_string()];
+        row_consumed("metrics_ks", "t", &OperationType::RowInsert);
+        row_consumed("metrics_ks", "t", &OperationType::RowInsert);
+        set_stream_group_lag(
+            "metrics_ks",
+            &tables,
+            &stream_ids,
+            chrono::Duration::milliseconds(1500),
+        );
+
+        let text = encode_text().unwrap();
+        assert!(text.contains(
+            r#"scylla_cdc_rows_consumed_total{keyspace="metrics_ks",operation="RowInsert",table="t"} 2"#
+        ));
+        assert!(text.contains(
+            r#"scylla_cdc_stream_group_lag_seconds{keyspace="metrics_ks",stream_group="0102",tables="t"} 1.5"#
+        ));
+
+        remove_stream_group_lag("metrics_ks", &tables, &stream_ids);
+        assert!(!encode_text().unwrap().contains(r#"stream_group="0102""#));
+    }
+}
@@ -0,0 +1,200 @@
+//! A module containing the policy of retrying failed reads of the CDC log.
+//!
+//! When fetching rows of a window fails with a retriable error,
+//! the [`StreamReader`](crate::stream_reader::StreamReader) waits for a backoff
+//! and reads the window again from its beginning.
+//! Rows already passed to the consumer are not passed again.
+use std::sync::Arc;
+use std::time::Duration;
+
+use scylla::transport::errors::{DbError, QueryError};
+
+use crate::cdc_types::StreamID;
+
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_INITIAL_BACKOFF_MILLIS: u64 = 100;
+const DEFAULT_MAX_BACKOFF_MILLIS: u64 = 10_000;
+
+type RetryHook = Arc<dyn Fn(&RetryEvent<'_>) + Send + Sync>;
+
+/// Information about a retry of a failed read, passed to the hook set with [`RetryPolicy::on_retry`].
+#[non_exhaustive]
+pub struct RetryEvent<'a> {
+    /// Streams of the reader that retries the read.
+    pub stream_ids: &'a [StreamID],
+    pub window_begin: chrono::Duration,
+    pub window_end: chrono::Duration,
+    /// Number of the retry, starting from 1.
+    pub retry: u32,
+    /// Time the reader waits before the retry.
+    pub delay: Duration,
+    /// Error that caused the retry.
+    pub error: &'a anyhow::Error,
+}
+
+/// Policy of retrying failed reads of the CDC log, with exponential backoff.
+///
+/// # Default configuration
+/// * max_retries: 5
+/// * initial_backoff: 100 milliseconds
+/// * max_backoff: 10 seconds
+/// * retriable errors: [`is_transient_error`]
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    is_retriable: Arc<dyn Fn(&QueryError) -> bool + Send + Sync>,
+    on_retry: Option<RetryHook>,
+}
+
+impl RetryPolicy {
+    /// Creates new RetryPolicy with default configuration.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: Duration::from_millis(DEFAULT_INITIAL_BACKOFF_MILLIS),
+            max_backoff: Duration::from_millis(DEFAULT_MAX_BACKOFF_MILLIS),
+            is_retriable: Arc::new(is_transient_error),
+            on_retry: None,
+        }
+    }
+
+    /// Set the maximum number of retries of a read of a single window.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the backoff before the first retry. Every next backoff is twice as long.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum backoff before a retry.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the function deciding which query errors are retriable.
+    /// Other errors stop the reader.
+    pub fn retriable_errors(
+        mut self,
+        is_retriable: impl Fn(&QueryError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_retriable = Arc::new(is_retriable);
+        self
+    }
+
+    /// Set the hook called before every retry, e.g. to log it or update metrics.
+    pub fn on_retry(mut self, on_retry: impl Fn(&RetryEvent<'_>) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Arc::new(on_retry));
+        self
+    }
+
+    // Returns the backoff before the given retry of a failed read
+    // or `None` if the read should not be retried.
+    pub(crate) fn retry_delay(&self, error: &anyhow::Error, retry: u32) -> Option<Duration> {
+        if retry > self.max_retries {
+            return None;
+        }
+        let query_error = error.downcast_ref::<QueryError>()?;
+        if !(self.is_retriable)(query_error) {
+            return None;
+        }
+
+        let multiplier = 2u32.saturating_pow(retry.saturating_sub(1));
+        Some(
+            self.initial_backoff
+                .saturating_mul(multiplier)
+                .min(self.max_backoff),
+        )
+    }
+
+    pub(crate) fn notify_retry(&self, event: &RetryEvent<'_>) {
+        if let Some(on_retry) = self.on_retry.as_ref() {
+            on_retry(event);
+        }
+    }
+}
+
+/// Create a [`RetryPolicy`] with default configuration, same as [`RetryPolicy::new()`]
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` for errors caused by temporary problems with the connection or the cluster,
+/// such as timeouts, unavailable replicas or overloaded nodes.
+pub fn is_transient_error(error: &QueryError) -> bool {
+    match error {
+        QueryError::IoError(_)
+        | QueryError::TimeoutError
+        | QueryError::TooManyOrphanedStreamIds(_)
+        | QueryError::UnableToAllocStreamId => true,
+        QueryError::DbError(db_error, _) => matches!(
+            db_error,
+            DbError::Unavailable { .. }
+                | DbError::Overloaded
+                | DbError::IsBootstrapping
+                | DbError::ReadTimeout { .. }
+                | DbError::ReadFailure { .. }
+                | DbError::ServerError
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::new()
+            .max_retries(4)
+            .initial_backoff(Duration::from_millis(10))
+            .max_backoff(Duration::from_millis(50));
+        let error = anyhow::Error::new(QueryError::TimeoutError);
+
+        let delays: Vec<Option<Duration>> = (1..=5)
+            .map(|retry| policy.retry_delay(&error, retry))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(10)),
+                Some(Duration::from_millis(20)),
+                Some(Duration::from_millis(40)),
+                Some(Duration::from_millis(50)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_retriable_errors() {
+        let policy = RetryPolicy::new();
+        let retriable = |error: anyhow::Error| policy.retry_delay(&error, 1).is_some();
+
+        assert!(retriable(anyhow::Error::new(QueryError::TimeoutError)));
+        assert!(retriable(anyhow::Error::new(QueryError::DbError(
+            DbError::Overloaded,
+            String::new()
+        ))));
+        assert!(!retriable(anyhow::Error::new(QueryError::DbError(
+            DbError::SyntaxError,
+            String::new()
+        ))));
+        assert!(!retriable(anyhow::anyhow!("not a query error")));
+
+        let policy = RetryPolicy::new().retriable_errors(|_| false);
+        assert_eq!(
+            policy.retry_delay(&anyhow::Error::new(QueryError::TimeoutError), 1),
+            None
+        );
+    }
+}
@@ -13,6 +13,7 @@ use tokio::time::sleep;
 use tracing::warn;
 
 use crate::cdc_types::{GenerationTimestamp, StreamID};
+use crate::log_source::CDCLogSource;
 
 /// Component responsible for managing stream generations.
 pub struct GenerationFetcher {
@@ -178,55 +179,58 @@ impl GenerationFetcher {
 
         Ok(None)
     }
+}
 
-    pub async fn fetch_generations_continuously(
-        self: Arc<Self>,
-        start_timestamp: chrono::Duration,
-        sleep_interval: time::Duration,
-    ) -> anyhow::Result<(mpsc::Receiver<GenerationTimestamp>, RemoteHandle<()>)> {
-        let (generation_sender, generation_receiver) = mpsc::channel(1);
-
-        let (future, future_handle) = async move {
-            let mut generation = loop {
-                match self.fetch_generation_by_timestamp(&start_timestamp).await {
-                    Ok(Some(generation)) => break generation,
-                    Ok(None) => {
-                        break {
-                            loop {
-                                match self.fetch_all_generations().await {
-                                    Ok(vectors) => match vectors.last() {
-                                        None => sleep(sleep_interval).await,
-                                        Some(generation) => break generation.clone(),
-                                    },
-                                    _ => warn!("Failed to fetch all generations"),
-                                }
+/// Fetches generations in the background and sends them through the returned channel, in order.
+/// The first sent generation is the one operating at `start_timestamp`
+/// or the oldest one, if there was no generation operating at that time.
+pub async fn fetch_generations_continuously(
+    source: Arc<dyn CDCLogSource>,
+    start_timestamp: chrono::Duration,
+    sleep_interval: time::Duration,
+) -> anyhow::Result<(mpsc::Receiver<GenerationTimestamp>, RemoteHandle<()>)> {
+    let (generation_sender, generation_receiver) = mpsc::channel(1);
+
+    let (future, future_handle) = async move {
+        let mut generation = loop {
+            match source.fetch_generation_by_timestamp(&start_timestamp).await {
+                Ok(Some(generation)) => break generation,
+                Ok(None) => {
+                    break {
+                        loop {
+                            match source.fetch_all_generations().await {
+                                Ok(vectors) => match vectors.last() {
+                                    None => sleep(sleep_interval).await,
+                                    Some(generation) => break generation.clone(),
+                                },
+                                _ => warn!("Failed to fetch all generations"),
                             }
                         }
                     }
-                    _ => warn!("Failed to fetch generation by timestamp"),
                 }
-            };
-            if generation_sender.send(generation.clone()).await.is_err() {
-                return;
+                _ => warn!("Failed to fetch generation by timestamp"),
             }
+        };
+        if generation_sender.send(generation.clone()).await.is_err() {
+            return;
+        }
 
-            loop {
-                generation = loop {
-                    match self.fetch_next_generation(&generation).await {
-                        Ok(Some(generation)) => break generation,
-                        Ok(None) => sleep(sleep_interval).await,
-                        _ => warn!("Failed to fetch next generation"),
-                    }
-                };
-                if generation_sender.send(generation.clone()).await.is_err() {
-                    break;
+        loop {
+            generation = loop {
+                match source.fetch_next_generation(&generation).await {
+                    Ok(Some(generation)) => break generation,
+                    Ok(None) => sleep(sleep_interval).await,
+                    _ => warn!("Failed to fetch next generation"),
                 }
+            };
+            if generation_sender.send(generation.clone()).await.is_err() {
+                break;
             }
         }
-        .remote_handle();
-        tokio::spawn(future);
-        Ok((generation_receiver, future_handle))
     }
+    .remote_handle();
+    tokio::spawn(future);
+    Ok((generation_receiver, future_handle))
 }
 
 // Returns current cluster size in case of a success.
@@ -266,6 +270,7 @@ mod tests {
     use scylla_cdc_test_utils::prepare_db;
 
     use super::*;
+    use crate::log_source::ScyllaLogSource;
 
     const TEST_STREAM_TABLE: &str = "cdc_streams_descriptions_v2";
     const TEST_GENERATION_TABLE: &str = "cdc_generation_timestamps";
@@ -396,7 +401,7 @@ mod tests {
         let fetcher = setup().await.unwrap();
 
         // Input.
-        let timestamp_ms_vec = vec![
+        let timestamp_ms_vec = [
             GENERATION_OLD_MILLISECONDS - 1,
             GENERATION_OLD_MILLISECONDS,
             (GENERATION_NEW_MILLISECONDS + GENERATION_OLD_MILLISECONDS) / 2,
@@ -404,7 +409,7 @@ mod tests {
             GENERATION_NEW_MILLISECONDS + 1,
         ];
         // Expected output.
-        let correct_generation_vec = vec![
+        let correct_generation_vec = [
             None,
             Some(GENERATION_OLD_MILLISECONDS),
             Some(GENERATION_OLD_MILLISECONDS),
@@ -480,7 +485,7 @@ mod tests {
 
         let stream_ids = fetcher.fetch_stream_ids(&gen).await.unwrap();
 
-        let correct_stream_ids: Vec<Vec<StreamID>> = vec![[TEST_STREAM_1, TEST_STREAM_2]]
+        let correct_stream_ids: Vec<Vec<StreamID>> = [[TEST_STREAM_1, TEST_STREAM_2]]
             .iter()
             .map(|stream_vec| {
                 stream_vec
@@ -500,13 +505,18 @@ mod tests {
         let fetcher = setup().await.unwrap();
         let session = fetcher.session.clone();
 
-        let (mut generation_receiver, _future) = Arc::new(fetcher)
-            .fetch_generations_continuously(
-                chrono::Duration::milliseconds(GENERATION_OLD_MILLISECONDS - 1),
-                time::Duration::from_millis(100),
-            )
-            .await
-            .unwrap();
+        let source = Arc::new(ScyllaLogSource::with_generation_fetcher(
+            session.clone(),
+            fetcher,
+        ));
+
+        let (mut generation_receiver, _future) = fetch_generations_continuously(
+            source,
+            chrono::Duration::milliseconds(GENERATION_OLD_MILLISECONDS - 1),
+            time::Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
 
         let first_gen = GenerationTimestamp {
             timestamp: chrono::Duration::milliseconds(GENERATION_OLD_MILLISECONDS),
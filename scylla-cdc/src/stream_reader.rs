@@ -1,18 +1,23 @@
 //! A module containing the logic responsible for reading data from one stream.
 
 use std::cmp::{max, min};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time;
 
 use futures::StreamExt;
-use scylla::frame::value::Timestamp;
-use scylla::Session;
-use tokio::sync::watch;
+use scylla::frame::response::result::Row;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch, Semaphore, SemaphorePermit};
 use tokio::time::sleep;
 
-use crate::cdc_types::{GenerationTimestamp, StreamID};
+use crate::cdc_types::{GenerationTimestamp, RowPosition, StreamID};
 use crate::checkpoints::{start_saving_checkpoints, CDCCheckpointSaver, Checkpoint};
 use crate::consumer::{CDCRow, CDCRowSchema, Consumer};
+use crate::error_policy::ErrorPolicy;
+use crate::log_source::CDCLogSource;
+use crate::retry_policy::{RetryEvent, RetryPolicy};
 
 #[derive(Clone)]
 pub struct CDCReaderConfig {
@@ -24,67 +29,175 @@ pub struct CDCReaderConfig {
     pub should_save_progress: bool,
     pub checkpoint_saver: Option<Arc<dyn CDCCheckpointSaver>>,
     pub pause_between_saves: time::Duration,
+    pub save_only_acknowledged_progress: bool,
+    pub max_unacknowledged_windows: usize,
+    // Shared by all the readers to limit how many of them read a window at the same time.
+    pub reader_permits: Option<Arc<Semaphore>>,
+    pub prefetch_depth: Option<usize>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub error_policy: ErrorPolicy,
+}
+
+// Rows passed to the consumer in a window that hasn't been fully acknowledged yet.
+struct UnacknowledgedWindow {
+    end: chrono::Duration,
+    // Positions of the rows that weren't acknowledged yet, in the order they were consumed.
+    positions: Vec<RowPosition>,
+}
+
+// Keeps track of the windows whose rows weren't all acknowledged by the consumer.
+#[derive(Default)]
+struct AcknowledgementTracker {
+    windows: VecDeque<UnacknowledgedWindow>,
+}
+
+impl AcknowledgementTracker {
+    fn push_window(&mut self, end: chrono::Duration, positions: Vec<RowPosition>) {
+        match self.windows.back_mut() {
+            // A window without rows is acknowledged together with the previous one.
+            Some(window) if positions.is_empty() => window.end = end,
+            _ => self
+                .windows
+                .push_back(UnacknowledgedWindow { end, positions }),
+        }
+    }
+
+    // Marks the rows consumed up to the row at the given position as acknowledged.
+    // The acknowledged row doesn't have to be one of the consumed rows:
+    // the last consumed row of its stream at or before its position is acknowledged instead.
+    // Returns the end of the last window whose rows are now all acknowledged,
+    // or `None` if no window was fully acknowledged since the last call.
+    fn acknowledge(
+        &mut self,
+        acknowledged_position: Option<RowPosition>,
+    ) -> Option<chrono::Duration> {
+        let mut acknowledged_end = None;
+
+        let index = acknowledged_position.and_then(|acknowledged_position| {
+            self.windows
+                .iter()
+                .enumerate()
+                .rev()
+                .find_map(|(i, window)| {
+                    window
+                        .positions
+                        .iter()
+                        .rposition(|position| {
+                            position.stream_id == acknowledged_position.stream_id
+                                && *position <= acknowledged_position
+                        })
+                        .map(|j| (i, j))
+                })
+        });
+        if let Some((i, j)) = index {
+            if i > 0 {
+                acknowledged_end = Some(self.windows[i - 1].end);
+            }
+            self.windows.drain(..i);
+            self.windows[0].positions.drain(..=j);
+        }
+
+        while let Some(window) = self.windows.front() {
+            if !window.positions.is_empty() {
+                break;
+            }
+            acknowledged_end = Some(window.end);
+            self.windows.pop_front();
+        }
+
+        acknowledged_end
+    }
+
+    // Returns the number of windows with unacknowledged rows.
+    fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
 }
 
 /// A component responsible for reading data from one stream.
 /// For the description of the reading algorithm,
 /// please see the documentation of the [`log_reader`](crate::log_reader) module.
 pub struct StreamReader {
-    session: Arc<Session>,
+    source: Arc<dyn CDCLogSource>,
     stream_id_vec: Vec<StreamID>,
     upper_timestamp: tokio::sync::Mutex<Option<chrono::Duration>>,
+    // All the rows with earlier `cdc$time` were read from the streams of this reader.
+    read_timestamp: std::sync::Mutex<chrono::Duration>,
+    // End of the last window fetched from the CDC log, `None` before the first window.
+    window_end: std::sync::Mutex<Option<chrono::Duration>>,
+    // Set if the reading finished before the consumer acknowledged all the rows.
+    has_unacknowledged_rows: AtomicBool,
     config: CDCReaderConfig,
 }
 
 impl StreamReader {
     pub fn new(
-        session: &Arc<Session>,
+        source: &Arc<dyn CDCLogSource>,
         stream_ids: Vec<StreamID>,
         config: CDCReaderConfig,
     ) -> StreamReader {
         StreamReader {
-            session: session.clone(),
+            source: Arc::clone(source),
             stream_id_vec: stream_ids,
             upper_timestamp: Default::default(),
+            read_timestamp: std::sync::Mutex::new(config.lower_timestamp),
+            window_end: Default::default(),
+            has_unacknowledged_rows: Default::default(),
             config,
         }
     }
 
+    pub fn stream_ids(&self) -> &[StreamID] {
+        &self.stream_id_vec
+    }
+
+    /// Returns the timestamp up to which the rows of all the streams of this reader were read.
+    pub fn read_timestamp(&self) -> chrono::Duration {
+        *self.read_timestamp.lock().unwrap()
+    }
+
+    /// Returns the end of the last window fetched from the CDC log,
+    /// or `None` if no window was fetched yet.
+    /// With prefetching, it may be several windows ahead of [`StreamReader::read_timestamp`].
+    pub fn window_end(&self) -> Option<chrono::Duration> {
+        *self.window_end.lock().unwrap()
+    }
+
+    /// Returns `true` if the reading finished before all the consumed rows were acknowledged,
+    /// so the saved checkpoints of some streams are before the end of the reading.
+    /// Possible only if saving only the acknowledged progress is enabled.
+    pub fn has_unacknowledged_rows(&self) -> bool {
+        self.has_unacknowledged_rows.load(Ordering::SeqCst)
+    }
+
     pub async fn set_upper_timestamp(&self, new_upper_timestamp: chrono::Duration) {
         let mut guard = self.upper_timestamp.lock().await;
         *guard = Some(new_upper_timestamp);
     }
 
+    /// Reads rows of the CDC logs of the given tables from the streams of this reader.
+    /// In every window, the tables are read one after another.
+    ///
+    /// The progress is tracked separately for every stream: a row is passed to the consumer
+    /// only if its `cdc$time` is not before the position loaded from the checkpoint of its stream,
+    /// so streams whose loaded checkpoints are ahead of the others don't replay their rows.
+    /// The position of a stream is the `cdc$time` of its last consumed change,
+    /// so after a restart only the changes sharing that timestamp are read again.
     pub async fn fetch_cdc(
         &self,
         keyspace: String,
-        table_name: String,
-        mut consumer: Box<dyn Consumer>,
+        table_names: Vec<String>,
+        consumer: Box<dyn Consumer>,
     ) -> anyhow::Result<()> {
-        let query = format!(
-            "SELECT * FROM {}.{}_scylla_cdc_log \
-            WHERE \"cdc$stream_id\" in ? \
-            AND \"cdc$time\" >= minTimeuuid(?) \
-            AND \"cdc$time\" < minTimeuuid(?)  BYPASS CACHE",
-            keyspace, table_name
-        );
-        let query_base = self.session.prepare(query).await?;
-        let mut window_begin = self.config.lower_timestamp;
-        let window_size = chrono::Duration::from_std(self.config.window_size)?;
-        let safety_interval = chrono::Duration::from_std(self.config.safety_interval)?;
-        let mut checkpoint = Checkpoint {
-            timestamp: window_begin.to_std()?,
-            stream_id: self.stream_id_vec[0].clone(),
-            generation: GenerationTimestamp {
-                timestamp: window_begin,
-            },
-        };
-        let (sender, receiver) = watch::channel(checkpoint.clone());
-
-        if self.config.should_load_progress {
-            let mut loaded_timestamp = chrono::Duration::max_value();
-            for stream in &self.stream_id_vec {
-                if let Some(timestamp) = self
+        let mut checkpoints = Vec::with_capacity(self.stream_id_vec.len());
+        for stream in &self.stream_id_vec {
+            let mut timestamp = self.config.lower_timestamp;
+            if self.config.should_load_progress {
+                if let Some(loaded_timestamp) = self
                     .config
                     .checkpoint_saver
                     .as_ref()
@@ -92,25 +205,114 @@ impl StreamReader {
                     .load_last_checkpoint(stream)
                     .await?
                 {
-                    loaded_timestamp = min(timestamp, loaded_timestamp);
+                    timestamp = max(timestamp, loaded_timestamp);
                 }
             }
-            if loaded_timestamp != chrono::Duration::max_value() {
-                window_begin = max(window_begin, loaded_timestamp);
-            }
+            checkpoints.push(Checkpoint {
+                timestamp: timestamp.to_std()?,
+                stream_id: stream.clone(),
+                generation: GenerationTimestamp {
+                    timestamp: self.config.lower_timestamp,
+                },
+            });
         }
+        let (sender, receiver) = watch::channel(checkpoints);
 
         let mut _handle;
         if self.config.should_save_progress {
             _handle = start_saving_checkpoints(
-                self.stream_id_vec.clone(),
                 self.config.checkpoint_saver.as_ref().unwrap().clone(),
                 receiver,
                 self.config.pause_between_saves,
             );
         }
 
+        let result = self
+            .read_windows(&keyspace, &table_names, consumer, &sender)
+            .await;
+
+        // The positions of the streams cover only the consumed rows,
+        // so they are saved even if the reading failed.
+        if self.config.should_save_progress {
+            let checkpoints = sender.borrow().clone();
+            self.config
+                .checkpoint_saver
+                .as_ref()
+                .unwrap()
+                .save_checkpoints(&checkpoints)
+                .await?;
+        }
+
+        result
+    }
+
+    async fn read_windows(
+        &self,
+        keyspace: &str,
+        table_names: &[String],
+        consumer: Box<dyn Consumer>,
+        positions: &watch::Sender<Vec<Checkpoint>>,
+    ) -> anyhow::Result<()> {
+        let window_begin = positions
+            .borrow()
+            .iter()
+            .map(|checkpoint| checkpoint.timestamp)
+            .min()
+            .map(chrono::Duration::from_std)
+            .transpose()?
+            .unwrap_or(self.config.lower_timestamp);
+        *self.read_timestamp.lock().unwrap() = window_begin;
+
+        let mut window_consumer = WindowConsumer::new(self, consumer, positions);
+
+        match self.config.prefetch_depth {
+            None => {
+                self.fetch_windows(
+                    keyspace,
+                    table_names,
+                    window_begin,
+                    WindowSink::Direct(&mut window_consumer),
+                )
+                .await
+            }
+            Some(prefetch_depth) => {
+                // The rows are fetched ahead of the consumer, at most `prefetch_depth` of them.
+                // A single channel keeps the order of the rows of every stream.
+                let (sender, mut receiver) = mpsc::channel(prefetch_depth);
+                let fetch = self.fetch_windows(
+                    keyspace,
+                    table_names,
+                    window_begin,
+                    WindowSink::Channel(sender),
+                );
+                let consume = async {
+                    while let Some(item) = receiver.recv().await {
+                        window_consumer.consume(item).await?;
+                    }
+                    Ok(())
+                };
+                tokio::try_join!(fetch, consume)?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn fetch_windows(
+        &self,
+        keyspace: &str,
+        table_names: &[String],
+        mut window_begin: chrono::Duration,
+        mut sink: WindowSink<'_, '_>,
+    ) -> anyhow::Result<()> {
+        let window_size = chrono::Duration::from_std(self.config.window_size)?;
+        let safety_interval = chrono::Duration::from_std(self.config.safety_interval)?;
+
         loop {
+            // Readers waiting for a permit get it in the order they asked for it,
+            // so with the limit in place, they read their windows in turns.
+            let mut permit = ReaderPermit::new(self.config.reader_permits.as_deref());
+            permit.acquire().await?;
+
             let now_timestamp =
                 chrono::Duration::milliseconds(chrono::Local::now().timestamp_millis());
             let window_end = max(
@@ -118,49 +320,406 @@ impl StreamReader {
                 min(window_begin + window_size, now_timestamp - safety_interval),
             );
 
-            let mut rows_stream = self
-                .session
-                .execute_iter(
-                    query_base.clone(),
-                    (
-                        &self.stream_id_vec,
-                        Timestamp(window_begin),
-                        Timestamp(window_end),
-                    ),
-                )
-                .await?;
+            // Rows passed to the sink during the previous attempts to read the window.
+            let mut sent_rows = vec![HashMap::new(); table_names.len()];
+            let mut retry = 0;
+            loop {
+                let error = match self
+                    .fetch_window(
+                        keyspace,
+                        table_names,
+                        window_begin,
+                        window_end,
+                        &mut sink,
+                        &mut permit,
+                        &mut sent_rows,
+                    )
+                    .await
+                {
+                    Ok(()) => break,
+                    Err(WindowError::Consume(error)) => return Err(error),
+                    Err(WindowError::Fetch(error)) => error,
+                };
 
-            let schema = CDCRowSchema::new(rows_stream.get_column_specs());
+                retry += 1;
+                let retry_policy = match self.config.retry_policy.as_ref() {
+                    Some(retry_policy) => retry_policy,
+                    None => return Err(error),
+                };
+                let delay = match retry_policy.retry_delay(&error, retry) {
+                    Some(delay) => delay,
+                    None => return Err(error),
+                };
+                retry_policy.notify_retry(&RetryEvent {
+                    stream_ids: &self.stream_id_vec,
+                    window_begin,
+                    window_end,
+                    retry,
+                    delay,
+                    error: &error,
+                });
+                sleep(delay).await;
+            }
 
-            while let Some(row) = rows_stream.next().await {
-                consumer
-                    .consume_cdc(CDCRow::from_row(row?, &schema))
-                    .await?;
+            *self.window_end.lock().unwrap() = Some(window_end);
+            permit.release();
+            #[cfg(feature = "metrics")]
+            {
+                crate::metrics::window_fetched(keyspace);
+                let now = chrono::Duration::milliseconds(chrono::Local::now().timestamp_millis());
+                crate::metrics::set_stream_group_lag(
+                    keyspace,
+                    table_names,
+                    &self.stream_id_vec,
+                    max(now - window_end, chrono::Duration::zero()),
+                );
             }
 
-            if let Some(timestamp_to_stop) = self.upper_timestamp.lock().await.as_ref() {
-                if window_end >= *timestamp_to_stop {
-                    break;
-                }
+            let is_last_window = match self.upper_timestamp.lock().await.as_ref() {
+                Some(timestamp_to_stop) => window_end >= *timestamp_to_stop,
+                None => false,
+            };
+            sink.send(
+                WindowItem::End {
+                    window_end,
+                    is_last_window,
+                },
+                &mut permit,
+            )
+            .await?;
+            if is_last_window {
+                break;
             }
 
             window_begin = window_end;
-            checkpoint.timestamp = window_begin.to_std()?;
-            sender.send(checkpoint.clone())?;
             sleep(self.config.sleep_interval).await;
         }
 
-        if self.config.should_save_progress {
-            self.config
-                .checkpoint_saver
-                .as_ref()
-                .unwrap()
-                .save_checkpoint(&checkpoint)
-                .await?;
+        Ok(())
+    }
+
+    // Reads rows of the window from all the tables and passes them to the sink,
+    // skipping the rows that were passed in the previous attempts.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_window(
+        &self,
+        keyspace: &str,
+        table_names: &[String],
+        window_begin: chrono::Duration,
+        window_end: chrono::Duration,
+        sink: &mut WindowSink<'_, '_>,
+        permit: &mut ReaderPermit<'_>,
+        sent_rows: &mut [HashMap<StreamID, RowPosition>],
+    ) -> Result<(), WindowError> {
+        for (table_index, table_name) in table_names.iter().enumerate() {
+            let is_last_table = table_index + 1 == table_names.len();
+            #[cfg(feature = "metrics")]
+            let query_start = std::time::Instant::now();
+            let mut rows = self
+                .source
+                .fetch_rows(
+                    keyspace,
+                    table_name,
+                    &self.stream_id_vec,
+                    window_begin,
+                    window_end,
+                )
+                .await
+                .map_err(WindowError::Fetch)?;
+            #[cfg(feature = "metrics")]
+            crate::metrics::observe_query(keyspace, table_name, query_start.elapsed());
+
+            let schema = Arc::new(CDCRowSchema::new(&rows.column_specs));
+
+            while let Some(row) = rows.rows.next().await {
+                let row = row.map_err(WindowError::Fetch)?;
+
+                // Keeping track of the sent rows is needed only if the window can be read again.
+                if self.config.retry_policy.is_some() {
+                    if let Some(position) = row_position(&row, &schema) {
+                        let sent = &mut sent_rows[table_index];
+                        if sent
+                            .get(&position.stream_id)
+                            .is_some_and(|sent| position <= *sent)
+                        {
+                            continue;
+                        }
+                        sent.insert(position.stream_id.clone(), position);
+                    }
+                }
+
+                sink.send(
+                    WindowItem::Row {
+                        row,
+                        schema: Arc::clone(&schema),
+                        is_last_table,
+                    },
+                    permit,
+                )
+                .await
+                .map_err(WindowError::Consume)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum WindowError {
+    // Reading rows from the source failed, so the window may be read again.
+    Fetch(anyhow::Error),
+    // Passing rows to the consumer failed.
+    Consume(anyhow::Error),
+}
+
+// Rows of different changes may share the timestamp,
+// so they are told apart by the whole `cdc$time` together with `cdc$batch_seq_no`.
+fn row_position(row: &Row, schema: &CDCRowSchema) -> Option<RowPosition> {
+    let column = |i: usize| row.columns.get(i).and_then(|column| column.as_ref());
+
+    Some(RowPosition {
+        stream_id: StreamID::new(column(schema.stream_id)?.as_blob()?.clone()),
+        time: column(schema.time)?.as_uuid()?,
+        batch_seq_no: column(schema.batch_seq_no)?.as_int()?,
+    })
+}
+
+// Rows of a window, followed by the end of the window.
+enum WindowItem {
+    Row {
+        row: Row,
+        schema: Arc<CDCRowSchema>,
+        is_last_table: bool,
+    },
+    End {
+        window_end: chrono::Duration,
+        is_last_window: bool,
+    },
+}
+
+// Receives the fetched rows, either by consuming them right away
+// or by passing them to the consuming task.
+enum WindowSink<'a, 'b> {
+    Direct(&'b mut WindowConsumer<'a>),
+    Channel(mpsc::Sender<WindowItem>),
+}
+
+impl WindowSink<'_, '_> {
+    async fn send(
+        &mut self,
+        item: WindowItem,
+        permit: &mut ReaderPermit<'_>,
+    ) -> anyhow::Result<()> {
+        let stopped = || anyhow::anyhow!("the consuming task has stopped");
+        match self {
+            WindowSink::Direct(window_consumer) => window_consumer.consume(item).await,
+            WindowSink::Channel(sender) => {
+                let slot = match sender.try_reserve() {
+                    Ok(slot) => slot,
+                    Err(TrySendError::Full(())) => {
+                        // Other readers may read their windows while this one waits for the consumer.
+                        let had_permit = permit.release();
+                        let slot = sender.reserve().await.map_err(|_| stopped())?;
+                        if had_permit {
+                            permit.acquire().await?;
+                        }
+                        slot
+                    }
+                    Err(TrySendError::Closed(())) => return Err(stopped()),
+                };
+                slot.send(item);
+                Ok(())
+            }
+        }
+    }
+}
+
+// A permit to read a window, taken from the permits shared by all the readers.
+// Without the limit on concurrent readers, acquiring it doesn't wait.
+struct ReaderPermit<'a> {
+    permits: Option<&'a Semaphore>,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl<'a> ReaderPermit<'a> {
+    fn new(permits: Option<&'a Semaphore>) -> Self {
+        ReaderPermit {
+            permits,
+            permit: None,
+        }
+    }
+
+    async fn acquire(&mut self) -> anyhow::Result<()> {
+        if let Some(permits) = self.permits {
+            self.permit = Some(permits.acquire().await?);
+        }
+        Ok(())
+    }
+
+    // Returns whether the permit was held.
+    fn release(&mut self) -> bool {
+        self.permit.take().is_some()
+    }
+}
+
+// Passes the rows to the consumer and keeps track of the positions of the streams.
+struct WindowConsumer<'a> {
+    reader: &'a StreamReader,
+    consumer: Box<dyn Consumer>,
+    positions: &'a watch::Sender<Vec<Checkpoint>>,
+    stream_indexes: HashMap<&'a StreamID, usize>,
+    // Positions of the streams loaded from the checkpoints.
+    // The earlier rows were consumed before a restart.
+    loaded_positions: Vec<time::Duration>,
+    acknowledgement_tracker: AcknowledgementTracker,
+    consumed_positions: Vec<RowPosition>,
+}
+
+impl<'a> WindowConsumer<'a> {
+    fn new(
+        reader: &'a StreamReader,
+        consumer: Box<dyn Consumer>,
+        positions: &'a watch::Sender<Vec<Checkpoint>>,
+    ) -> Self {
+        let stream_indexes = reader
+            .stream_id_vec
+            .iter()
+            .enumerate()
+            .map(|(i, stream)| (stream, i))
+            .collect();
+        let loaded_positions = positions
+            .borrow()
+            .iter()
+            .map(|checkpoint| checkpoint.timestamp)
+            .collect();
+
+        WindowConsumer {
+            reader,
+            consumer,
+            positions,
+            stream_indexes,
+            loaded_positions,
+            acknowledgement_tracker: AcknowledgementTracker::default(),
+            consumed_positions: vec![],
+        }
+    }
+
+    async fn consume(&mut self, item: WindowItem) -> anyhow::Result<()> {
+        match item {
+            WindowItem::Row {
+                row,
+                schema,
+                is_last_table,
+            } => {
+                self.consume_row(CDCRow::from_row(row, &schema), is_last_table)
+                    .await
+            }
+            WindowItem::End {
+                window_end,
+                is_last_window,
+            } => self.finish_window(window_end, is_last_window),
+        }
+    }
+
+    async fn consume_row(&mut self, row: CDCRow<'_>, is_last_table: bool) -> anyhow::Result<()> {
+        let save_only_acknowledged_progress = self.reader.config.save_only_acknowledged_progress;
+        let stream_index = self.stream_indexes.get(&row.stream_id).copied();
+        let time = row.time;
+        let end_of_batch = row.end_of_batch;
+
+        if let Some(i) = stream_index {
+            if let Some(timestamp) = time_to_timestamp(&time) {
+                if timestamp.to_std()? < self.loaded_positions[i] {
+                    return Ok(());
+                }
+            }
+        }
+
+        if save_only_acknowledged_progress {
+            self.consumed_positions.push(row.position());
+        }
+        #[cfg(feature = "metrics")]
+        crate::metrics::row_consumed(row.keyspace(), row.table_name(), &row.operation);
+        self.consume_with_policy(row).await?;
+
+        // Rows of a stream from the earlier tables are consumed up to the end of the window,
+        // so the position of the stream may be advanced only while reading the last table.
+        // Other changes of the stream may share the timestamp of the consumed one,
+        // so the stream moves only up to it, and the later changes are still read after a restart.
+        if is_last_table && end_of_batch && !save_only_acknowledged_progress {
+            if let (Some(i), Some(timestamp)) = (stream_index, time_to_timestamp(&time)) {
+                let timestamp = timestamp.to_std()?;
+                self.positions.send_modify(|checkpoints| {
+                    checkpoints[i].timestamp = max(checkpoints[i].timestamp, timestamp);
+                });
+            }
         }
 
         Ok(())
     }
+
+    // Passes the row to the consumer and handles its errors according to the error policy.
+    async fn consume_with_policy(&mut self, row: CDCRow<'_>) -> anyhow::Result<()> {
+        let time = row.time;
+        self.reader
+            .config
+            .error_policy
+            .run(&mut self.consumer, time, row)
+            .await
+    }
+
+    fn finish_window(
+        &mut self,
+        window_end: chrono::Duration,
+        is_last_window: bool,
+    ) -> anyhow::Result<()> {
+        *self.reader.read_timestamp.lock().unwrap() = window_end;
+
+        if self.reader.config.save_only_acknowledged_progress {
+            let consumed_positions = std::mem::take(&mut self.consumed_positions);
+            self.acknowledgement_tracker
+                .push_window(window_end, consumed_positions);
+            if let Some(acknowledged_end) = self
+                .acknowledgement_tracker
+                .acknowledge(self.consumer.acknowledged_position())
+            {
+                advance_positions(self.positions, acknowledged_end.to_std()?);
+            }
+            let max_windows = self.reader.config.max_unacknowledged_windows;
+            if self.acknowledgement_tracker.len() > max_windows {
+                anyhow::bail!(
+                    "The consumer didn't acknowledge rows of more than {} windows",
+                    max_windows
+                );
+            }
+            if is_last_window {
+                self.reader
+                    .has_unacknowledged_rows
+                    .store(!self.acknowledgement_tracker.is_empty(), Ordering::SeqCst);
+            }
+        } else if !is_last_window {
+            advance_positions(self.positions, window_end.to_std()?);
+        }
+
+        Ok(())
+    }
+}
+
+// Moves the positions of all the streams that are behind the given timestamp to it.
+fn advance_positions(positions: &watch::Sender<Vec<Checkpoint>>, timestamp: time::Duration) {
+    positions.send_modify(|checkpoints| {
+        for checkpoint in checkpoints.iter_mut() {
+            checkpoint.timestamp = max(checkpoint.timestamp, timestamp);
+        }
+    });
+}
+
+fn time_to_timestamp(time: &uuid::Uuid) -> Option<chrono::Duration> {
+    let (seconds, nanoseconds) = time.get_timestamp()?.to_unix();
+    Some(
+        chrono::Duration::seconds(seconds as i64)
+            + chrono::Duration::nanoseconds(nanoseconds as i64),
+    )
 }
 
 #[cfg(test)]
@@ -168,10 +727,17 @@ mod tests {
     use async_trait::async_trait;
     use futures::stream::StreamExt;
     use scylla::query::Query;
+    use scylla::Session;
     use scylla_cdc_test_utils::{now, populate_simple_db_with_pk, prepare_simple_db, TEST_TABLE};
     use tokio::sync::Mutex;
 
     use super::*;
+    use crate::consumer::OperationType;
+    use crate::error_policy::DeadLetterSink;
+    use crate::in_memory_log_source::{InMemoryLogRow, InMemoryLogSource};
+    use crate::log_source::{CDCLogRows, ScyllaLogSource};
+    use scylla::frame::response::result::{ColumnType, CqlValue};
+    use scylla::transport::errors::QueryError;
 
     const SECOND_IN_MILLIS: u64 = 1_000;
     const SLEEP_INTERVAL: u64 = SECOND_IN_MILLIS / 10;
@@ -181,7 +747,7 @@ mod tests {
 
     impl StreamReader {
         fn test_new(
-            session: &Arc<Session>,
+            source: &Arc<dyn CDCLogSource>,
             stream_ids: Vec<StreamID>,
             start_timestamp: chrono::Duration,
             window_size: time::Duration,
@@ -197,14 +763,15 @@ mod tests {
                 should_save_progress: false,
                 checkpoint_saver: None,
                 pause_between_saves: Default::default(),
+                save_only_acknowledged_progress: false,
+                max_unacknowledged_windows: usize::MAX,
+                reader_permits: None,
+                prefetch_depth: None,
+                retry_policy: None,
+                error_policy: ErrorPolicy::halt(),
             };
 
-            StreamReader {
-                session: session.clone(),
-                stream_id_vec: stream_ids,
-                upper_timestamp: Default::default(),
-                config,
-            }
+            StreamReader::new(source, stream_ids, config)
         }
     }
 
@@ -216,8 +783,9 @@ mod tests {
         let window_size = time::Duration::from_millis(WINDOW_SIZE);
         let safety_interval = time::Duration::from_millis(SAFETY_INTERVAL);
 
+        let source: Arc<dyn CDCLogSource> = Arc::new(ScyllaLogSource::new(session.clone()));
         let reader = StreamReader::test_new(
-            session,
+            &source,
             stream_id_vec,
             start_timestamp,
             window_size,
@@ -291,7 +859,7 @@ mod tests {
         });
 
         cdc_reader
-            .fetch_cdc(ks, TEST_TABLE.to_string(), consumer)
+            .fetch_cdc(ks, vec![TEST_TABLE.to_string()], consumer)
             .await
             .unwrap();
 
@@ -305,14 +873,14 @@ mod tests {
 
             if pk == partition_key_1 as i32 {
                 assert_eq!(pk, partition_key_1 as i32);
-                assert_eq!(t, count1 as i32);
+                assert_eq!(t, count1);
                 assert_eq!(v.to_string(), format!("val{}", count1));
                 assert_eq!(s.to_string(), format!("static{}", count1));
                 count1 += 1;
                 row_count_with_pk1 += 1;
             } else {
                 assert_eq!(pk, partition_key_2 as i32);
-                assert_eq!(t, count2 as i32);
+                assert_eq!(t, count2);
                 assert_eq!(v.to_string(), format!("val{}", count2));
                 assert_eq!(s.to_string(), format!("static{}", count2));
                 count2 += 1;
@@ -343,7 +911,7 @@ mod tests {
         });
 
         cdc_reader
-            .fetch_cdc(ks, TEST_TABLE.to_string(), consumer)
+            .fetch_cdc(ks, vec![TEST_TABLE.to_string()], consumer)
             .await
             .unwrap();
 
@@ -393,7 +961,7 @@ mod tests {
         });
 
         cdc_reader
-            .fetch_cdc(ks, TEST_TABLE.to_string(), consumer)
+            .fetch_cdc(ks, vec![TEST_TABLE.to_string()], consumer)
             .await
             .unwrap();
 
@@ -405,4 +973,675 @@ mod tests {
             assert_eq!(s.to_string(), "static0".to_string());
         }
     }
+
+    fn push_test_row(
+        source: &InMemoryLogSource,
+        stream_id: &StreamID,
+        timestamp: chrono::Duration,
+        pk: i32,
+        t: i32,
+    ) {
+        let row = InMemoryLogRow::new(OperationType::RowInsert)
+            .value("pk", CqlValue::Int(pk))
+            .value("s", CqlValue::Text(format!("static{}", t)))
+            .value("t", CqlValue::Int(t))
+            .value("v", CqlValue::Text(format!("val{}", t)));
+        source
+            .push_change("ks", TEST_TABLE, stream_id, timestamp, vec![row])
+            .unwrap();
+    }
+
+    // Returns a source with rows of two streams, spread over several windows,
+    // and a single row after `start_timestamp` + 1200 milliseconds.
+    fn prepare_in_memory_source(
+        start_timestamp: chrono::Duration,
+    ) -> (InMemoryLogSource, Vec<StreamID>) {
+        let source = InMemoryLogSource::new();
+        source.add_table(
+            "ks",
+            TEST_TABLE,
+            &[
+                ("pk", ColumnType::Int),
+                ("s", ColumnType::Text),
+                ("t", ColumnType::Int),
+                ("v", ColumnType::Text),
+            ],
+        );
+
+        let stream_id_1 = StreamID::new(vec![1]);
+        let stream_id_2 = StreamID::new(vec![2]);
+        let millis = chrono::Duration::milliseconds;
+
+        push_test_row(&source, &stream_id_1, start_timestamp + millis(100), 0, 0);
+        push_test_row(&source, &stream_id_2, start_timestamp + millis(400), 1, 0);
+        push_test_row(&source, &stream_id_1, start_timestamp + millis(700), 0, 1);
+        push_test_row(&source, &stream_id_1, start_timestamp + millis(1000), 0, 2);
+        push_test_row(&source, &stream_id_1, start_timestamp + millis(1900), 0, 3);
+
+        (source, vec![stream_id_1, stream_id_2])
+    }
+
+    // Fails to consume the row with `pk` = 0 and `t` = 1 the given number of times.
+    struct PoisonTestConsumer {
+        fetched_rows: Arc<Mutex<Vec<(i32, i32)>>>,
+        poison_failures: usize,
+    }
+
+    #[async_trait]
+    impl Consumer for PoisonTestConsumer {
+        async fn consume_cdc(&mut self, mut data: CDCRow<'_>) -> anyhow::Result<()> {
+            let pk = data.take_value("pk").unwrap().as_int().unwrap();
+            let t = data.take_value("t").unwrap().as_int().unwrap();
+            if (pk, t) == (0, 1) && self.poison_failures > 0 {
+                self.poison_failures -= 1;
+                anyhow::bail!("Poison row");
+            }
+            self.fetched_rows.lock().await.push((pk, t));
+            Ok(())
+        }
+    }
+
+    // Reads the rows up to `start_timestamp` + 1200 milliseconds and returns their `pk` and `t` values.
+    async fn fetch_in_memory_test_rows(
+        source: &Arc<dyn CDCLogSource>,
+        stream_ids: Vec<StreamID>,
+        start_timestamp: chrono::Duration,
+        configure: impl FnOnce(&mut CDCReaderConfig),
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        fetch_in_memory_test_rows_with_poison(source, stream_ids, start_timestamp, 0, configure)
+            .await
+    }
+
+    async fn fetch_in_memory_test_rows_with_poison(
+        source: &Arc<dyn CDCLogSource>,
+        stream_ids: Vec<StreamID>,
+        start_timestamp: chrono::Duration,
+        poison_failures: usize,
+        configure: impl FnOnce(&mut CDCReaderConfig),
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut cdc_reader = StreamReader::test_new(
+            source,
+            stream_ids,
+            start_timestamp,
+            time::Duration::from_millis(WINDOW_SIZE),
+            time::Duration::from_millis(SAFETY_INTERVAL),
+            time::Duration::ZERO,
+        );
+        configure(&mut cdc_reader.config);
+        cdc_reader
+            .set_upper_timestamp(start_timestamp + chrono::Duration::milliseconds(1200))
+            .await;
+        let fetched_rows = Arc::new(Mutex::new(vec![]));
+        let consumer = Box::new(PoisonTestConsumer {
+            fetched_rows: Arc::clone(&fetched_rows),
+            poison_failures,
+        });
+
+        cdc_reader
+            .fetch_cdc("ks".to_string(), vec![TEST_TABLE.to_string()], consumer)
+            .await?;
+        assert_eq!(
+            cdc_reader.read_timestamp(),
+            start_timestamp + chrono::Duration::milliseconds(1200)
+        );
+
+        let fetched_rows = fetched_rows.lock().await.clone();
+        Ok(fetched_rows)
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_from_in_memory_source() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        let source: Arc<dyn CDCLogSource> = Arc::new(source);
+
+        // Rows are read the same way with and without prefetching.
+        for prefetch_depth in [None, Some(1), Some(16)] {
+            let fetched_rows =
+                fetch_in_memory_test_rows(&source, stream_ids.clone(), start_timestamp, |config| {
+                    config.prefetch_depth = prefetch_depth
+                })
+                .await
+                .unwrap();
+            assert_eq!(fetched_rows, vec![(0, 0), (1, 0), (0, 1), (0, 2)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_changes_with_equal_timestamps() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        // Two more changes of the first stream, written with the timestamp of its first change,
+        // e.g. by a batch or with `USING TIMESTAMP`.
+        let timestamp = start_timestamp + chrono::Duration::milliseconds(100);
+        push_test_row(&source, &stream_ids[0], timestamp, 0, 10);
+        push_test_row(&source, &stream_ids[0], timestamp, 0, 11);
+        let source: Arc<dyn CDCLogSource> = Arc::new(source);
+
+        for prefetch_depth in [None, Some(1)] {
+            let fetched_rows =
+                fetch_in_memory_test_rows(&source, stream_ids.clone(), start_timestamp, |config| {
+                    config.prefetch_depth = prefetch_depth
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                fetched_rows,
+                vec![(0, 0), (0, 10), (0, 11), (1, 0), (0, 1), (0, 2)]
+            );
+        }
+    }
+
+    // Passes the rows read from the inner source through `map_rows`.
+    struct MappedLogSource {
+        inner: InMemoryLogSource,
+        map_rows: Box<dyn Fn(CDCLogRows) -> CDCLogRows + Send + Sync>,
+    }
+
+    #[async_trait]
+    impl CDCLogSource for MappedLogSource {
+        async fn fetch_all_generations(&self) -> anyhow::Result<Vec<GenerationTimestamp>> {
+            self.inner.fetch_all_generations().await
+        }
+
+        async fn fetch_generation_by_timestamp(
+            &self,
+            time: &chrono::Duration,
+        ) -> anyhow::Result<Option<GenerationTimestamp>> {
+            self.inner.fetch_generation_by_timestamp(time).await
+        }
+
+        async fn fetch_next_generation(
+            &self,
+            generation: &GenerationTimestamp,
+        ) -> anyhow::Result<Option<GenerationTimestamp>> {
+            self.inner.fetch_next_generation(generation).await
+        }
+
+        async fn fetch_stream_ids(
+            &self,
+            generation: &GenerationTimestamp,
+        ) -> anyhow::Result<Vec<Vec<StreamID>>> {
+            self.inner.fetch_stream_ids(generation).await
+        }
+
+        async fn fetch_cdc_table_names(&self, keyspace: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.fetch_cdc_table_names(keyspace).await
+        }
+
+        async fn fetch_rows(
+            &self,
+            keyspace: &str,
+            table_name: &str,
+            stream_ids: &[StreamID],
+            window_begin: chrono::Duration,
+            window_end: chrono::Duration,
+        ) -> anyhow::Result<CDCLogRows> {
+            let rows = self
+                .inner
+                .fetch_rows(keyspace, table_name, stream_ids, window_begin, window_end)
+                .await?;
+            Ok((self.map_rows)(rows))
+        }
+    }
+
+    // Every second read of rows fails with a timeout after returning the first row.
+    fn flaky_log_source(inner: InMemoryLogSource) -> MappedLogSource {
+        let reads = std::sync::atomic::AtomicUsize::new(0);
+        MappedLogSource {
+            inner,
+            map_rows: Box::new(move |mut rows| {
+                if reads
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                    .is_multiple_of(2)
+                {
+                    let error = futures::stream::once(async {
+                        Err(anyhow::Error::new(QueryError::TimeoutError))
+                    });
+                    rows.rows = rows.rows.take(1).chain(error).boxed();
+                }
+                rows
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_retries_failed_reads() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+
+        for prefetch_depth in [None, Some(1)] {
+            let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+            let source: Arc<dyn CDCLogSource> = Arc::new(flaky_log_source(source));
+            let retries = Arc::new(std::sync::atomic::AtomicU32::new(0));
+            let retry_policy = {
+                let retries = Arc::clone(&retries);
+                RetryPolicy::new()
+                    .initial_backoff(time::Duration::from_millis(1))
+                    .on_retry(move |event| {
+                        assert_eq!(event.retry, 1);
+                        retries.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    })
+            };
+
+            // The rows passed to the consumer before a failure are not passed again.
+            let fetched_rows =
+                fetch_in_memory_test_rows(&source, stream_ids.clone(), start_timestamp, |config| {
+                    config.prefetch_depth = prefetch_depth;
+                    config.retry_policy = Some(retry_policy);
+                })
+                .await
+                .unwrap();
+            assert_eq!(fetched_rows, vec![(0, 0), (1, 0), (0, 1), (0, 2)]);
+            // Reading every window failed once.
+            assert_eq!(retries.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+            // Without the retry policy, the first failure stops the reader.
+            let result =
+                fetch_in_memory_test_rows(&source, stream_ids, start_timestamp, |config| {
+                    config.prefetch_depth = prefetch_depth
+                })
+                .await;
+            assert!(result.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn check_window_end_of_failed_reads() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        let source: Arc<dyn CDCLogSource> = Arc::new(flaky_log_source(source));
+        let cdc_reader = StreamReader::test_new(
+            &source,
+            stream_ids,
+            start_timestamp,
+            time::Duration::from_millis(WINDOW_SIZE),
+            time::Duration::from_millis(SAFETY_INTERVAL),
+            time::Duration::ZERO,
+        );
+        let consumer = Box::new(PoisonTestConsumer {
+            fetched_rows: Default::default(),
+            poison_failures: 0,
+        });
+
+        // The first window failed, so no window was fetched.
+        let result = cdc_reader
+            .fetch_cdc("ks".to_string(), vec![TEST_TABLE.to_string()], consumer)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(cdc_reader.window_end(), None);
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_retries_with_equal_timestamps() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        // The first read of the window fails after the first of the changes sharing a timestamp.
+        let timestamp = start_timestamp + chrono::Duration::milliseconds(100);
+        push_test_row(&source, &stream_ids[0], timestamp, 0, 10);
+        push_test_row(&source, &stream_ids[0], timestamp, 0, 11);
+        let source: Arc<dyn CDCLogSource> = Arc::new(flaky_log_source(source));
+
+        let fetched_rows =
+            fetch_in_memory_test_rows(&source, stream_ids, start_timestamp, |config| {
+                config.retry_policy =
+                    Some(RetryPolicy::new().initial_backoff(time::Duration::from_millis(1)))
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            fetched_rows,
+            vec![(0, 0), (0, 10), (0, 11), (1, 0), (0, 1), (0, 2)]
+        );
+    }
+
+    // Consumes the rows slowly and records how far ahead of it the rows were read from the source.
+    struct SlowTestConsumer {
+        read_rows: Arc<std::sync::atomic::AtomicUsize>,
+        consumed_rows: usize,
+        max_lead: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Consumer for SlowTestConsumer {
+        async fn consume_cdc(&mut self, _: CDCRow<'_>) -> anyhow::Result<()> {
+            self.consumed_rows += 1;
+            let lead =
+                self.read_rows.load(std::sync::atomic::Ordering::SeqCst) - self.consumed_rows;
+            self.max_lead
+                .fetch_max(lead, std::sync::atomic::Ordering::SeqCst);
+            sleep(time::Duration::from_millis(2)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_prefetch_depth() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+
+        for prefetch_depth in [None, Some(1), Some(4)] {
+            let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+            // More rows in the first window than any of the tested depths.
+            for t in 10..30 {
+                let timestamp = start_timestamp + chrono::Duration::milliseconds(100 + t as i64);
+                push_test_row(&source, &stream_ids[0], timestamp, 0, t);
+            }
+            let read_rows = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let source: Arc<dyn CDCLogSource> = Arc::new(MappedLogSource {
+                inner: source,
+                map_rows: Box::new({
+                    let read_rows = Arc::clone(&read_rows);
+                    move |mut rows| {
+                        let read_rows = Arc::clone(&read_rows);
+                        rows.rows = rows
+                            .rows
+                            .inspect(move |_| {
+                                read_rows.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            })
+                            .boxed();
+                        rows
+                    }
+                }),
+            });
+
+            let mut cdc_reader = StreamReader::test_new(
+                &source,
+                stream_ids,
+                start_timestamp,
+                time::Duration::from_millis(WINDOW_SIZE),
+                time::Duration::from_millis(SAFETY_INTERVAL),
+                time::Duration::ZERO,
+            );
+            cdc_reader.config.prefetch_depth = prefetch_depth;
+            cdc_reader
+                .set_upper_timestamp(start_timestamp + chrono::Duration::milliseconds(1200))
+                .await;
+            let max_lead = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let consumer = Box::new(SlowTestConsumer {
+                read_rows: Arc::clone(&read_rows),
+                consumed_rows: 0,
+                max_lead: Arc::clone(&max_lead),
+            });
+            cdc_reader
+                .fetch_cdc("ks".to_string(), vec![TEST_TABLE.to_string()], consumer)
+                .await
+                .unwrap();
+            assert_eq!(read_rows.load(std::sync::atomic::Ordering::SeqCst), 24);
+
+            // Besides the rows waiting in the buffer,
+            // the reader holds the row it waits to pass to the buffer.
+            let max_lead = max_lead.load(std::sync::atomic::Ordering::SeqCst);
+            match prefetch_depth {
+                None => assert_eq!(max_lead, 0),
+                Some(depth) => assert!(
+                    (depth..=depth + 1).contains(&max_lead),
+                    "read {} rows ahead with prefetch depth {}",
+                    max_lead,
+                    depth
+                ),
+            }
+        }
+    }
+
+    // Waits for `ready` before consuming the first row.
+    struct WaitingTestConsumer {
+        ready: Arc<tokio::sync::Notify>,
+        waited: bool,
+    }
+
+    #[async_trait]
+    impl Consumer for WaitingTestConsumer {
+        async fn consume_cdc(&mut self, _: CDCRow<'_>) -> anyhow::Result<()> {
+            if !self.waited {
+                self.ready.notified().await;
+                self.waited = true;
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_prefetch_releases_reader_permit() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        for t in 10..14 {
+            let timestamp = start_timestamp + chrono::Duration::milliseconds(100 + t as i64);
+            push_test_row(&source, &stream_ids[0], timestamp, 0, t);
+        }
+        let source: Arc<dyn CDCLogSource> = Arc::new(source);
+        let reader_permits = Arc::new(Semaphore::new(1));
+        let ready = Arc::new(tokio::sync::Notify::new());
+
+        // The consumer of the first reader waits for the second reader,
+        // so the first reader fills its buffer and waits for the consumer.
+        let mut waiting_reader = StreamReader::test_new(
+            &source,
+            vec![stream_ids[0].clone()],
+            start_timestamp,
+            time::Duration::from_millis(WINDOW_SIZE),
+            time::Duration::from_millis(SAFETY_INTERVAL),
+            time::Duration::ZERO,
+        );
+        waiting_reader.config.prefetch_depth = Some(1);
+        waiting_reader.config.reader_permits = Some(Arc::clone(&reader_permits));
+        waiting_reader
+            .set_upper_timestamp(start_timestamp + chrono::Duration::milliseconds(1200))
+            .await;
+        let waiting_consumer = Box::new(WaitingTestConsumer {
+            ready: Arc::clone(&ready),
+            waited: false,
+        });
+        let waiting = waiting_reader.fetch_cdc(
+            "ks".to_string(),
+            vec![TEST_TABLE.to_string()],
+            waiting_consumer,
+        );
+
+        // The second reader gets the permit while the first one waits for its consumer.
+        let other = async {
+            sleep(time::Duration::from_millis(50)).await;
+            let rows = fetch_in_memory_test_rows(
+                &source,
+                vec![stream_ids[1].clone()],
+                start_timestamp,
+                |config| config.reader_permits = Some(Arc::clone(&reader_permits)),
+            )
+            .await;
+            ready.notify_one();
+            rows
+        };
+
+        let (waiting, other) = tokio::time::timeout(time::Duration::from_secs(10), async {
+            tokio::join!(waiting, other)
+        })
+        .await
+        .expect("the readers waited for each other");
+        waiting.unwrap();
+        assert_eq!(other.unwrap(), vec![(1, 0)]);
+    }
+
+    #[derive(Default)]
+    struct TestDeadLetterSink {
+        rows: std::sync::Mutex<Vec<(i32, String)>>,
+    }
+
+    #[async_trait]
+    impl DeadLetterSink for TestDeadLetterSink {
+        async fn send_dead_letter(
+            &self,
+            mut row: CDCRow<'_>,
+            error: &anyhow::Error,
+        ) -> anyhow::Result<()> {
+            let t = row.take_value("t").unwrap().as_int().unwrap();
+            self.rows.lock().unwrap().push((t, error.to_string()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_error_policy() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        let source: Arc<dyn CDCLogSource> = Arc::new(source);
+        let fetch = |poison_failures, error_policy: ErrorPolicy| {
+            fetch_in_memory_test_rows_with_poison(
+                &source,
+                stream_ids.clone(),
+                start_timestamp,
+                poison_failures,
+                |config| config.error_policy = error_policy,
+            )
+        };
+        let all_rows = vec![(0, 0), (1, 0), (0, 1), (0, 2)];
+        let rows_without_poison = vec![(0, 0), (1, 0), (0, 2)];
+        let backoff = time::Duration::from_millis(1);
+
+        // By default, the reader stops.
+        assert!(fetch(1, ErrorPolicy::halt()).await.is_err());
+        // The retries succeed when the consumer fails fewer times than allowed.
+        assert_eq!(
+            fetch(2, ErrorPolicy::halt().with_retries(2, backoff))
+                .await
+                .unwrap(),
+            all_rows
+        );
+        assert!(fetch(3, ErrorPolicy::halt().with_retries(2, backoff))
+            .await
+            .is_err());
+        assert_eq!(
+            fetch(usize::MAX, ErrorPolicy::skip()).await.unwrap(),
+            rows_without_poison
+        );
+
+        let sink = Arc::new(TestDeadLetterSink::default());
+        assert_eq!(
+            fetch(
+                usize::MAX,
+                ErrorPolicy::dead_letter(sink.clone()).with_retries(1, backoff)
+            )
+            .await
+            .unwrap(),
+            rows_without_poison
+        );
+        assert_eq!(
+            *sink.rows.lock().unwrap(),
+            vec![(1, "Poison row".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_acknowledgement_tracker() {
+        let mut tracker = AcknowledgementTracker::default();
+        let time = |i: u128| uuid::Uuid::from_u128(i);
+        let position = |time: uuid::Uuid, batch_seq_no: i32| RowPosition {
+            stream_id: StreamID::new(vec![1]),
+            time,
+            batch_seq_no,
+        };
+        let end = chrono::Duration::seconds;
+
+        tracker.push_window(end(1), vec![position(time(1), 0), position(time(2), 0)]);
+        tracker.push_window(end(2), vec![]);
+        tracker.push_window(
+            end(3),
+            vec![
+                position(time(3), 0),
+                position(time(4), 0),
+                position(time(5), 0),
+            ],
+        );
+        assert_eq!(tracker.acknowledge(None), None);
+        // Acknowledging a row in the middle of a window.
+        assert_eq!(tracker.acknowledge(Some(position(time(1), 0))), None);
+        // Acknowledging the last row of a window acknowledges the following empty windows too.
+        assert_eq!(
+            tracker.acknowledge(Some(position(time(2), 0))),
+            Some(end(2))
+        );
+        // Acknowledging the same row again changes nothing.
+        assert_eq!(tracker.acknowledge(Some(position(time(2), 0))), None);
+
+        tracker.push_window(end(4), vec![position(time(6), 0)]);
+        tracker.push_window(end(5), vec![position(time(7), 0)]);
+        // Acknowledging a row from a later window acknowledges all the previous windows.
+        assert_eq!(
+            tracker.acknowledge(Some(position(time(6), 0))),
+            Some(end(4))
+        );
+        assert_eq!(
+            tracker.acknowledge(Some(position(time(7), 0))),
+            Some(end(5))
+        );
+
+        tracker.push_window(end(6), vec![]);
+        assert_eq!(tracker.acknowledge(None), Some(end(6)));
+        assert!(tracker.is_empty());
+
+        // Rows sharing `cdc$time` are told apart by `cdc$batch_seq_no`.
+        tracker.push_window(end(7), vec![position(time(8), 0), position(time(8), 1)]);
+        assert_eq!(tracker.acknowledge(Some(position(time(8), 0))), None);
+        assert_eq!(
+            tracker.acknowledge(Some(position(time(8), 1))),
+            Some(end(7))
+        );
+    }
+
+    #[test]
+    fn test_acknowledging_unknown_positions() {
+        let mut tracker = AcknowledgementTracker::default();
+        let time = |i: u128| uuid::Uuid::from_u128(i);
+        let position = |stream: u8, time: uuid::Uuid| RowPosition {
+            stream_id: StreamID::new(vec![stream]),
+            time,
+            batch_seq_no: 0,
+        };
+        let end = chrono::Duration::seconds;
+
+        tracker.push_window(end(1), vec![position(1, time(1)), position(2, time(2))]);
+        tracker.push_window(end(2), vec![position(1, time(3)), position(2, time(4))]);
+        // Windows without rows don't need to be tracked separately.
+        tracker.push_window(end(3), vec![]);
+        assert_eq!(tracker.len(), 2);
+
+        // Positions before all the consumed rows of their stream acknowledge nothing.
+        assert_eq!(tracker.acknowledge(Some(position(1, time(0)))), None);
+        assert_eq!(tracker.acknowledge(Some(position(3, time(9)))), None);
+        // A position between the consumed rows acknowledges the rows consumed up to the earlier one.
+        assert_eq!(
+            tracker.acknowledge(Some(position(2, time(3)))),
+            Some(end(1))
+        );
+        // A position after all the consumed rows of its stream acknowledges its last row.
+        assert_eq!(tracker.acknowledge(Some(position(1, time(9)))), None);
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(
+            tracker.acknowledge(Some(position(2, time(9)))),
+            Some(end(3))
+        );
+        assert!(tracker.is_empty());
+    }
+
+    #[tokio::test]
+    async fn check_fetch_cdc_without_acknowledgements() {
+        let start_timestamp = now() - chrono::Duration::seconds(START_TIME_DELAY_IN_SECONDS);
+        let (source, stream_ids) = prepare_in_memory_source(start_timestamp);
+        let source: Arc<dyn CDCLogSource> = Arc::new(source);
+
+        // The consumer never acknowledges its rows, which are read in 4 windows.
+        let result =
+            fetch_in_memory_test_rows(&source, stream_ids.clone(), start_timestamp, |config| {
+                config.save_only_acknowledged_progress = true;
+                config.max_unacknowledged_windows = 3;
+            })
+            .await;
+        assert!(result.is_err());
+
+        let fetched_rows =
+            fetch_in_memory_test_rows(&source, stream_ids, start_timestamp, |config| {
+                config.save_only_acknowledged_progress = true;
+                config.max_unacknowledged_windows = 4;
+            })
+            .await
+            .unwrap();
+        assert_eq!(fetched_rows, vec![(0, 0), (1, 0), (0, 1), (0, 2)]);
+    }
 }